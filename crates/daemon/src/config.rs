@@ -1,3 +1,4 @@
+use scheduler::{ClockKind, Scheduler};
 use serde::Deserialize;
 use std::fs;
 use std::path::Path;
@@ -8,6 +9,10 @@ pub struct Config {
     /// Path to watch for configuration changes.
     #[serde(default = "default_config_path")]
     pub config_path: String,
+    /// Clock the scheduler runs on: `"wall"` (the default), `"hybrid"` or
+    /// `"virtual"`.
+    #[serde(default)]
+    pub clock: ClockKind,
 }
 
 fn default_config_path() -> String {
@@ -22,4 +27,9 @@ impl Config {
         cfg.config_path = path.as_ref().to_string_lossy().to_string();
         Ok(cfg)
     }
+
+    /// Create a scheduler on the configured clock.
+    pub fn scheduler(&self) -> Scheduler {
+        Scheduler::with_clock(self.clock.build())
+    }
 }
@@ -4,13 +4,19 @@ pub mod config;
 mod signal;
 
 use config::Config;
+use scheduler::{Scheduler, SchedulerHandle};
 use signal::shutdown_channel;
 
 /// Initialize the daemon and return a running instance.
 pub fn init(cfg: Config) -> anyhow::Result<Daemon> {
-    tracing::info!("initializing daemon");
+    tracing::info!(clock = ?cfg.clock, "initializing daemon");
     let shutdown = shutdown_channel()?;
-    Ok(Daemon { cfg, shutdown })
+    let scheduler = cfg.scheduler();
+    Ok(Daemon {
+        cfg,
+        shutdown,
+        scheduler,
+    })
 }
 
 /// Convenience wrapper to initialize and immediately run the daemon.
@@ -22,16 +28,32 @@ pub fn run(cfg: Config) -> anyhow::Result<()> {
 pub struct Daemon {
     cfg: Config,
     shutdown: crossbeam::channel::Receiver<()>,
+    scheduler: Scheduler,
 }
 
 impl Daemon {
-    /// Block until a shutdown signal is received.
+    /// The scheduler, on the clock the config names.
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Handle for submitting work to the scheduler once the daemon runs.
+    pub fn handle(&self) -> SchedulerHandle {
+        self.scheduler.handle()
+    }
+
+    /// Serve the scheduler until a shutdown signal is received.
     pub fn run(self) -> anyhow::Result<()> {
         tracing::info!("daemon running");
         // Watch for config changes (stub).
         let _watcher = signal::start_watcher(&self.cfg.config_path).ok();
+        let (handle, scheduler) = self.scheduler.serve_in_background();
         // Wait for shutdown signal.
         let _ = self.shutdown.recv();
+        handle.shutdown();
+        if scheduler.join().is_err() {
+            anyhow::bail!("scheduler thread panicked");
+        }
         tracing::info!("daemon shutdown complete");
         Ok(())
     }
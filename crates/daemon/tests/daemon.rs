@@ -1,4 +1,5 @@
 use daemon::{self, config::Config};
+use scheduler::ClockKind;
 use signal_hook::consts::SIGTERM;
 use signal_hook::low_level::raise;
 use std::time::Duration;
@@ -25,3 +26,34 @@ fn init_ok() {
     let cfg = Config::load(&path).unwrap();
     daemon::init(cfg).unwrap();
 }
+
+#[test]
+fn clock_is_configurable() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "").unwrap();
+    assert_eq!(Config::load(&path).unwrap().clock, ClockKind::Wall);
+
+    std::fs::write(&path, "clock = \"hybrid\"\n").unwrap();
+    let cfg = Config::load(&path).unwrap();
+    assert_eq!(cfg.clock, ClockKind::Hybrid);
+    assert!(cfg.scheduler().snapshot().tasks.is_empty());
+}
+
+#[test]
+fn init_builds_the_scheduler_on_the_configured_clock() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "").unwrap();
+    let daemon = daemon::init(Config::load(&path).unwrap()).unwrap();
+    assert!(!daemon.scheduler().clock().is_virtual());
+
+    std::fs::write(&path, "clock = \"virtual\"\n").unwrap();
+    let cfg = Config::load(&path).unwrap();
+    assert_eq!(cfg.clock, ClockKind::Virtual);
+    let daemon = daemon::init(cfg).unwrap();
+    assert!(daemon.scheduler().clock().is_virtual());
+
+    std::fs::write(&path, "clock = \"sundial\"\n").unwrap();
+    assert!(Config::load(&path).is_err());
+}
@@ -0,0 +1,56 @@
+//! Compare the single dispatch loop with the work-stealing worker mode.
+//!
+//! Run with `cargo bench -p scheduler --bench workers`.
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use scheduler::{Channel, Scheduler, task::TaskContext};
+use std::hint::black_box;
+
+const TASKS: u64 = 64;
+const MESSAGES: u64 = 32;
+const WORKERS: usize = 4;
+
+/// CPU work done for every message so dispatch is not the only cost.
+fn churn(seed: u64) -> u64 {
+    (0..2_000u64).fold(seed, |acc, n| {
+        acc.rotate_left(5) ^ n.wrapping_mul(0x9e37_79b9)
+    })
+}
+
+/// Producer/consumer pairs exchanging messages over bounded channels.
+fn ping_pong(mut sched: Scheduler) {
+    for i in 0..TASKS {
+        unsafe {
+            sched.spawn(move |ctx: TaskContext| {
+                let chan = Channel::<u64>::bounded(&ctx, 4);
+                let producer = ctx.spawn(10, move |ctx: TaskContext| {
+                    for n in 0..MESSAGES {
+                        chan.send(&ctx, churn(i ^ n)).unwrap();
+                    }
+                    chan.close(&ctx);
+                });
+                let mut acc = 0u64;
+                while let Ok(v) = chan.recv(&ctx) {
+                    acc ^= churn(v);
+                }
+                producer.join(&ctx).unwrap();
+                black_box(acc)
+            });
+        }
+    }
+    sched.run();
+}
+
+fn bench_workers(c: &mut Criterion) {
+    // Size the coroutine pool once so both modes run on the same threads.
+    may::config().set_workers(WORKERS);
+    let mut group = c.benchmark_group("ping_pong");
+    group.sample_size(10);
+    group.bench_function("single_loop", |b| b.iter(|| ping_pong(Scheduler::new())));
+    group.bench_function(BenchmarkId::new("work_stealing", WORKERS), |b| {
+        b.iter(|| ping_pong(Scheduler::with_workers(WORKERS)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_workers);
+criterion_main!(benches);
@@ -0,0 +1,121 @@
+//! Interop between scheduler tasks and `async`/`await`.
+//!
+//! Inside a task, [`TaskContext::block_on`](crate::task::TaskContext::block_on)
+//! polls a future on the task's own coroutine. While the future is pending
+//! the task parks like any other blocked task, so the `may` worker and the
+//! scheduler keep serving others; the future's waker asks the scheduler to
+//! resume the task and poll again.
+//!
+//! In the other direction, [`SchedulerHandle::completion`] returns a future
+//! that resolves once a task has ended, so async code such as a gRPC service
+//! can await work it submitted.
+//!
+//! [`SchedulerHandle::completion`]: crate::SchedulerHandle::completion
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crossbeam::channel::Sender;
+
+use crate::handle::{Control, SchedulerHandle};
+use crate::task::{TaskId, TaskState};
+
+/// Waker of a future polled by [`TaskContext::block_on`](crate::task::TaskContext::block_on).
+pub(crate) struct TaskWaker {
+    pub tid: TaskId,
+    pub handle: SchedulerHandle,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.handle.send(Control::Wake(self.tid));
+    }
+}
+
+/// Someone waiting for a task to end.
+pub(crate) enum Subscriber {
+    Channel(Sender<TaskState>),
+    Future(Completer),
+}
+
+impl Subscriber {
+    pub fn notify(self, state: TaskState) {
+        match self {
+            Self::Channel(tx) => {
+                let _ = tx.send(state);
+            }
+            Self::Future(completer) => completer.complete(state),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    done: bool,
+    outcome: Option<TaskState>,
+    waker: Option<Waker>,
+}
+
+/// Scheduler side of a [`TaskCompletion`]. Dropping it unfulfilled resolves
+/// the future with `None`.
+pub(crate) struct Completer(Arc<Mutex<Slot>>);
+
+impl Completer {
+    pub fn complete(self, state: TaskState) {
+        self.finish(Some(state));
+    }
+
+    fn finish(&self, outcome: Option<TaskState>) {
+        let mut slot = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if slot.done {
+            return;
+        }
+        slot.done = true;
+        slot.outcome = outcome;
+        let waker = slot.waker.take();
+        drop(slot);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Completer {
+    fn drop(&mut self) {
+        self.finish(None);
+    }
+}
+
+/// Future returned by [`SchedulerHandle::completion`](crate::SchedulerHandle::completion).
+///
+/// Resolves to the task's final state, or to `None` if the scheduler did not
+/// know the task or stopped before it ended.
+pub struct TaskCompletion {
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl TaskCompletion {
+    pub(crate) fn new() -> (Self, Completer) {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        (Self { slot: slot.clone() }, Completer(slot))
+    }
+}
+
+impl Future for TaskCompletion {
+    type Output = Option<TaskState>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if slot.done {
+            Poll::Ready(slot.outcome)
+        } else {
+            slot.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Builder for spawning tasks with a name, priority, tags and limits.
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::checkpoint::{CheckpointError, TaskDescriptor};
+use crate::handle::{Control, HandleError, SchedulerHandle};
+use crate::scheduler::Scheduler;
+use crate::syscall::{SyscallReply, SystemCall, TaskFn};
+use crate::task::{JoinHandle, TaskContext, TaskId, TaskLimits, TaskMeta, TaskOutput};
+
+/// Configures a task before spawning it, either on a [`Scheduler`] via
+/// [`Scheduler::builder`], from another thread via
+/// [`SchedulerHandle::builder`] or as a child via [`TaskContext::builder`].
+///
+/// Children inherit their parent's tags; tags set on the builder win.
+pub struct TaskBuilder<S> {
+    spawner: S,
+    pri: u8,
+    meta: TaskMeta,
+    limits: TaskLimits,
+}
+
+impl<S> TaskBuilder<S> {
+    pub(crate) fn new(spawner: S) -> Self {
+        Self {
+            spawner,
+            pri: 10,
+            meta: TaskMeta::default(),
+            limits: TaskLimits::default(),
+        }
+    }
+
+    /// Name shown in logs, PAL events and snapshots.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.meta.name = Some(name.into());
+        self
+    }
+
+    /// Scheduling priority (0 = highest); defaults to 10.
+    pub fn priority(mut self, pri: u8) -> Self {
+        self.pri = pri;
+        self
+    }
+
+    /// Attach a key/value tag such as a correlation or request id.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.meta.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Attach several tags at once.
+    pub fn tags<K, V>(mut self, tags: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.meta
+            .tags
+            .extend(tags.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Cancel the task if it is still running `after` this much virtual time.
+    pub fn deadline(mut self, after: Duration) -> Self {
+        self.limits.deadline = Some(after);
+        self
+    }
+
+    /// Cancel the task when it makes more than `max` syscalls.
+    pub fn syscall_budget(mut self, max: u64) -> Self {
+        self.limits.syscalls = Some(max);
+        self
+    }
+
+    /// Cancel the task if it is still running after this much real time.
+    /// Ignored by simulated schedulers, whose runs must not depend on the
+    /// host clock.
+    pub fn wall_time_budget(mut self, budget: Duration) -> Self {
+        self.limits.wall_time = Some(budget);
+        self
+    }
+}
+
+impl TaskBuilder<&mut Scheduler> {
+    /// Spawn the task as a root task. Dropping the returned handle tells
+    /// the scheduler to forget the task's outcome.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_with_priority`].
+    pub unsafe fn spawn<F, T>(self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let Self {
+            spawner,
+            pri,
+            meta,
+            limits,
+        } = self;
+        let f: TaskFn = Box::new(move |ctx| Box::new(f(ctx)) as TaskOutput);
+        let tid = unsafe { spawner.spawn_task(pri, None, meta, limits, f) };
+        JoinHandle::owning(tid, spawner.handle())
+    }
+
+    /// Spawn a root task of the kind registered as `kind` with
+    /// [`Scheduler::register_task`], so that checkpoints include it.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_with_priority`].
+    pub unsafe fn spawn_registered(
+        self,
+        kind: &str,
+        args: &impl Serialize,
+    ) -> Result<TaskId, CheckpointError> {
+        let task = TaskDescriptor::new(kind, args)?;
+        unsafe {
+            self.spawner
+                .spawn_registered_task(self.pri, self.meta, self.limits, task)
+        }
+    }
+}
+
+impl TaskBuilder<&SchedulerHandle> {
+    /// Submit the task as a root task from any thread.
+    ///
+    /// This is safe where [`Scheduler::spawn_with_priority`] is not: the body
+    /// is `Send + 'static`, so nothing it captures can dangle or be shared
+    /// unsynchronized, and the loop starts its coroutine on a stack of the
+    /// size `may` is configured with, whose guard page turns an overflow into
+    /// an abort. The body must not hold a thread-local borrow across a
+    /// syscall, as it may resume on another thread.
+    pub fn spawn<F, T>(self, f: F) -> Result<TaskId, HandleError>
+    where
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let f: TaskFn = Box::new(move |ctx| Box::new(f(ctx)) as TaskOutput);
+        self.spawner.submit(self.pri, self.meta, self.limits, f)
+    }
+
+    /// Submit a root task of the kind registered as `kind` with
+    /// [`Scheduler::register_task`], so that checkpoints include it. Safe
+    /// for the reasons given on [`TaskBuilder::spawn`].
+    pub fn spawn_registered(
+        self,
+        kind: &str,
+        args: &impl Serialize,
+    ) -> Result<TaskId, CheckpointError> {
+        let task = TaskDescriptor::new(kind, args)?;
+        let f = self.spawner.registry.build(&task)?;
+        let Self {
+            spawner,
+            pri,
+            meta,
+            limits,
+        } = self;
+        Ok(spawner.submit_with(|tid| Control::SpawnRegistered {
+            tid,
+            pri,
+            meta,
+            limits,
+            task,
+            f,
+        })?)
+    }
+}
+
+impl TaskBuilder<&TaskContext> {
+    /// Spawn the task as a child of the calling task.
+    ///
+    /// # Safety
+    /// See [`TaskContext::spawn`].
+    pub unsafe fn spawn<F, T>(self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        match self.spawner.request(SystemCall::Spawn {
+            pri: self.pri,
+            meta: self.meta,
+            limits: self.limits,
+            f: Box::new(move |ctx| Box::new(f(ctx)) as TaskOutput),
+        }) {
+            SyscallReply::Spawned(tid) => JoinHandle::owning(tid, self.spawner.handle.clone()),
+            other => panic!("unexpected reply to Spawn: {other:?}"),
+        }
+    }
+
+    /// Spawn a child of the kind registered as `kind` with
+    /// [`Scheduler::register_task`], so that checkpoints include it.
+    ///
+    /// # Safety
+    /// See [`TaskContext::spawn`].
+    pub unsafe fn spawn_registered(
+        self,
+        kind: &str,
+        args: &impl Serialize,
+    ) -> Result<JoinHandle<()>, CheckpointError> {
+        let task = TaskDescriptor::new(kind, args)?;
+        let f = self.spawner.handle.registry.build(&task)?;
+        match self.spawner.request(SystemCall::SpawnRegistered {
+            pri: self.pri,
+            meta: self.meta,
+            limits: self.limits,
+            task,
+            f,
+        }) {
+            SyscallReply::Spawned(tid) => Ok(JoinHandle::owning(tid, self.spawner.handle.clone())),
+            other => panic!("unexpected reply to SpawnRegistered: {other:?}"),
+        }
+    }
+}
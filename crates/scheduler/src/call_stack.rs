@@ -0,0 +1,58 @@
+//! LIFO stack of nested calls made by a task.
+//!
+//! Tasks are stackful coroutines, so a sub-coroutine entered with
+//! [`TaskContext::call`](crate::task::TaskContext::call) simply runs on the
+//! caller's coroutine: its syscalls carry the caller's task id and it returns
+//! its value like a function. The stack only records the frames so the
+//! scheduler can show them in snapshots and panic reports, keeping
+//! trampolining transparent to the scheduler loop.
+use std::fmt;
+
+/// Names of the calls a task is currently inside, outermost first.
+#[derive(Clone, Debug, Default)]
+pub struct CallStack {
+    frames: Vec<String>,
+    /// Frames active when a panic started unwinding through the stack.
+    unwound: Option<Vec<String>>,
+}
+
+impl CallStack {
+    /// Enter a call named `name`.
+    pub fn push(&mut self, name: impl Into<String>) {
+        self.unwound = None;
+        self.frames.push(name.into());
+    }
+
+    /// Leave the innermost call, remembering the frames if it is unwinding.
+    pub fn pop(&mut self, unwinding: bool) -> Option<String> {
+        if unwinding && self.unwound.is_none() {
+            self.unwound = Some(self.frames.clone());
+        }
+        self.frames.pop()
+    }
+
+    /// Calls currently active, outermost first.
+    pub fn frames(&self) -> &[String] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Frames to blame for a panic: those a panic unwound through, or the
+    /// current ones.
+    pub fn panic_frames(&self) -> &[String] {
+        self.unwound.as_deref().unwrap_or(&self.frames)
+    }
+}
+
+impl fmt::Display for CallStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.frames.join(" > "))
+    }
+}
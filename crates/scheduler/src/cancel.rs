@@ -0,0 +1,88 @@
+//! Cooperative cancellation.
+//!
+//! Cancelling a task first only raises its [`CancelToken`]: blocking
+//! syscalls of the task return early with a cancellation error, and when the
+//! body returns or unwinds its cleanup hooks run on the task itself. Only if
+//! the task is still alive after the scheduler's grace period is its
+//! coroutine cancelled hard.
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::task::TaskContext;
+
+const LIVE: u8 = 0;
+const REQUESTED: u8 = 1;
+const CLEANING: u8 = 2;
+
+/// Flag telling a task that it has been asked to stop.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    state: Arc<AtomicU8>,
+}
+
+impl CancelToken {
+    /// Whether cancellation of the task was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::Acquire) != LIVE
+    }
+
+    /// Raise the flag; returns `false` if it was already raised.
+    pub(crate) fn request(&self) -> bool {
+        self.state
+            .compare_exchange(LIVE, REQUESTED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Whether the scheduler should cut the task's waits short. Waits made by
+    /// cleanup hooks are left alone.
+    pub(crate) fn aborts_waits(&self) -> bool {
+        self.state.load(Ordering::Acquire) == REQUESTED
+    }
+
+    fn begin_cleanup(&self) {
+        self.state.store(CLEANING, Ordering::Release);
+    }
+}
+
+type Hook = Box<dyn FnOnce(&TaskContext) + Send>;
+
+/// Cleanup hooks registered with [`TaskContext::on_cancel`].
+#[derive(Clone, Default)]
+pub(crate) struct CleanupHooks {
+    hooks: Arc<Mutex<Vec<Hook>>>,
+}
+
+impl CleanupHooks {
+    pub fn push(&self, hook: Hook) {
+        self.hooks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(hook);
+    }
+
+    /// Run the hooks of a cancelled task, most recently registered first.
+    pub fn run(&self, ctx: &TaskContext) {
+        ctx.cancel.begin_cleanup();
+        loop {
+            let hook = self
+                .hooks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop();
+            let Some(hook) = hook else { break };
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(ctx)));
+            if res.is_err() {
+                tracing::warn!(task = %ctx.tid, "cleanup hook panicked");
+            }
+        }
+    }
+}
+
+/// Panic payload unwinding a cancelled task out of a wait that cannot
+/// report an error.
+pub(crate) struct CancelUnwind;
+
+/// Unwind the current task because its wait was cut short by cancellation.
+pub(crate) fn unwind_cancelled() -> ! {
+    std::panic::resume_unwind(Box::new(CancelUnwind))
+}
@@ -0,0 +1,177 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::syscall::{SyscallReply, SystemCall};
+use crate::task::{TaskContext, TaskId};
+
+/// Identifier of a scheduler-managed channel.
+pub type ChannelId = u64;
+
+/// Type-erased message carried by a channel.
+pub type Message = Box<dyn Any + Send>;
+
+/// Reason a receive did not yield a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelError {
+    /// The channel was closed and its buffer is drained.
+    Closed,
+    /// No message arrived before the timeout elapsed.
+    TimedOut,
+    /// The receiving task was cancelled while waiting.
+    Cancelled,
+}
+
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => f.write_str("channel closed"),
+            Self::TimedOut => f.write_str("timed out waiting on channel"),
+            Self::Cancelled => f.write_str("cancelled while waiting on channel"),
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {}
+
+/// Error returned when sending on a closed channel; carries the message back.
+#[derive(PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+/// Scheduler-side buffer for a single channel.
+///
+/// Parked receivers and senders are tracked by the [`WaitMap`](crate::WaitMap);
+/// this only holds the data that is in flight.
+pub(crate) struct ChannelState {
+    /// Maximum buffered messages, or `None` for an unbounded channel.
+    pub cap: Option<usize>,
+    pub buf: VecDeque<Message>,
+    /// Messages of senders parked because the buffer was full.
+    pub pending: HashMap<TaskId, Message>,
+    pub closed: bool,
+}
+
+impl ChannelState {
+    pub fn new(cap: Option<usize>) -> Self {
+        Self {
+            cap,
+            buf: VecDeque::new(),
+            pending: HashMap::new(),
+            closed: false,
+        }
+    }
+
+    /// Returns `true` if another message fits in the buffer.
+    pub fn has_room(&self) -> bool {
+        self.cap.is_none_or(|cap| self.buf.len() < cap)
+    }
+}
+
+/// Typed handle to a scheduler channel.
+///
+/// Operations park only the calling coroutine; blocked receivers and
+/// senders are woken in FIFO order when the peer acts.
+pub struct Channel<T> {
+    id: ChannelId,
+    _ty: PhantomData<fn(T) -> T>,
+}
+
+impl<T: Send + 'static> Channel<T> {
+    /// Open a channel holding at most `cap` buffered messages. A capacity of
+    /// zero makes every send wait for a receiver.
+    pub fn bounded(ctx: &TaskContext, cap: usize) -> Self {
+        Self::open(ctx, Some(cap))
+    }
+
+    /// Open a channel whose sends never block.
+    pub fn unbounded(ctx: &TaskContext) -> Self {
+        Self::open(ctx, None)
+    }
+
+    fn open(ctx: &TaskContext, cap: Option<usize>) -> Self {
+        match ctx.request(SystemCall::ChannelOpen { cap }) {
+            SyscallReply::ChannelOpened(id) => Self::from_id(id),
+            other => panic!("unexpected reply to ChannelOpen: {other:?}"),
+        }
+    }
+
+    /// Wrap an existing channel id. Every user must agree on `T`.
+    pub fn from_id(id: ChannelId) -> Self {
+        Self {
+            id,
+            _ty: PhantomData,
+        }
+    }
+
+    /// Identifier of the underlying channel.
+    pub fn id(&self) -> ChannelId {
+        self.id
+    }
+
+    /// Send `value`, parking while the channel is full.
+    pub fn send(&self, ctx: &TaskContext, value: T) -> Result<(), SendError<T>> {
+        match ctx.request(SystemCall::Send {
+            chan: self.id,
+            msg: Box::new(value),
+        }) {
+            SyscallReply::Sent(Ok(())) => Ok(()),
+            SyscallReply::Sent(Err(msg)) => Err(SendError(downcast(msg))),
+            other => panic!("unexpected reply to Send: {other:?}"),
+        }
+    }
+
+    /// Receive the next message, parking until one is available.
+    pub fn recv(&self, ctx: &TaskContext) -> Result<T, ChannelError> {
+        Self::received(ctx.request(SystemCall::Recv(self.id)))
+    }
+
+    /// Like [`Channel::recv`] but give up after `dur` of scheduler time.
+    pub fn recv_timeout(&self, ctx: &TaskContext, dur: Duration) -> Result<T, ChannelError> {
+        Self::received(ctx.request(SystemCall::RecvTimeout { chan: self.id, dur }))
+    }
+
+    /// Close the channel. Buffered messages can still be received; parked
+    /// senders get their message back. The scheduler frees the channel once
+    /// its buffer is drained, so channels that are no longer needed should be
+    /// closed.
+    pub fn close(&self, ctx: &TaskContext) {
+        ctx.syscall(SystemCall::ChannelClose(self.id));
+    }
+
+    fn received(reply: SyscallReply) -> Result<T, ChannelError> {
+        match reply {
+            SyscallReply::Received(res) => res.map(downcast),
+            SyscallReply::Cancelled => Err(ChannelError::Cancelled),
+            other => panic!("unexpected reply to Recv: {other:?}"),
+        }
+    }
+}
+
+fn downcast<T: 'static>(msg: Message) -> T {
+    *msg.downcast::<T>()
+        .unwrap_or_else(|_| panic!("channel message has a different type"))
+}
+
+impl<T> Clone for Channel<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Channel<T> {}
+
+impl<T> fmt::Debug for Channel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Channel").field(&self.id).finish()
+    }
+}
@@ -0,0 +1,247 @@
+//! Checkpoints of the scheduler's logical state.
+//!
+//! Task bodies are closures and cannot be saved, so only tasks and timers
+//! spawned from a registered kind survive a restart. A kind is a named
+//! function taking serializable arguments, registered with
+//! [`Scheduler::register_task`](crate::Scheduler::register_task) and spawned
+//! with [`TaskBuilder::spawn_registered`](crate::TaskBuilder) or
+//! [`Scheduler::spawn_registered_timer`](crate::Scheduler::spawn_registered_timer).
+//!
+//! [`Scheduler::checkpoint`](crate::Scheduler::checkpoint) records those
+//! tasks with their priorities, limits, parents and what they were waiting
+//! for, the registered timers and the virtual clock.
+//! [`Scheduler::restore`](crate::Scheduler::restore) re-spawns the tasks
+//! under their old ids and re-arms the timers. A restored task starts its
+//! body over, but only once the sleep or join it was in has ended, so a task
+//! that keeps its progress in its arguments carries on where it left off.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::handle::HandleError;
+use crate::pal::Stage;
+use crate::snapshot::TaskStatus;
+use crate::syscall::TaskFn;
+use crate::task::{TaskContext, TaskId, TaskLimits, TaskMeta, TaskOutput};
+use crate::timer::{TimerId, TimerSpec};
+use crate::wait_map::WaitTarget;
+
+/// A registered kind of task and the arguments to run it with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaskDescriptor {
+    pub kind: String,
+    pub args: Value,
+}
+
+impl TaskDescriptor {
+    pub(crate) fn new(kind: &str, args: &impl Serialize) -> Result<Self, CheckpointError> {
+        let args = serde_json::to_value(args).map_err(|e| CheckpointError::BadArgs {
+            kind: kind.to_string(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self {
+            kind: kind.to_string(),
+            args,
+        })
+    }
+}
+
+/// What a task was waiting for when the checkpoint was taken.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PendingWait {
+    /// Sleeping until this virtual time.
+    Sleep { until: Duration },
+    /// Waiting for any of `targets` to end, or until `until` if set.
+    Join {
+        targets: Vec<TaskId>,
+        until: Option<Duration>,
+    },
+}
+
+impl PendingWait {
+    /// The wait a task in `status` is in, if it can outlast a restart.
+    pub(crate) fn from_status(status: &TaskStatus) -> Option<Self> {
+        match status {
+            TaskStatus::Sleeping { until } => Some(Self::Sleep { until: *until }),
+            TaskStatus::Joining { targets } => Some(Self::Join {
+                targets: targets.clone(),
+                until: None,
+            }),
+            TaskStatus::TimedWait {
+                target: WaitTarget::Task(target),
+                until,
+            } => Some(Self::Join {
+                targets: vec![*target],
+                until: Some(*until),
+            }),
+            // Channels and I/O sources do not survive a restart, but the
+            // timeout still ends the wait.
+            TaskStatus::TimedWait { until, .. } => Some(Self::Sleep { until: *until }),
+            _ => None,
+        }
+    }
+
+    /// Virtual time at which the wait ends regardless of other tasks.
+    pub(crate) fn until(&self) -> Option<Duration> {
+        match self {
+            Self::Sleep { until } => Some(*until),
+            Self::Join { until, .. } => *until,
+        }
+    }
+}
+
+/// A restartable task in a [`Checkpoint`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaskCheckpoint {
+    pub id: TaskId,
+    pub parent: Option<TaskId>,
+    pub priority: u8,
+    pub meta: TaskMeta,
+    pub limits: TaskLimits,
+    pub task: TaskDescriptor,
+    pub wait: Option<PendingWait>,
+}
+
+/// A registered timer in a [`Checkpoint`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimerCheckpoint {
+    pub id: TimerId,
+    pub spec: TimerSpec,
+    pub task: TaskDescriptor,
+    /// Virtual time of the next tick.
+    pub next_at: Option<Duration>,
+    /// Runs owed, including one for a run that was in progress.
+    pub pending: u64,
+    pub fired: u64,
+    pub skipped: u64,
+}
+
+/// Result of [`Scheduler::checkpoint`](crate::Scheduler::checkpoint).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Virtual time when the checkpoint was taken.
+    pub now: Duration,
+    /// Wall time at virtual time zero, which cron timers count from.
+    pub wall_epoch: SystemTime,
+    /// Restartable live tasks ordered by id.
+    pub tasks: Vec<TaskCheckpoint>,
+    /// Timers spawned from a registered kind, ordered by id.
+    pub timers: Vec<TimerCheckpoint>,
+    /// Live tasks that were not spawned from a registered kind and are lost
+    /// on restore.
+    pub skipped: Vec<TaskId>,
+}
+
+/// Why a registered task could not be spawned or restored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckpointError {
+    /// No task kind of this name is registered.
+    UnknownKind(String),
+    /// The arguments do not fit what the kind takes.
+    BadArgs { kind: String, reason: String },
+    /// Only a scheduler without tasks or timers can be restored.
+    NotEmpty,
+    /// A handle could not submit the task.
+    Rejected(HandleError),
+}
+
+impl From<HandleError> for CheckpointError {
+    fn from(e: HandleError) -> Self {
+        Self::Rejected(e)
+    }
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownKind(kind) => write!(f, "no task kind {kind:?} is registered"),
+            Self::BadArgs { kind, reason } => {
+                write!(f, "bad arguments for task kind {kind:?}: {reason}")
+            }
+            Self::NotEmpty => f.write_str("scheduler already has tasks or timers"),
+            Self::Rejected(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// Turns the arguments of a registered kind into a task body.
+type Factory = Arc<dyn Fn(&Value) -> Result<TaskFn, String> + Send + Sync>;
+
+/// Task kinds by name, shared by a scheduler, its handles and its tasks.
+#[derive(Clone, Default)]
+pub(crate) struct TaskRegistry {
+    kinds: Arc<Mutex<HashMap<String, Factory>>>,
+}
+
+impl TaskRegistry {
+    pub fn register<A, F>(&self, kind: String, f: F)
+    where
+        A: DeserializeOwned + Send + 'static,
+        F: Fn(TaskContext, A) + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let factory: Factory = Arc::new(move |args: &Value| {
+            let args: A = serde_json::from_value(args.clone()).map_err(|e| e.to_string())?;
+            let f = f.clone();
+            Ok(Box::new(move |ctx: TaskContext| {
+                f(ctx, args);
+                Box::new(()) as TaskOutput
+            }) as TaskFn)
+        });
+        self.kinds
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(kind, factory);
+    }
+
+    fn factory(&self, kind: &str) -> Result<Factory, CheckpointError> {
+        self.kinds
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(kind)
+            .cloned()
+            .ok_or_else(|| CheckpointError::UnknownKind(kind.to_string()))
+    }
+
+    /// Body of a task running `task`.
+    pub fn build(&self, task: &TaskDescriptor) -> Result<TaskFn, CheckpointError> {
+        let factory = self.factory(&task.kind)?;
+        factory(&task.args).map_err(|reason| CheckpointError::BadArgs {
+            kind: task.kind.clone(),
+            reason,
+        })
+    }
+
+    /// Timer body starting a task running `task` on each tick.
+    pub fn build_timer(
+        &self,
+        task: &TaskDescriptor,
+    ) -> Result<Arc<dyn Fn(TaskContext) + Send + Sync>, CheckpointError> {
+        // Fail now on arguments the kind does not take, not on every tick.
+        drop(self.build(task)?);
+        let factory = self.factory(&task.kind)?;
+        let task = task.clone();
+        Ok(Arc::new(move |ctx: TaskContext| {
+            match factory(&task.args) {
+                Ok(body) => {
+                    body(ctx);
+                }
+                Err(reason) => {
+                    tracing::error!(task = %ctx.tid, kind = task.kind, %reason, "timer could not build its task");
+                    let kind = &task.kind;
+                    ctx.report(
+                        Stage::Failed,
+                        format!("bad arguments for task kind {kind:?}: {reason}"),
+                    );
+                }
+            }
+        }))
+    }
+}
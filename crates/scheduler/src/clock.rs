@@ -1,6 +1,31 @@
+//! Time sources for the scheduler.
+//!
+//! Sleeps, timeouts, timers and restart backoff all read the scheduler's
+//! [`Clock`]. By default that is a [`TickClock`], which jumps straight to the
+//! next deadline whenever nothing is runnable, so tests and simulations run
+//! fast and reproducibly. A service that must wait real time picks a
+//! [`WallClock`] with [`Scheduler::with_clock`](crate::Scheduler::with_clock);
+//! a [`HybridClock`] also waits real time but can be pushed forward with
+//! [`SchedulerHandle::advance_clock`](crate::SchedulerHandle::advance_clock),
+//! so tests of such services need not sit out long deadlines.
 use std::time::{Duration, Instant};
 
-#[allow(unused)]
+use serde::{Deserialize, Serialize};
+
+/// Source of the scheduler's notion of "now".
+pub trait Clock: Send {
+    /// The current time.
+    fn now(&self) -> Instant;
+
+    /// Whether the scheduler may skip idle time by advancing the clock to
+    /// the next deadline instead of waiting for it.
+    fn is_virtual(&self) -> bool;
+
+    /// Move the clock forward by `dur`. Returns `false` if this clock
+    /// cannot be moved.
+    fn advance(&mut self, dur: Duration) -> bool;
+}
+
 #[derive(Clone)]
 /// Virtual clock used by the scheduler for deterministic time control.
 ///
@@ -12,7 +37,6 @@ pub struct TickClock {
 }
 
 impl TickClock {
-    #![allow(unused)]
     /// Create a new clock starting at the given instant.
     pub fn new(start: Instant) -> Self {
         Self { now: start }
@@ -28,3 +52,106 @@ impl TickClock {
         self.now += dur;
     }
 }
+
+impl Default for TickClock {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl Clock for TickClock {
+    fn now(&self) -> Instant {
+        self.now
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    fn advance(&mut self, dur: Duration) -> bool {
+        self.tick(dur);
+        true
+    }
+}
+
+/// The host's monotonic clock. Deadlines are waited out in real time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WallClock;
+
+impl Clock for WallClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn is_virtual(&self) -> bool {
+        false
+    }
+
+    fn advance(&mut self, _dur: Duration) -> bool {
+        false
+    }
+}
+
+/// The host's monotonic clock plus an offset that only grows.
+///
+/// Deadlines are waited out in real time, but [`Clock::advance`] moves the
+/// clock ahead so that a pending deadline comes due at once.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HybridClock {
+    offset: Duration,
+}
+
+impl HybridClock {
+    /// Create a clock that starts out in step with the host clock.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How far the clock has been moved ahead of the host clock.
+    pub fn offset(&self) -> Duration {
+        self.offset
+    }
+}
+
+impl Clock for HybridClock {
+    fn now(&self) -> Instant {
+        Instant::now() + self.offset
+    }
+
+    fn is_virtual(&self) -> bool {
+        false
+    }
+
+    fn advance(&mut self, dur: Duration) -> bool {
+        self.offset += dur;
+        true
+    }
+}
+
+/// Which [`Clock`] to run a scheduler on, as named in configuration files.
+///
+/// Defaults to [`ClockKind::Wall`], since a configured scheduler is usually a
+/// service that must wait real time. [`Scheduler::new`](crate::Scheduler::new)
+/// still runs on a virtual clock.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockKind {
+    /// A [`TickClock`].
+    Virtual,
+    /// A [`WallClock`].
+    #[default]
+    Wall,
+    /// A [`HybridClock`].
+    Hybrid,
+}
+
+impl ClockKind {
+    /// Create a clock of this kind, starting now.
+    pub fn build(self) -> Box<dyn Clock> {
+        match self {
+            Self::Virtual => Box::new(TickClock::default()),
+            Self::Wall => Box::new(WallClock),
+            Self::Hybrid => Box::new(HybridClock::new()),
+        }
+    }
+}
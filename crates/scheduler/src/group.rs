@@ -0,0 +1,112 @@
+//! Structured concurrency: tasks spawned into a [`TaskGroup`] never outlive it.
+//!
+//! A group is a scope owned by one parent task. Members are joined with
+//! [`TaskGroup::join_any`] or [`TaskGroup::join_all`]; any member still
+//! running when the group is cancelled or dropped is cancelled together with
+//! everything it spawned.
+use std::marker::PhantomData;
+
+use crate::cancel::unwind_cancelled;
+use crate::handle::Control;
+use crate::syscall::{SyscallReply, SystemCall};
+use crate::task::{JoinHandle, TaskContext, TaskError, TaskId, downcast_output};
+
+/// Scope of sibling tasks returning `T`, owned by the task that created it.
+pub struct TaskGroup<'a, T> {
+    ctx: &'a TaskContext,
+    /// Members not yet joined, in spawn order.
+    members: Vec<TaskId>,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Send + 'static> TaskGroup<'a, T> {
+    /// Open an empty group in the calling task.
+    pub fn new(ctx: &'a TaskContext) -> Self {
+        Self {
+            ctx,
+            members: Vec::new(),
+            _ty: PhantomData,
+        }
+    }
+
+    /// Spawn a member with the given priority.
+    ///
+    /// # Safety
+    /// Same requirements as [`TaskContext::spawn`].
+    pub unsafe fn spawn<F>(&mut self, pri: u8, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+    {
+        let handle = unsafe { self.ctx.spawn(pri, f) }.disown();
+        self.members.push(handle.id());
+        handle
+    }
+
+    /// Number of members that have not been joined yet.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns `true` if every member has been joined.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Park until any unjoined member finishes and claim its outcome.
+    ///
+    /// Returns `None` once every member has been joined. Unwinds the calling
+    /// task if it is cancelled while waiting.
+    pub fn join_any(&mut self) -> Option<(TaskId, Result<T, TaskError>)> {
+        if self.members.is_empty() {
+            return None;
+        }
+        match self.ctx.request(SystemCall::JoinAny(self.members.clone())) {
+            SyscallReply::JoinedAny(tid, res) => {
+                self.members.retain(|&m| m != tid);
+                Some((tid, res.and_then(downcast_output)))
+            }
+            SyscallReply::Cancelled => unwind_cancelled(),
+            other => panic!("unexpected reply to JoinAny: {other:?}"),
+        }
+    }
+
+    /// Park until every member finishes and return their values in spawn
+    /// order.
+    ///
+    /// The first member to fail or be cancelled cancels the remaining ones
+    /// and its error becomes the result of the whole group.
+    pub fn join_all(mut self) -> Result<Vec<T>, TaskError> {
+        let order = self.members.clone();
+        let mut values: Vec<Option<T>> = order.iter().map(|_| None).collect();
+        while let Some((tid, res)) = self.join_any() {
+            let slot = order.iter().position(|&m| m == tid).unwrap();
+            values[slot] = Some(res?);
+        }
+        Ok(values.into_iter().flatten().collect())
+    }
+
+    /// Cancel every unjoined member and all of its descendants.
+    pub fn cancel(mut self) {
+        self.cancel_members();
+    }
+}
+
+impl<T> TaskGroup<'_, T> {
+    fn cancel_members(&mut self) {
+        for tid in self.members.drain(..) {
+            self.ctx.syscall(SystemCall::Cancel(tid));
+            self.ctx.handle.send(Control::Release(tid));
+        }
+    }
+}
+
+impl<T> Drop for TaskGroup<'_, T> {
+    fn drop(&mut self) {
+        // While unwinding the scheduler already cascades the owner's failure
+        // or cancellation to its subtree.
+        if std::thread::panicking() {
+            return;
+        }
+        self.cancel_members();
+    }
+}
@@ -0,0 +1,306 @@
+//! Submitting work to and controlling a scheduler from other threads.
+//!
+//! A [`SchedulerHandle`] is cheap to clone and may be moved to any thread.
+//! Requests are queued on a control channel and applied by the run loop in
+//! order, which [`Scheduler::serve`](crate::Scheduler::serve) keeps alive
+//! until a handle asks it to drain or shut down. Queries wait for the loop
+//! to answer them.
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crossbeam::channel::{Receiver, Sender, bounded};
+#[cfg(feature = "async-io")]
+use mio::Waker;
+
+use crate::bridge::{Subscriber, TaskCompletion};
+use crate::builder::TaskBuilder;
+use crate::checkpoint::{Checkpoint, TaskDescriptor, TaskRegistry};
+use crate::pal::PalBus;
+use crate::snapshot::SchedulerSnapshot;
+use crate::syscall::TaskFn;
+use crate::task::{TaskContext, TaskId, TaskLimits, TaskMeta, TaskState};
+use crate::timer::{TimerFn, TimerId, TimerSpec};
+
+/// Request from a handle to the run loop.
+pub(crate) enum Control {
+    Spawn {
+        tid: TaskId,
+        pri: u8,
+        meta: TaskMeta,
+        limits: TaskLimits,
+        f: TaskFn,
+    },
+    /// Spawn a root task of a registered kind, so checkpoints include it.
+    SpawnRegistered {
+        tid: TaskId,
+        pri: u8,
+        meta: TaskMeta,
+        limits: TaskLimits,
+        task: TaskDescriptor,
+        f: TaskFn,
+    },
+    /// Ask a task and its descendants to stop.
+    Cancel(TaskId),
+    /// Nobody will join the task any more; forget its outcome.
+    Release(TaskId),
+    Timer {
+        id: TimerId,
+        spec: TimerSpec,
+        f: TimerFn,
+    },
+    CancelTimer(TimerId),
+    State {
+        tid: TaskId,
+        reply: Sender<Option<TaskState>>,
+    },
+    Snapshot(Sender<SchedulerSnapshot>),
+    Checkpoint(Sender<Checkpoint>),
+    /// Tell `sub` the task's final state once it ends.
+    Subscribe {
+        tid: TaskId,
+        sub: Subscriber,
+    },
+    /// A future polled by the task is ready to make progress.
+    Wake(TaskId),
+    /// Move a clock that allows it forward.
+    AdvanceClock(Duration),
+    /// Finish the tasks in flight, then return from `serve`.
+    Drain,
+    /// Cancel every live task, then return from `serve`.
+    Shutdown,
+}
+
+/// Why a handle could not submit work.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandleError {
+    /// The scheduler is draining or shutting down, or was dropped.
+    ShuttingDown,
+    /// The scheduler was dropped before answering.
+    Stopped,
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShuttingDown => f.write_str("scheduler is no longer accepting tasks"),
+            Self::Stopped => f.write_str("scheduler stopped"),
+        }
+    }
+}
+
+impl std::error::Error for HandleError {}
+
+/// `Send + Clone` handle for submitting, cancelling and observing tasks of a
+/// running scheduler; see [`Scheduler::handle`](crate::Scheduler::handle).
+///
+/// Queries block until the loop answers, so only make them while
+/// [`Scheduler::run`](crate::Scheduler::run) or
+/// [`Scheduler::serve`](crate::Scheduler::serve) is running or the scheduler
+/// has been dropped.
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    pub(crate) control: Sender<Control>,
+    /// Task ids are handed out here so a submission returns without waiting
+    /// for the loop.
+    pub(crate) next_id: Arc<AtomicU64>,
+    pub(crate) next_timer: Arc<AtomicU64>,
+    pub(crate) accepting: Arc<AtomicBool>,
+    pub(crate) pal: PalBus,
+    pub(crate) registry: TaskRegistry,
+    #[cfg(feature = "async-io")]
+    pub(crate) waker: Arc<Waker>,
+}
+
+impl SchedulerHandle {
+    /// Configure a root task to submit.
+    pub fn builder(&self) -> TaskBuilder<&Self> {
+        TaskBuilder::new(self)
+    }
+
+    /// Submit a root task with the default priority.
+    ///
+    /// Unlike [`Scheduler::spawn`](crate::Scheduler::spawn) this is safe: the
+    /// body owns everything it uses and the loop starts its coroutine; see
+    /// [`TaskBuilder::spawn`](crate::TaskBuilder) for what the body must not
+    /// do.
+    pub fn spawn<F, T>(&self, f: F) -> Result<TaskId, HandleError>
+    where
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.builder().spawn(f)
+    }
+
+    /// Ask `tid` and its descendants to stop; see [`crate::cancel`].
+    pub fn cancel(&self, tid: TaskId) -> Result<(), HandleError> {
+        if self.send(Control::Cancel(tid)) {
+            Ok(())
+        } else {
+            Err(HandleError::Stopped)
+        }
+    }
+
+    /// Register a timer; see [`Scheduler::spawn_timer`](crate::Scheduler::spawn_timer).
+    /// Each firing runs like a task submitted with [`SchedulerHandle::spawn`].
+    pub fn spawn_timer<F>(&self, spec: TimerSpec, f: F) -> Result<TimerId, HandleError>
+    where
+        F: Fn(TaskContext) + Send + Sync + 'static,
+    {
+        if !self.is_accepting() {
+            return Err(HandleError::ShuttingDown);
+        }
+        let id = self.next_timer.fetch_add(1, Ordering::Relaxed);
+        if !self.send(Control::Timer {
+            id,
+            spec,
+            f: Arc::new(f),
+        }) {
+            return Err(HandleError::ShuttingDown);
+        }
+        Ok(id)
+    }
+
+    /// Stop timer `id` from firing again.
+    pub fn cancel_timer(&self, id: TimerId) -> Result<(), HandleError> {
+        if self.send(Control::CancelTimer(id)) {
+            Ok(())
+        } else {
+            Err(HandleError::Stopped)
+        }
+    }
+
+    /// Move the scheduler's clock forward by `dur`, so that sleeps, timeouts
+    /// and timers due by then fire without waiting for them. Only clocks such
+    /// as [`HybridClock`](crate::clock::HybridClock) can be moved; others
+    /// ignore the request.
+    pub fn advance_clock(&self, dur: Duration) -> Result<(), HandleError> {
+        if self.send(Control::AdvanceClock(dur)) {
+            Ok(())
+        } else {
+            Err(HandleError::Stopped)
+        }
+    }
+
+    /// See [`Scheduler::pal`](crate::Scheduler::pal).
+    pub fn pal(&self) -> PalBus {
+        self.pal.clone()
+    }
+
+    /// Current state of `tid`, or `None` if the scheduler does not know it.
+    pub fn state(&self, tid: TaskId) -> Result<Option<TaskState>, HandleError> {
+        self.query(|reply| Control::State { tid, reply })
+    }
+
+    /// See [`Scheduler::snapshot`](crate::Scheduler::snapshot).
+    pub fn snapshot(&self) -> Result<SchedulerSnapshot, HandleError> {
+        self.query(Control::Snapshot)
+    }
+
+    /// See [`Scheduler::checkpoint`](crate::Scheduler::checkpoint).
+    pub fn checkpoint(&self) -> Result<Checkpoint, HandleError> {
+        self.query(Control::Checkpoint)
+    }
+
+    /// Receive the final state of `tid` once it ends, immediately if it
+    /// already has. The receiver disconnects without a value if the task is
+    /// unknown.
+    pub fn subscribe(&self, tid: TaskId) -> Result<Receiver<TaskState>, HandleError> {
+        let (reply, rx) = bounded(1);
+        let sub = Subscriber::Channel(reply);
+        if self.send(Control::Subscribe { tid, sub }) {
+            Ok(rx)
+        } else {
+            Err(HandleError::Stopped)
+        }
+    }
+
+    /// Future resolving to the final state of `tid` once it ends; see
+    /// [`crate::bridge`].
+    pub fn completion(&self, tid: TaskId) -> TaskCompletion {
+        let (future, completer) = TaskCompletion::new();
+        // A scheduler that is gone drops the completer, resolving the future.
+        self.send(Control::Subscribe {
+            tid,
+            sub: Subscriber::Future(completer),
+        });
+        future
+    }
+
+    /// Stop accepting tasks and let the loop return once every task in
+    /// flight has finished.
+    pub fn drain(&self) {
+        self.accepting.store(false, Ordering::Release);
+        self.send(Control::Drain);
+    }
+
+    /// Stop accepting tasks and cancel every live task; the loop returns once
+    /// they are gone.
+    pub fn shutdown(&self) {
+        self.accepting.store(false, Ordering::Release);
+        self.send(Control::Shutdown);
+    }
+
+    /// Whether submissions are still accepted.
+    pub fn is_accepting(&self) -> bool {
+        self.accepting.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn submit(
+        &self,
+        pri: u8,
+        meta: TaskMeta,
+        limits: TaskLimits,
+        f: TaskFn,
+    ) -> Result<TaskId, HandleError> {
+        self.submit_with(|tid| Control::Spawn {
+            tid,
+            pri,
+            meta,
+            limits,
+            f,
+        })
+    }
+
+    /// Queue the spawn `cmd` builds for a fresh task id.
+    pub(crate) fn submit_with(
+        &self,
+        cmd: impl FnOnce(TaskId) -> Control,
+    ) -> Result<TaskId, HandleError> {
+        if !self.is_accepting() {
+            return Err(HandleError::ShuttingDown);
+        }
+        let tid = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if !self.send(cmd(tid)) {
+            return Err(HandleError::ShuttingDown);
+        }
+        Ok(tid)
+    }
+
+    fn query<T>(&self, cmd: impl FnOnce(Sender<T>) -> Control) -> Result<T, HandleError> {
+        let (reply, rx) = bounded(1);
+        if !self.send(cmd(reply)) {
+            return Err(HandleError::Stopped);
+        }
+        rx.recv().map_err(|_| HandleError::Stopped)
+    }
+
+    /// Queue `cmd` and wake the loop; returns `false` if the scheduler is gone.
+    pub(crate) fn send(&self, cmd: Control) -> bool {
+        let sent = self.control.send(cmd).is_ok();
+        #[cfg(feature = "async-io")]
+        if sent && let Err(e) = self.waker.wake() {
+            tracing::warn!(?e, "failed to wake scheduler");
+        }
+        sent
+    }
+}
+
+impl fmt::Debug for SchedulerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SchedulerHandle")
+            .field("accepting", &self.is_accepting())
+            .finish_non_exhaustive()
+    }
+}
@@ -1,13 +1,66 @@
 use std::os::unix::io::RawFd;
 
+use serde::{Deserialize, Serialize};
+
+/// Readiness an [`IoSource`] is registered for.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IoInterest {
+    #[default]
+    Readable,
+    Writable,
+    Both,
+}
+
+/// How a readiness event is handed to the tasks waiting on one source.
+///
+/// The poller itself may report readiness either way; this only decides who
+/// wakes. Readiness that arrives while nobody waits is remembered either way,
+/// so the next wait on the source returns at once.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IoTrigger {
+    /// Every waiter wakes and re-checks the source, e.g. with a
+    /// non-blocking read; those that find nothing wait again.
+    #[default]
+    WakeAll,
+    /// Each event wakes only the longest-waiting task, which is expected to
+    /// consume everything that is ready.
+    WakeOne,
+}
+
+/// Why a task waiting in [`SystemCall::IoWaitTimeout`](crate::SystemCall::IoWaitTimeout)
+/// woke up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IoWake {
+    /// The source became ready.
+    Ready,
+    /// The timeout elapsed first.
+    TimedOut,
+    /// The source was deregistered while the task waited.
+    Deregistered,
+    /// The waiting task is being cancelled.
+    Cancelled,
+}
+
 /// Source of I/O readiness that can be registered with [`Scheduler`].
 ///
 /// Implementors provide access to the underlying file descriptor and a stable
 /// identifier used by the scheduler when waiting for readiness events.
+///
+/// [`Scheduler`]: crate::Scheduler
 pub trait IoSource: Send + Sync {
     /// Return the raw file descriptor associated with this source.
     fn raw_fd(&self) -> RawFd;
 
     /// Unique identifier for this source.
     fn id(&self) -> u64;
+
+    /// Readiness to wait for; readable by default.
+    fn interest(&self) -> IoInterest {
+        IoInterest::Readable
+    }
+
+    /// How readiness is shared among waiters; every waiter wakes by default.
+    fn trigger(&self) -> IoTrigger {
+        IoTrigger::WakeAll
+    }
 }
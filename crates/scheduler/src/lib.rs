@@ -1,18 +1,60 @@
-#![feature(coroutines)]
-
-mod clock;
+pub mod bridge;
+pub mod builder;
+pub mod call_stack;
+pub mod cancel;
+pub mod channel;
+pub mod checkpoint;
+pub mod clock;
+pub mod group;
+pub mod handle;
 pub mod io;
-mod pal;
+mod local;
+pub mod pal;
+pub mod policy;
+#[cfg(feature = "async-io")]
+pub mod process;
 pub mod ready_queue;
 pub mod scheduler;
+pub mod simulation;
+pub mod snapshot;
+pub mod supervisor;
+pub mod sync;
 pub mod syscall;
 pub mod task;
+pub mod timer;
+pub mod trace;
 mod wait_map;
+pub mod watchdog;
 
-pub use io::IoSource;
-pub use pal::{TaskEvent, emit as pal_emit};
+pub use bridge::TaskCompletion;
+pub use builder::TaskBuilder;
+pub use call_stack::CallStack;
+pub use cancel::CancelToken;
+pub use channel::{Channel, ChannelError, ChannelId, SendError};
+pub use checkpoint::{
+    Checkpoint, CheckpointError, PendingWait, TaskCheckpoint, TaskDescriptor, TimerCheckpoint,
+};
+pub use clock::{Clock, ClockKind, HybridClock, TickClock, WallClock};
+pub use group::TaskGroup;
+pub use handle::{HandleError, SchedulerHandle};
+pub use io::{IoInterest, IoSource, IoTrigger, IoWake};
+pub use pal::{PalBus, PalRecvError, PalSubscription, Stage, TaskEvent};
+pub use policy::{Aging, FairShare, SchedulingPolicy, ShareGroup, Strict};
+#[cfg(feature = "async-io")]
+pub use process::{ChildPipe, ChildProcess, ProcessId};
 pub use ready_queue::{ReadyEntry, ReadyQueue};
 pub use scheduler::Scheduler;
-pub use syscall::SystemCall;
-pub use task::{Task, TaskId};
-pub use wait_map::WaitMap;
+pub use simulation::SimOutcome;
+pub use snapshot::{SchedulerSnapshot, TaskSnapshot, TaskStatus};
+pub use supervisor::{RestartStrategy, SupervisorId, SupervisorSpec, SupervisorStatus};
+pub use syscall::{SyscallReply, SystemCall};
+pub use task::{JoinHandle, Task, TaskError, TaskId, TaskLimit, TaskLimits, TaskMeta, TaskOutput};
+pub use timer::{
+    CronError, CronSchedule, MissedTicks, Schedule, TimerId, TimerSnapshot, TimerSpec,
+};
+pub use trace::{
+    Divergence, JsonLinesSink, MemorySink, TraceCall, TraceEvent, TraceOutcome, TraceReply,
+    TraceSink, replay_trace,
+};
+pub use wait_map::{IO_PENDING_LIMIT, WaitMap, WaitTarget};
+pub use watchdog::StallReport;
@@ -0,0 +1,34 @@
+//! Task-local storage keyed by type.
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Values private to one task, shared by every clone of its
+/// [`TaskContext`](crate::task::TaskContext).
+#[derive(Clone, Default)]
+pub(crate) struct TaskLocals {
+    slots: Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send>>>>,
+}
+
+impl TaskLocals {
+    pub fn insert<T: Send + 'static>(&self, value: T) -> Option<T> {
+        let old = self
+            .slots
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Box::new(value));
+        old.map(|old| *old.downcast::<T>().expect("slot keyed by type"))
+    }
+
+    pub fn remove<T: Send + 'static>(&self) -> Option<T> {
+        let old = self.slots.lock().unwrap().remove(&TypeId::of::<T>());
+        old.map(|old| *old.downcast::<T>().expect("slot keyed by type"))
+    }
+
+    pub fn with<T: Send + 'static, R>(&self, f: impl FnOnce(Option<&mut T>) -> R) -> R {
+        let mut slots = self.slots.lock().unwrap();
+        f(slots
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|slot| slot.downcast_mut::<T>()))
+    }
+}
@@ -1,15 +1,314 @@
-//! Minimal placeholder for Process Activity Log events used in tests.
+//! Process Activity Log (PAL): a live stream of what tasks are doing.
+//!
+//! The scheduler publishes a [`TaskEvent`] whenever a task is spawned, goes
+//! to sleep, waits to join another task, or ends, and tasks add their own
+//! progress with [`TaskContext::report`](crate::task::TaskContext::report).
+//! CLIs and dashboards read the stream through a [`PalSubscription`] from
+//! [`PalBus::subscribe`].
+//!
+//! As ADR-0007 asks, the log is ephemeral and bounded. Publishing never
+//! blocks: a subscriber whose buffer is full misses events and learns how
+//! many on its next receive. The bus also keeps the latest events in a replay
+//! ring, which a new subscriber receives first. With no subscribers and the
+//! ring disabled, events are not even built.
+//!
+//! Events are stamped with the scheduler's clock, as wall time at virtual time
+//! zero plus the virtual time elapsed, so simulated runs log the same times.
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender, TryRecvError, TrySendError, bounded};
+use serde::{Deserialize, Serialize, Serializer};
+
 use crate::task::TaskId;
+use crate::timer::civil_date;
+
+/// Events a subscriber may buffer before it starts missing them.
+pub const DEFAULT_BUFFER: usize = 256;
+
+/// Events kept for subscribers that join late.
+pub const DEFAULT_REPLAY: usize = 1024;
+
+/// What a task is doing, as shown to PAL consumers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Stage {
+    /// The task was created.
+    Spawned,
+    /// The task is sleeping.
+    Sleeping,
+    /// The task waits for another task to end.
+    Joining,
+    /// The task took on an instruction to carry out.
+    ReceivedInstruction,
+    /// The task waits for a language model to answer.
+    #[serde(rename = "WaitingForLLM")]
+    WaitingForLlm,
+    /// The task started running a tool.
+    ToolExecutionStart,
+    /// A tool the task ran has finished.
+    ToolExecutionComplete,
+    /// The task shows a patch for review.
+    PreviewingPatch,
+    /// The task waits for a user to approve its work.
+    AwaitingUserApproval,
+    /// The task commits a patch.
+    CommittingPatch,
+    /// The task has nothing to do for now.
+    Idle,
+    /// The task ended on its own.
+    Completed,
+    /// The task was cancelled, or stopped for exceeding a limit.
+    Cancelled,
+    /// The task panicked.
+    Failed,
+}
+
+/// One entry of the Process Activity Log, in the schema of ADR-0007.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TaskEvent {
+    /// Wall time of the event, serialized as RFC 3339 in UTC.
+    #[serde(serialize_with = "rfc3339")]
+    pub ts: SystemTime,
+    pub task_id: TaskId,
+    pub stage: Stage,
+    /// Human-readable detail.
+    pub message: String,
+}
+
+impl TaskEvent {
+    /// An event for `task_id` that happens now.
+    pub fn new(task_id: TaskId, stage: Stage, message: impl Into<String>) -> Self {
+        Self {
+            ts: SystemTime::now(),
+            task_id,
+            stage,
+            message: message.into(),
+        }
+    }
+}
+
+fn rfc3339<S: Serializer>(ts: &SystemTime, s: S) -> Result<S::Ok, S::Error> {
+    let since = ts.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (year, month, day) = civil_date(secs / 86_400);
+    let (hour, min, sec) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    s.collect_str(&format_args!(
+        "{year:04}-{month:02}-{day:02}T{hour:02}:{min:02}:{sec:02}.{:03}Z",
+        since.subsec_millis()
+    ))
+}
+
+struct Subscriber {
+    tx: Sender<TaskEvent>,
+    /// Only events of this task, if set.
+    task: Option<TaskId>,
+    missed: Arc<AtomicU64>,
+}
+
+struct Bus {
+    subscribers: Vec<Subscriber>,
+    replay: VecDeque<TaskEvent>,
+    buffer: usize,
+    replay_len: usize,
+}
+
+/// Publish/subscribe bus of the Process Activity Log. Clones share the bus;
+/// get a scheduler's with [`Scheduler::pal`](crate::Scheduler::pal).
+#[derive(Clone)]
+pub struct PalBus {
+    bus: Arc<Mutex<Bus>>,
+}
+
+impl PalBus {
+    /// Create a bus with the default buffer and replay sizes.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_BUFFER, DEFAULT_REPLAY)
+    }
+
+    /// Create a bus whose subscribers buffer up to `buffer` events and which
+    /// replays up to `replay` recent events to new subscribers.
+    pub fn with_capacity(buffer: usize, replay: usize) -> Self {
+        Self {
+            bus: Arc::new(Mutex::new(Bus {
+                subscribers: Vec::new(),
+                replay: VecDeque::with_capacity(replay),
+                buffer: buffer.max(1),
+                replay_len: replay,
+            })),
+        }
+    }
+
+    /// Change the buffer size of later subscribers and the replay length.
+    pub fn set_capacity(&self, buffer: usize, replay: usize) {
+        let mut bus = self.lock();
+        bus.buffer = buffer.max(1);
+        bus.replay_len = replay;
+        let excess = bus.replay.len().saturating_sub(replay);
+        bus.replay.drain(..excess);
+    }
+
+    /// Send `event` to every interested subscriber without waiting for any.
+    pub fn publish(&self, event: TaskEvent) {
+        self.publish_with(|| event);
+    }
+
+    /// Like [`PalBus::publish`], but only build the event if a subscriber or
+    /// the replay ring would receive it.
+    pub fn publish_with(&self, event: impl FnOnce() -> TaskEvent) {
+        let mut bus = self.lock();
+        if bus.subscribers.is_empty() && bus.replay_len == 0 {
+            return;
+        }
+        let event = event();
+        bus.subscribers.retain(|sub| {
+            if sub.task.is_some_and(|tid| tid != event.task_id) {
+                return true;
+            }
+            match sub.tx.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    sub.missed.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+        if bus.replay_len > 0 {
+            if bus.replay.len() == bus.replay_len {
+                bus.replay.pop_front();
+            }
+            bus.replay.push_back(event);
+        }
+    }
+
+    /// Subscribe to the events of every task, starting with the replay ring.
+    pub fn subscribe(&self) -> PalSubscription {
+        self.subscribe_to(None)
+    }
+
+    /// Subscribe to the events of `tid`, starting with those still in the
+    /// replay ring.
+    pub fn subscribe_task(&self, tid: TaskId) -> PalSubscription {
+        self.subscribe_to(Some(tid))
+    }
+
+    fn subscribe_to(&self, task: Option<TaskId>) -> PalSubscription {
+        let mut bus = self.lock();
+        let replay: Vec<TaskEvent> = bus
+            .replay
+            .iter()
+            .filter(|event| task.is_none_or(|tid| tid == event.task_id))
+            .cloned()
+            .collect();
+        // The replayed events come on top of the room for live ones.
+        let (tx, rx) = bounded(bus.buffer + replay.len());
+        for event in replay {
+            let _ = tx.try_send(event);
+        }
+        let missed = Arc::new(AtomicU64::new(0));
+        bus.subscribers.push(Subscriber {
+            tx,
+            task,
+            missed: missed.clone(),
+        });
+        PalSubscription { rx, missed }
+    }
+
+    /// The events in the replay ring, oldest first.
+    pub fn recent(&self) -> Vec<TaskEvent> {
+        self.lock().replay.iter().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Bus> {
+        self.bus.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for PalBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for PalBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bus = self.lock();
+        f.debug_struct("PalBus")
+            .field("subscribers", &bus.subscribers.len())
+            .field("replay", &bus.replay.len())
+            .finish()
+    }
+}
+
+/// Why a [`PalSubscription`] returned no event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PalRecvError {
+    /// The subscriber fell behind and this many events were dropped since
+    /// its last receive. Receiving again continues with the buffered events.
+    Lagged(u64),
+    /// Every handle to the bus is gone.
+    Closed,
+}
+
+impl fmt::Display for PalRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lagged(n) => write!(f, "subscriber lagged behind by {n} events"),
+            Self::Closed => f.write_str("activity log closed"),
+        }
+    }
+}
+
+impl std::error::Error for PalRecvError {}
+
+/// Receiving end of a [`PalBus`] subscription. Dropping it unsubscribes.
+pub struct PalSubscription {
+    rx: Receiver<TaskEvent>,
+    missed: Arc<AtomicU64>,
+}
+
+impl PalSubscription {
+    /// Wait for the next event.
+    pub fn recv(&self) -> Result<TaskEvent, PalRecvError> {
+        self.check_lag()?;
+        self.rx.recv().map_err(|_| PalRecvError::Closed)
+    }
+
+    /// Take the next event if one is buffered.
+    pub fn try_recv(&self) -> Result<Option<TaskEvent>, PalRecvError> {
+        self.check_lag()?;
+        match self.rx.try_recv() {
+            Ok(event) => Ok(Some(event)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(PalRecvError::Closed),
+        }
+    }
+
+    /// Wait up to `dur` for the next event.
+    pub fn recv_timeout(&self, dur: Duration) -> Result<Option<TaskEvent>, PalRecvError> {
+        self.check_lag()?;
+        match self.rx.recv_timeout(dur) {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(PalRecvError::Closed),
+        }
+    }
 
-/// Events emitted to the Process Activity Log.
-#[allow(dead_code)]
-pub enum TaskEvent {
-    /// A task failed due to panic.
-    Failed(TaskId),
+    fn check_lag(&self) -> Result<(), PalRecvError> {
+        match self.missed.swap(0, Ordering::Relaxed) {
+            0 => Ok(()),
+            n => Err(PalRecvError::Lagged(n)),
+        }
+    }
 }
 
-/// Emit a PAL event.
-#[allow(dead_code)]
-pub fn emit(_event: TaskEvent) {
-    // Placeholder implementation
+impl fmt::Debug for PalSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PalSubscription")
+            .field("buffered", &self.rx.len())
+            .finish()
+    }
 }
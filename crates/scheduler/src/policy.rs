@@ -0,0 +1,153 @@
+//! Pluggable policies deciding which ready task runs next.
+//!
+//! A [`ReadyQueue`](crate::ReadyQueue) keeps the entries of a policy with a
+//! fixed sort key in a heap and otherwise asks its policy to pick one of the
+//! queued entries on every pop. [`Strict`] reproduces the historic ordering;
+//! [`Aging`] and [`FairShare`] prevent a stream of urgent work from starving
+//! everything else.
+use std::collections::HashMap;
+
+use crate::ready_queue::ReadyEntry;
+
+/// Identifier of a fair-share group. Tasks inherit the group of their parent;
+/// top-level tasks start in group `0`.
+pub type ShareGroup = u64;
+
+/// Chooses the next task to run from a ready queue.
+pub trait SchedulingPolicy: Send {
+    /// Return the index in `queued` of the entry to run next. `queued` is
+    /// never empty and is in no particular order.
+    fn select(&self, queued: &[ReadyEntry]) -> usize;
+
+    /// Sort key of `entry`, smallest first, for policies whose order is
+    /// fixed once an entry is queued. Queues then keep a heap instead of
+    /// calling [`SchedulingPolicy::select`] on every pop.
+    fn key(&self, _entry: &ReadyEntry) -> Option<u128> {
+        None
+    }
+
+    /// Called after `entry` was popped to run.
+    fn dispatched(&mut self, _entry: &ReadyEntry) {}
+
+    /// Fresh instance with the same configuration, used for additional
+    /// queues such as per-worker ones.
+    fn fork(&self) -> Box<dyn SchedulingPolicy>;
+}
+
+fn best_by<K: Ord>(queued: &[ReadyEntry], key: impl Fn(&ReadyEntry) -> K) -> usize {
+    queued
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, e)| key(e))
+        .map(|(i, _)| i)
+        .expect("select called on an empty queue")
+}
+
+/// Lowest priority value first, FIFO among equals.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Strict;
+
+impl SchedulingPolicy for Strict {
+    fn select(&self, queued: &[ReadyEntry]) -> usize {
+        best_by(queued, |e| (e.pri, e.seq))
+    }
+
+    fn key(&self, entry: &ReadyEntry) -> Option<u128> {
+        Some(u128::from(entry.pri) << 64 | u128::from(entry.seq))
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(*self)
+    }
+}
+
+/// Strict priority where waiting raises a task's effective priority.
+///
+/// Age is measured in scheduler sequence numbers: an entry gains one
+/// priority level for every `step` entries queued after it, so progress is
+/// guaranteed without consulting a clock.
+#[derive(Clone, Copy, Debug)]
+pub struct Aging {
+    step: u64,
+}
+
+impl Aging {
+    /// Boost waiting entries by one level per `step` newer entries.
+    pub fn new(step: u64) -> Self {
+        Self { step: step.max(1) }
+    }
+
+    fn effective(&self, entry: &ReadyEntry, newest: u64) -> u8 {
+        let boost = (newest - entry.seq) / self.step;
+        entry.pri.saturating_sub(boost.min(u8::MAX as u64) as u8)
+    }
+}
+
+impl SchedulingPolicy for Aging {
+    fn select(&self, queued: &[ReadyEntry]) -> usize {
+        let newest = queued.iter().map(|e| e.seq).max().unwrap_or(0);
+        best_by(queued, |e| (self.effective(e, newest), e.seq))
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(*self)
+    }
+}
+
+/// Charge per dispatch for a group of weight 1.
+const UNIT: u64 = 1 << 16;
+
+/// Weighted fair share between [`ShareGroup`]s.
+///
+/// Each dispatch charges the task's group `1 / weight`; the group with the
+/// least charge runs next, strict priority deciding within it. A group that
+/// was idle resumes at the charge of the last dispatched group instead of
+/// cashing in the time it did not use.
+#[derive(Clone, Debug, Default)]
+pub struct FairShare {
+    weights: HashMap<ShareGroup, u32>,
+    charged: HashMap<ShareGroup, u64>,
+    floor: u64,
+}
+
+impl FairShare {
+    /// Every group starts with weight 1.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give `group` `weight` shares relative to the others.
+    pub fn weight(mut self, group: ShareGroup, weight: u32) -> Self {
+        self.weights.insert(group, weight.max(1));
+        self
+    }
+
+    fn charge_of(&self, group: ShareGroup) -> u64 {
+        self.charged
+            .get(&group)
+            .copied()
+            .unwrap_or(0)
+            .max(self.floor)
+    }
+}
+
+impl SchedulingPolicy for FairShare {
+    fn select(&self, queued: &[ReadyEntry]) -> usize {
+        best_by(queued, |e| (self.charge_of(e.group()), e.pri, e.seq))
+    }
+
+    fn dispatched(&mut self, entry: &ReadyEntry) {
+        let charge = self.charge_of(entry.group());
+        self.floor = charge;
+        let weight = self.weights.get(&entry.group()).copied().unwrap_or(1);
+        self.charged
+            .insert(entry.group(), charge + UNIT / u64::from(weight));
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self {
+            weights: self.weights.clone(),
+            ..Self::default()
+        })
+    }
+}
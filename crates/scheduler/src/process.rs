@@ -0,0 +1,168 @@
+//! Child processes driven by the scheduler's poll loop.
+//!
+//! Waiting on a child with [`std::process::Child::wait`] inside a task would
+//! block the `may` worker running it. Instead a task hands its
+//! [`Command`](std::process::Command) to the scheduler with
+//! [`TaskContext::spawn_process`]. The scheduler watches the child through a
+//! pidfd polled next to the other I/O sources. Piped stdout and stderr become
+//! non-blocking [`ChildPipe`]s registered as I/O sources, so a task can stream
+//! them with [`ChildPipe::read`] and await the exit with
+//! [`ChildProcess::wait`] while other tasks keep running.
+//!
+//! A child still running when the task that spawned it ends is killed and
+//! reaped in the background.
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::process::{Child, ExitStatus};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mio::Token;
+
+use crate::io::{IoSource, IoWake};
+use crate::syscall::{SyscallReply, SystemCall};
+use crate::task::{TaskContext, TaskId};
+
+/// Operating-system id of a child process.
+pub type ProcessId = u32;
+
+/// First I/O source id handed to child pipes. Ids of user-registered
+/// [`IoSource`]s must stay below it.
+pub const PIPE_ID_BASE: u64 = 1 << 63;
+
+/// A pipe is no longer watched once its process has been waited for, so a
+/// reader re-checks it now and then instead of relying on readiness alone.
+const RECHECK: Duration = Duration::from_secs(1);
+
+/// Non-blocking end of a child's stdout or stderr.
+#[derive(Debug)]
+pub struct ChildPipe {
+    file: File,
+    id: u64,
+}
+
+impl ChildPipe {
+    pub(crate) fn new(fd: OwnedFd, id: u64) -> io::Result<Self> {
+        set_nonblocking(fd.as_raw_fd())?;
+        Ok(Self {
+            file: File::from(fd),
+            id,
+        })
+    }
+
+    /// Read what the child has written, waiting without blocking the worker
+    /// until something arrives. Returns `Ok(0)` once the child closed the pipe.
+    pub fn read(&self, ctx: &TaskContext, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match (&self.file).read(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                res => return res,
+            }
+            if ctx.io_wait_timeout(self.id, RECHECK) == IoWake::Cancelled {
+                return Err(cancelled());
+            }
+        }
+    }
+
+    /// Read until the child closes the pipe, appending to `buf`.
+    pub fn read_to_end(&self, ctx: &TaskContext, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let mut chunk = [0u8; 4096];
+        let start = buf.len();
+        loop {
+            match self.read(ctx, &mut chunk)? {
+                0 => return Ok(buf.len() - start),
+                n => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+}
+
+impl IoSource for ChildPipe {
+    fn raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+/// A child started by [`TaskContext::spawn_process`].
+#[derive(Debug)]
+pub struct ChildProcess {
+    pid: ProcessId,
+    /// The child's stdout, if the command piped it.
+    pub stdout: Option<Arc<ChildPipe>>,
+    /// The child's stderr, if the command piped it.
+    pub stderr: Option<Arc<ChildPipe>>,
+}
+
+impl ChildProcess {
+    pub(crate) fn new(
+        pid: ProcessId,
+        stdout: Option<Arc<ChildPipe>>,
+        stderr: Option<Arc<ChildPipe>>,
+    ) -> Self {
+        Self {
+            pid,
+            stdout,
+            stderr,
+        }
+    }
+
+    /// Operating-system id of the child.
+    pub fn id(&self) -> ProcessId {
+        self.pid
+    }
+
+    /// Wait for the child to exit. Only the first wait after the exit gets
+    /// the status; later ones fail with [`io::ErrorKind::NotFound`].
+    pub fn wait(&self, ctx: &TaskContext) -> io::Result<ExitStatus> {
+        match ctx.request(SystemCall::WaitProcess(self.pid)) {
+            SyscallReply::ProcessExited(res) => res,
+            SyscallReply::Cancelled => Err(cancelled()),
+            other => panic!("unexpected reply to WaitProcess: {other:?}"),
+        }
+    }
+}
+
+/// Scheduler-side record of a child.
+pub(crate) struct ProcessState {
+    pub child: Child,
+    pub pidfd: OwnedFd,
+    pub token: Token,
+    /// Task that spawned the child.
+    pub owner: TaskId,
+    /// Ids of the child's registered pipes.
+    pub pipes: Vec<u64>,
+    /// Set once the child has been reaped.
+    pub status: Option<ExitStatus>,
+    pub waiters: Vec<TaskId>,
+    /// Sent `SIGKILL` because its owner ended; forgotten once reaped.
+    pub killed: bool,
+}
+
+/// Open a descriptor that becomes readable once `pid` exits.
+pub(crate) fn pidfd_open(pid: ProcessId) -> io::Result<OwnedFd> {
+    // SAFETY: pidfd_open takes no pointers and returns a new descriptor or -1.
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor was just created and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    // SAFETY: fcntl on a descriptor we own, without pointer arguments.
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn cancelled() -> io::Error {
+    io::Error::other("task cancelled")
+}
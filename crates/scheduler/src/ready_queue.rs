@@ -1,6 +1,7 @@
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashSet};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
 
+use crate::policy::{SchedulingPolicy, ShareGroup, Strict};
 use crate::task::TaskId;
 
 /// Entry in the ready queue representing a runnable task.
@@ -12,6 +13,30 @@ pub struct ReadyEntry {
     pub seq: u64,
     /// Identifier of the runnable task.
     pub tid: TaskId,
+    group: ShareGroup,
+}
+
+impl ReadyEntry {
+    /// Entry for `tid` in fair-share group `0`.
+    pub fn new(pri: u8, seq: u64, tid: TaskId) -> Self {
+        Self {
+            pri,
+            seq,
+            tid,
+            group: 0,
+        }
+    }
+
+    /// Charge the entry to fair-share `group` instead.
+    pub fn in_group(mut self, group: ShareGroup) -> Self {
+        self.group = group;
+        self
+    }
+
+    /// Fair-share group the task is charged to.
+    pub fn group(&self) -> ShareGroup {
+        self.group
+    }
 }
 
 impl Ord for ReadyEntry {
@@ -29,56 +54,168 @@ impl PartialOrd for ReadyEntry {
     }
 }
 
-/// Priority queue of runnable task IDs.
-#[derive(Default)]
+/// Heap slot of a policy that orders entries by [`SchedulingPolicy::key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Keyed {
+    key: u128,
+    entry: ReadyEntry,
+    /// Pushed by [`ReadyQueue::force_push`]; popped even if the task is no
+    /// longer queued.
+    forced: bool,
+}
+
+/// Queue of runnable task IDs ordered by a [`SchedulingPolicy`].
+///
+/// Entries of a policy with a [key](SchedulingPolicy::key) sit in a heap, so
+/// pushes and pops take `O(log n)`; removed entries are skipped once they
+/// reach the top. Other policies select among all queued entries on every
+/// pop.
 pub struct ReadyQueue {
-    heap: BinaryHeap<ReadyEntry>,
-    set: HashSet<TaskId>,
+    /// Queued entry of each task.
+    queued: HashMap<TaskId, ReadyEntry>,
+    /// Entries of a keyed policy, smallest key on top. The top is always
+    /// queued.
+    heap: BinaryHeap<Reverse<Keyed>>,
+    /// Entries of a policy without keys, in no particular order.
+    entries: Vec<ReadyEntry>,
+    policy: Box<dyn SchedulingPolicy>,
+}
+
+impl Default for ReadyQueue {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ReadyQueue {
-    /// Create an empty ready queue.
+    /// Create an empty strict-priority ready queue.
     pub fn new() -> Self {
+        Self::with_policy(Box::new(Strict))
+    }
+
+    /// Create an empty ready queue ordered by `policy`.
+    pub fn with_policy(policy: Box<dyn SchedulingPolicy>) -> Self {
         Self {
+            queued: HashMap::new(),
             heap: BinaryHeap::new(),
-            set: HashSet::new(),
+            entries: Vec::new(),
+            policy,
+        }
+    }
+
+    /// Replace the policy; queued entries are kept.
+    pub fn set_policy(&mut self, policy: Box<dyn SchedulingPolicy>) {
+        let queued: Vec<ReadyEntry> = self.queued.drain().map(|(_, entry)| entry).collect();
+        self.heap.clear();
+        self.entries.clear();
+        self.policy = policy;
+        for entry in queued {
+            self.push(entry);
         }
     }
 
+    /// The policy ordering this queue.
+    pub fn policy(&self) -> &dyn SchedulingPolicy {
+        self.policy.as_ref()
+    }
+
     /// Push a task entry onto the queue.
     pub fn push(&mut self, entry: ReadyEntry) {
-        if self.set.insert(entry.tid) {
-            self.heap.push(entry);
+        if self.queued.contains_key(&entry.tid) {
+            return;
+        }
+        self.queued.insert(entry.tid, entry);
+        self.insert(entry, false);
+    }
+
+    fn insert(&mut self, entry: ReadyEntry, forced: bool) {
+        match self.policy.key(&entry) {
+            Some(key) => self.heap.push(Reverse(Keyed { key, entry, forced })),
+            None => self.entries.push(entry),
         }
     }
 
     /// Returns `true` if the queue already contains `tid`.
     pub fn contains(&self, tid: TaskId) -> bool {
-        self.set.contains(&tid)
+        self.queued.contains_key(&tid)
+    }
+
+    /// Remove `tid` from the queue, returning `true` if it was present.
+    pub fn remove(&mut self, tid: TaskId) -> bool {
+        if self.queued.remove(&tid).is_none() {
+            return false;
+        }
+        if self.heap.is_empty() {
+            self.entries.retain(|entry| entry.tid != tid);
+        } else {
+            self.skip_removed();
+        }
+        true
+    }
+
+    /// Drop heap slots of tasks that are no longer queued from the top.
+    fn skip_removed(&mut self) {
+        while let Some(Reverse(top)) = self.heap.peek() {
+            if top.forced || self.queued.get(&top.entry.tid) == Some(&top.entry) {
+                break;
+            }
+            self.heap.pop();
+        }
     }
 
     /// Pop the next task ID from the queue.
     pub fn pop(&mut self) -> Option<TaskId> {
-        self.heap.pop().map(|entry| {
-            self.set.remove(&entry.tid);
-            entry.tid
-        })
+        self.pop_entry().map(|entry| entry.tid)
+    }
+
+    /// Pop the next entry, keeping its priority and sequence number.
+    pub fn pop_entry(&mut self) -> Option<ReadyEntry> {
+        let entry = if let Some(Reverse(top)) = self.heap.pop() {
+            if !top.forced {
+                self.queued.remove(&top.entry.tid);
+            }
+            self.skip_removed();
+            top.entry
+        } else if self.entries.is_empty() {
+            return None;
+        } else {
+            let entry = self.entries.swap_remove(self.policy.select(&self.entries));
+            self.queued.remove(&entry.tid);
+            entry
+        };
+        self.policy.dispatched(&entry);
+        Some(entry)
+    }
+
+    /// Return the entry that would be popped next.
+    pub fn peek(&self) -> Option<&ReadyEntry> {
+        match self.heap.peek() {
+            Some(Reverse(top)) => Some(&top.entry),
+            None => {
+                (!self.entries.is_empty()).then(|| &self.entries[self.policy.select(&self.entries)])
+            }
+        }
+    }
+
+    /// Iterate over the queued entries in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &ReadyEntry> {
+        self.queued.values()
     }
 
     /// Returns `true` if the queue has no tasks.
     pub fn is_empty(&self) -> bool {
-        self.heap.is_empty()
+        self.heap.is_empty() && self.entries.is_empty()
     }
 
     /// Returns the number of tasks in the queue.
     pub fn len(&self) -> usize {
-        self.heap.len()
+        self.queued.len()
     }
 }
 
 impl ReadyQueue {
     /// Push a task ID without checking for duplicates. Used only for tests.
     pub fn force_push(&mut self, entry: ReadyEntry) {
-        self.heap.push(entry);
+        self.insert(entry, true);
     }
 }
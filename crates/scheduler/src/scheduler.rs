@@ -1,28 +1,74 @@
 use crossbeam::channel::{Receiver, RecvTimeoutError, Sender, unbounded};
-use may::coroutine::JoinHandle;
 use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap, HashSet};
-use std::sync::{Arc, Barrier};
-use std::thread::{Scope, ScopedJoinHandle};
-use std::time::{Duration, Instant};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+#[cfg(feature = "async-io")]
+use std::os::fd::{AsRawFd, OwnedFd};
+#[cfg(feature = "async-io")]
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Barrier, Mutex, Once, PoisonError};
+use std::thread::{self, Scope, ScopedJoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+#[cfg(not(feature = "async-io"))]
+use crossbeam::channel::select;
 #[cfg(feature = "async-io")]
-use mio::{Events, Interest, Poll, Token, unix::SourceFd};
+use mio::{Events, Interest, Poll, Token, Waker, unix::SourceFd};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 
-use crate::clock::TickClock;
+use crate::bridge::Subscriber;
+use crate::builder::TaskBuilder;
+use crate::call_stack::CallStack;
+use crate::cancel::{CancelToken, CleanupHooks};
+use crate::channel::{ChannelError, ChannelId, ChannelState, Message};
+use crate::checkpoint::{
+    Checkpoint, CheckpointError, PendingWait, TaskCheckpoint, TaskDescriptor, TaskRegistry,
+    TimerCheckpoint,
+};
+use crate::clock::{Clock, TickClock};
+use crate::handle::{Control, SchedulerHandle};
 #[cfg(feature = "async-io")]
-use crate::io::IoSource;
-use crate::pal::{self, TaskEvent};
+use crate::io::{IoInterest, IoSource};
+use crate::io::{IoTrigger, IoWake};
+use crate::local::TaskLocals;
+use crate::pal::{PalBus, Stage, TaskEvent};
+use crate::policy::{SchedulingPolicy, ShareGroup};
+#[cfg(feature = "async-io")]
+use crate::process::{self, ChildPipe, ChildProcess, PIPE_ID_BASE, ProcessId, ProcessState};
 use crate::ready_queue::ReadyEntry;
 use crate::ready_queue::ReadyQueue;
-use crate::syscall::SystemCall;
-use crate::task::{Task, TaskContext, TaskId, TaskState};
-use crate::wait_map::WaitMap;
+use crate::simulation::{Picker, SimOutcome, SimRng, Simulation};
+use crate::snapshot::{
+    SchedulerSnapshot, SleeperSnapshot, TaskSnapshot, TaskStatus, TimeoutSnapshot,
+};
+use crate::supervisor::{SupervisorId, SupervisorSpec, SupervisorState, SupervisorStatus};
+use crate::sync::{SyncId, SyncState};
+use crate::syscall::{SyscallReply, SystemCall, TaskFn};
+use crate::task::{
+    JoinHandle, Task, TaskContext, TaskError, TaskId, TaskLimit, TaskLimits, TaskMeta, TaskOutput,
+    TaskState, panic_message,
+};
+use crate::timer::{CronError, TimerFn, TimerId, TimerSnapshot, TimerSpec, TimerState};
+use crate::trace::{TraceCall, TraceEvent, TraceOutcome, TraceSink};
+use crate::wait_map::{WaitMap, WaitTarget};
+use crate::watchdog::{StallProbe, StallReport};
+
+mod simulate;
+mod worker;
+
+/// Tasks whose outcome a parked `JoinResult` or `JoinAny` caller will claim.
+struct ResultWait {
+    targets: Vec<TaskId>,
+    /// Reply with [`SyscallReply::JoinedAny`] naming the finished target.
+    any: bool,
+}
 
 /// Core runtime orchestrator managing runnable tasks, pending I/O events,
 /// and join waiters.
 pub struct Scheduler {
-    next_id: TaskId,
+    /// Shared with handles, which assign ids to the tasks they submit.
+    next_id: Arc<AtomicU64>,
     seq: u64,
     syscall_tx: Sender<(TaskId, SystemCall)>,
     syscall_rx: Receiver<(TaskId, SystemCall)>,
@@ -35,27 +81,155 @@ pub struct Scheduler {
     #[cfg(feature = "async-io")]
     sources: HashMap<Token, Arc<dyn IoSource>>,
     #[cfg(feature = "async-io")]
+    tokens: HashMap<u64, Token>,
+    #[cfg(feature = "async-io")]
+    processes: HashMap<ProcessId, ProcessState>,
+    /// Pidfd tokens of children that have not exited yet.
+    #[cfg(feature = "async-io")]
+    process_tokens: HashMap<Token, ProcessId>,
+    #[cfg(feature = "async-io")]
+    next_pipe_id: u64,
+    #[cfg(feature = "async-io")]
     next_token: usize,
-    clock: TickClock,
+    clock: Box<dyn Clock>,
+    /// Virtual time at which the scheduler was created.
+    epoch: Instant,
     sleepers: BinaryHeap<Reverse<(Instant, TaskId)>>,
-    timeout_waiters: BinaryHeap<Reverse<(Instant, TaskId, TaskId)>>,
+    timeout_waiters: BinaryHeap<Reverse<(Instant, TaskId, WaitTarget)>>,
     tasks: HashMap<TaskId, Task>,
     ready: ReadyQueue,
     wait_map: WaitMap,
-    cancelled: HashSet<TaskId>,
+    /// Live tasks whose owning [`JoinHandle`](crate::JoinHandle) was
+    /// dropped; their outcome is not kept once they end.
+    released: HashSet<TaskId>,
     states: HashMap<TaskId, TaskState>,
+    replies: HashMap<TaskId, SyscallReply>,
+    parents: HashMap<TaskId, TaskId>,
+    children: HashMap<TaskId, Vec<TaskId>>,
+    results: HashMap<TaskId, Result<TaskOutput, TaskError>>,
+    result_waiters: HashMap<TaskId, ResultWait>,
+    parked: HashSet<TaskId>,
+    deadlines: HashMap<TaskId, Instant>,
+    channels: HashMap<ChannelId, ChannelState>,
+    next_chan: ChannelId,
+    syncs: HashMap<SyncId, SyncState>,
+    next_sync: SyncId,
+    supervisors: HashMap<SupervisorId, SupervisorState>,
+    next_supervisor: SupervisorId,
+    /// Supervisor and child index of each supervised task.
+    supervised: HashMap<TaskId, (SupervisorId, usize)>,
+    restarts: BinaryHeap<Reverse<(Instant, SupervisorId, usize)>>,
+    timers: HashMap<TimerId, TimerState>,
+    /// Shared with handles, which register timers too.
+    next_timer: Arc<AtomicU64>,
+    /// Next tick of each timer; entries of cancelled timers linger.
+    timer_ticks: BinaryHeap<Reverse<(Instant, TimerId)>>,
+    /// Timer whose tick each running task is.
+    timer_runs: HashMap<TaskId, TimerId>,
+    /// Wall time at creation, against which cron schedules are matched.
+    wall_epoch: SystemTime,
+    /// Number of dispatch loops started by [`Scheduler::run`].
+    workers: usize,
+    /// Set when [`Scheduler::run`] steps tasks as a deterministic simulation.
+    sim: Option<Simulation>,
+    /// Receives every handled syscall and task exit when set.
+    trace: Option<Box<dyn TraceSink>>,
+    /// Time a cancelled task gets to wind down before it is cancelled hard.
+    cancel_grace: Duration,
+    hard_cancels: BinaryHeap<Reverse<(Instant, TaskId)>>,
+    /// Virtual deadlines of tasks spawned with one.
+    task_deadlines: BinaryHeap<Reverse<(Instant, TaskId)>>,
+    /// Real instants at which wall-time budgets run out.
+    wall_budgets: BinaryHeap<Reverse<(Instant, TaskId)>>,
+    /// Real instants at which I/O waits give up, with the awaited source.
+    io_timeouts: BinaryHeap<Reverse<(Instant, TaskId, u64)>>,
+    /// Current I/O timeout of each task parked on one.
+    io_deadlines: HashMap<TaskId, Instant>,
+    /// Tasks cancelled for exceeding a limit, until they end.
+    timed_out: HashMap<TaskId, TaskLimit>,
+    /// How long a resumed task may run without a syscall before the
+    /// watchdog reports it.
+    stall_timeout: Duration,
+    /// Real time at which each task was last resumed, until its next syscall.
+    resumed_at: HashMap<TaskId, Instant>,
+    /// Number of the report of each task that is stalled right now.
+    stalled: HashMap<TaskId, u64>,
+    /// The most recent stall reports, kept contiguous for
+    /// [`Scheduler::stalls`].
+    stalls: VecDeque<StallReport>,
+    /// Reports recorded so far, including those that no longer fit.
+    stalls_recorded: u64,
+    /// How many stall reports are kept.
+    stall_history: usize,
+    /// How many tasks the watchdog reports as stalled at once.
+    max_stalled: usize,
+    /// Handles waiting for a task to end.
+    subscribers: HashMap<TaskId, Vec<Subscriber>>,
+    /// Tasks parked until the waker of the future they poll fires.
+    future_waiters: HashSet<TaskId>,
+    /// Tasks whose future was woken before they parked.
+    wakeups: HashSet<TaskId>,
+    control_tx: Sender<Control>,
+    control_rx: Receiver<Control>,
+    /// Cleared once a handle asks the scheduler to drain or shut down.
+    accepting: Arc<AtomicBool>,
+    /// Process Activity Log the scheduler and its tasks publish to.
+    pal: PalBus,
+    /// Task kinds that can be spawned by name and restored.
+    registry: TaskRegistry,
+    /// What live tasks spawned from a registered kind run.
+    descriptors: HashMap<TaskId, TaskDescriptor>,
+    /// What timers spawned from a registered kind run.
+    timer_descriptors: HashMap<TimerId, TaskDescriptor>,
+    /// Restored tasks held back until the wait they were in has ended.
+    gates: HashMap<TaskId, PendingWait>,
+    #[cfg(feature = "async-io")]
+    waker: Arc<Waker>,
+    /// Set by [`Scheduler::serve`]: keep running without tasks.
+    serving: bool,
+    /// A drain or shutdown was requested.
+    stopping: bool,
+    /// How many ended tasks [`Scheduler::serve`] remembers.
+    retention: usize,
 }
 
+/// Default for [`Scheduler::set_retention`].
+const DEFAULT_RETENTION: usize = 4096;
+
+/// Default for [`Scheduler::set_cancel_grace`].
+const DEFAULT_CANCEL_GRACE: Duration = Duration::from_millis(100);
+
+/// How long a run loop waits for a task or I/O before giving up.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often an idle `async-io` loop checks for syscalls of running tasks.
+#[cfg(feature = "async-io")]
+const RUNNING_POLL: Duration = Duration::from_millis(1);
+
+/// Poll token of the waker handles use to interrupt an idle loop.
+#[cfg(feature = "async-io")]
+const WAKE_TOKEN: Token = Token(usize::MAX);
+
+/// Default for [`Scheduler::set_stall_timeout`].
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default for [`Scheduler::set_stall_history`].
+const DEFAULT_STALL_HISTORY: usize = 64;
+
 impl Scheduler {
     /// Create a new Scheduler instance.
     pub fn new() -> Self {
         let (syscall_tx, syscall_rx) = unbounded();
+        let epoch = Instant::now();
         #[cfg(not(feature = "async-io"))]
         let (io_tx, io_rx) = unbounded();
         #[cfg(feature = "async-io")]
         let poll = Poll::new().expect("poll");
+        #[cfg(feature = "async-io")]
+        let waker = Arc::new(Waker::new(poll.registry(), WAKE_TOKEN).expect("waker"));
+        let (control_tx, control_rx) = unbounded();
         Self {
-            next_id: 1,
+            next_id: Arc::new(AtomicU64::new(1)),
             seq: 0,
             syscall_tx,
             syscall_rx,
@@ -68,18 +242,238 @@ impl Scheduler {
             #[cfg(feature = "async-io")]
             sources: HashMap::new(),
             #[cfg(feature = "async-io")]
+            tokens: HashMap::new(),
+            #[cfg(feature = "async-io")]
+            processes: HashMap::new(),
+            #[cfg(feature = "async-io")]
+            process_tokens: HashMap::new(),
+            #[cfg(feature = "async-io")]
+            next_pipe_id: PIPE_ID_BASE,
+            #[cfg(feature = "async-io")]
             next_token: 0,
-            clock: TickClock::new(Instant::now()),
+            clock: Box::new(TickClock::new(epoch)),
+            epoch,
             sleepers: BinaryHeap::new(),
             timeout_waiters: BinaryHeap::new(),
             tasks: HashMap::new(),
             ready: ReadyQueue::new(),
             wait_map: WaitMap::new(),
-            cancelled: HashSet::new(),
+            released: HashSet::new(),
             states: HashMap::new(),
+            replies: HashMap::new(),
+            parents: HashMap::new(),
+            children: HashMap::new(),
+            results: HashMap::new(),
+            result_waiters: HashMap::new(),
+            parked: HashSet::new(),
+            deadlines: HashMap::new(),
+            channels: HashMap::new(),
+            next_chan: 1,
+            syncs: HashMap::new(),
+            next_sync: 1,
+            supervisors: HashMap::new(),
+            next_supervisor: 1,
+            supervised: HashMap::new(),
+            restarts: BinaryHeap::new(),
+            timers: HashMap::new(),
+            next_timer: Arc::new(AtomicU64::new(1)),
+            timer_ticks: BinaryHeap::new(),
+            timer_runs: HashMap::new(),
+            wall_epoch: SystemTime::now(),
+            workers: 1,
+            sim: None,
+            trace: None,
+            cancel_grace: DEFAULT_CANCEL_GRACE,
+            hard_cancels: BinaryHeap::new(),
+            task_deadlines: BinaryHeap::new(),
+            wall_budgets: BinaryHeap::new(),
+            io_timeouts: BinaryHeap::new(),
+            io_deadlines: HashMap::new(),
+            timed_out: HashMap::new(),
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+            resumed_at: HashMap::new(),
+            stalled: HashMap::new(),
+            stalls: VecDeque::new(),
+            stalls_recorded: 0,
+            stall_history: DEFAULT_STALL_HISTORY,
+            max_stalled: may::config().get_workers(),
+            subscribers: HashMap::new(),
+            future_waiters: HashSet::new(),
+            wakeups: HashSet::new(),
+            control_tx,
+            control_rx,
+            accepting: Arc::new(AtomicBool::new(true)),
+            pal: PalBus::new(),
+            registry: TaskRegistry::default(),
+            descriptors: HashMap::new(),
+            timer_descriptors: HashMap::new(),
+            gates: HashMap::new(),
+            #[cfg(feature = "async-io")]
+            waker,
+            serving: false,
+            stopping: false,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    /// Create a scheduler that runs `workers` dispatch loops with work
+    /// stealing instead of a single loop.
+    ///
+    /// The first call in a process also sizes the process-wide `may` thread
+    /// pool to `workers`, which only takes effect if no coroutine has been
+    /// spawned yet. Later calls leave the pool alone; size it yourself with
+    /// `may::config().set_workers` before spawning to pick another count.
+    pub fn with_workers(workers: usize) -> Self {
+        static SIZE_POOL: Once = Once::new();
+        let workers = workers.max(1);
+        SIZE_POOL.call_once(|| {
+            may::config().set_workers(workers);
+        });
+        Self {
+            workers,
+            ..Self::new()
         }
     }
 
+    /// Create a scheduler that runs as a deterministic simulation, choosing
+    /// the next task to step with an RNG seeded by `seed`.
+    ///
+    /// Tasks must only block through scheduler syscalls; priorities are
+    /// ignored so that every interleaving can be reached.
+    pub fn simulated(seed: u64) -> Self {
+        Self {
+            sim: Some(Simulation::new(Picker::Seeded(SimRng::new(seed)))),
+            ..Self::new()
+        }
+    }
+
+    /// Create a scheduler that reads time from `clock` instead of the
+    /// default [`TickClock`]. Simulations always run on a virtual clock.
+    pub fn with_clock(clock: Box<dyn Clock>) -> Self {
+        Self {
+            epoch: clock.now(),
+            clock,
+            ..Self::new()
+        }
+    }
+
+    /// Create a simulated scheduler that steps tasks in the order of a
+    /// schedule recorded by an earlier run.
+    pub fn replaying(schedule: Vec<TaskId>) -> Self {
+        Self {
+            sim: Some(Simulation::new(Picker::Replay(schedule.into()))),
+            ..Self::new()
+        }
+    }
+
+    /// Stop a simulated run after `steps` steps.
+    pub fn set_step_limit(&mut self, steps: usize) {
+        if let Some(sim) = &mut self.sim {
+            sim.max_steps = steps;
+        }
+    }
+
+    /// Tasks stepped so far by a simulated run, in order.
+    pub fn schedule(&self) -> &[TaskId] {
+        self.sim
+            .as_ref()
+            .map(|sim| sim.schedule.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// How the last simulated run ended.
+    pub fn sim_outcome(&self) -> Option<&SimOutcome> {
+        self.sim.as_ref()?.outcome.as_ref()
+    }
+
+    /// Record every syscall of a live task and every task exit to `sink`.
+    pub fn set_trace_sink(&mut self, sink: Box<dyn TraceSink>) {
+        self.trace = Some(sink);
+    }
+
+    /// Set how much virtual time a cancelled task gets to observe the
+    /// request and run its cleanup hooks before its coroutine is cancelled
+    /// hard. Zero cancels immediately.
+    pub fn set_cancel_grace(&mut self, grace: Duration) {
+        self.cancel_grace = grace;
+    }
+
+    /// Set how many ended tasks [`Scheduler::serve`] keeps the state,
+    /// outcome and spawn-tree links of; 4096 by default.
+    pub fn set_retention(&mut self, tasks: usize) {
+        self.retention = tasks;
+    }
+
+    /// Return a `Send + Clone` handle for submitting tasks from other
+    /// threads and for stopping [`Scheduler::serve`].
+    pub fn handle(&self) -> SchedulerHandle {
+        SchedulerHandle {
+            control: self.control_tx.clone(),
+            next_id: self.next_id.clone(),
+            next_timer: self.next_timer.clone(),
+            accepting: self.accepting.clone(),
+            pal: self.pal.clone(),
+            registry: self.registry.clone(),
+            #[cfg(feature = "async-io")]
+            waker: self.waker.clone(),
+        }
+    }
+
+    /// Return the Process Activity Log bus to subscribe to task activity.
+    pub fn pal(&self) -> PalBus {
+        self.pal.clone()
+    }
+
+    /// Run as a long-lived service: like [`Scheduler::run`], but the loop
+    /// waits for tasks submitted through [`Scheduler::handle`] instead of
+    /// returning when it runs out of work, and never gives up while idle.
+    /// It returns once a handle requested a [drain](SchedulerHandle::drain)
+    /// or [shutdown](SchedulerHandle::shutdown) and the remaining tasks are
+    /// gone. Simulated schedulers run their simulation as usual.
+    ///
+    /// Only the most recently ended tasks are remembered, as many as
+    /// [`Scheduler::set_retention`] allows: older ones drop out of the
+    /// returned completion order and their state and unclaimed outcome are
+    /// forgotten.
+    pub fn serve(&mut self) -> Vec<TaskId> {
+        self.serving = true;
+        let done = self.run();
+        self.serving = false;
+        done
+    }
+
+    /// Set how long a resumed task may run without making a syscall before
+    /// the watchdog records it as stalled.
+    pub fn set_stall_timeout(&mut self, timeout: Duration) {
+        self.stall_timeout = timeout;
+    }
+
+    /// Set how many stall reports are kept; older ones are dropped. 64 by
+    /// default.
+    pub fn set_stall_history(&mut self, reports: usize) {
+        self.stall_history = reports;
+        self.trim_stalls();
+    }
+
+    /// Set how many tasks the watchdog reports as stalled at once. Defaults
+    /// to the number of `may` workers: a task resumed while every worker is
+    /// held by a hog may just be waiting for a thread.
+    pub fn set_max_stalled(&mut self, tasks: usize) {
+        self.max_stalled = tasks;
+    }
+
+    /// The most recent stalls the watchdog detected, oldest first; see
+    /// [`Scheduler::set_stall_history`].
+    pub fn stalls(&self) -> &[StallReport] {
+        // Kept contiguous on every change.
+        self.stalls.as_slices().0
+    }
+
+    /// Number of dispatch loops [`Scheduler::run`] starts.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
     /// Return a handle that can be used to signal I/O readiness when
     /// `async-io` is disabled.
     #[cfg(not(feature = "async-io"))]
@@ -87,20 +481,206 @@ impl Scheduler {
         self.io_tx.clone()
     }
 
-    /// Register a new I/O source with the scheduler.
+    /// Register a new I/O source with the scheduler for the readiness given
+    /// by its [`IoSource::interest`].
     #[cfg(feature = "async-io")]
     pub fn register_io(&mut self, src: Arc<dyn IoSource>) {
         let token = Token(self.next_token);
         self.next_token += 1;
         let fd = src.raw_fd();
         let mut source = SourceFd(&fd);
+        let interest = match src.interest() {
+            IoInterest::Readable => Interest::READABLE,
+            IoInterest::Writable => Interest::WRITABLE,
+            IoInterest::Both => Interest::READABLE | Interest::WRITABLE,
+        };
         self.poll
             .registry()
-            .register(&mut source, token, Interest::READABLE)
+            .register(&mut source, token, interest)
             .expect("register io source");
+        self.tokens.insert(src.id(), token);
         self.sources.insert(token, src);
     }
 
+    /// Stop watching the I/O source with the given id. Tasks still waiting
+    /// on it are woken, with [`IoWake::Deregistered`] if they asked for a
+    /// reply, and readiness nobody consumed is dropped. Returns `false` if no
+    /// such source is registered.
+    #[cfg(feature = "async-io")]
+    pub fn deregister_io(&mut self, id: u64) -> bool {
+        let Some(token) = self.tokens.remove(&id) else {
+            return false;
+        };
+        if let Some(src) = self.sources.remove(&token) {
+            let fd = src.raw_fd();
+            if let Err(e) = self.poll.registry().deregister(&mut SourceFd(&fd)) {
+                tracing::warn!(?e, source = id, "failed to deregister io source");
+            }
+        }
+        for tid in self.wait_map.forget_io(id) {
+            self.wake_io(tid, IoWake::Deregistered);
+        }
+        true
+    }
+
+    /// Start `cmd` for `owner`, watching its exit and piped output.
+    #[cfg(feature = "async-io")]
+    fn spawn_process(&mut self, owner: TaskId, mut cmd: Command) -> std::io::Result<ChildProcess> {
+        let mut child = cmd.spawn()?;
+        let pid = child.id();
+        let watched = process::pidfd_open(pid).and_then(|pidfd| {
+            let token = Token(self.next_token);
+            self.next_token += 1;
+            self.poll.registry().register(
+                &mut SourceFd(&pidfd.as_raw_fd()),
+                token,
+                Interest::READABLE,
+            )?;
+            Ok((pidfd, token))
+        });
+        let (pidfd, token) = match watched {
+            Ok(w) => w,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+        let stdout = child.stdout.take().map(|p| self.watch_pipe(p.into()));
+        let stderr = child.stderr.take().map(|p| self.watch_pipe(p.into()));
+        let (stdout, stderr) = match (stdout.transpose(), stderr.transpose()) {
+            (Ok(out), Ok(err)) => (out, err),
+            (out, err) => {
+                let watched: Vec<u64> = [&out, &err]
+                    .into_iter()
+                    .filter_map(|pipe| pipe.as_ref().ok().and_then(Option::as_ref))
+                    .map(|pipe| pipe.id())
+                    .collect();
+                for id in watched {
+                    self.deregister_io(id);
+                }
+                let _ = self
+                    .poll
+                    .registry()
+                    .deregister(&mut SourceFd(&pidfd.as_raw_fd()));
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(out.and(err).expect_err("a pipe failed to register"));
+            }
+        };
+        let pipes = stdout.iter().chain(&stderr).map(|p| p.id()).collect();
+        tracing::info!(task = %owner, pid, "spawned process");
+        self.process_tokens.insert(token, pid);
+        self.processes.insert(
+            pid,
+            ProcessState {
+                child,
+                pidfd,
+                token,
+                owner,
+                pipes,
+                status: None,
+                waiters: Vec::new(),
+                killed: false,
+            },
+        );
+        Ok(ChildProcess::new(pid, stdout, stderr))
+    }
+
+    /// Register one of a child's output pipes as an I/O source.
+    #[cfg(feature = "async-io")]
+    fn watch_pipe(&mut self, fd: OwnedFd) -> std::io::Result<Arc<ChildPipe>> {
+        let pipe = Arc::new(ChildPipe::new(fd, self.next_pipe_id)?);
+        self.next_pipe_id += 1;
+        self.register_io(pipe.clone());
+        Ok(pipe)
+    }
+
+    /// Reap a child whose pidfd became readable and hand its status to the
+    /// tasks waiting for it.
+    #[cfg(feature = "async-io")]
+    fn process_exited(&mut self, pid: ProcessId) {
+        let Some(proc) = self.processes.get_mut(&pid) else {
+            return;
+        };
+        let status = match proc.child.try_wait() {
+            Ok(Some(status)) => Ok(status),
+            Ok(None) => return,
+            Err(e) => Err(e),
+        };
+        tracing::info!(pid, ?status, "process exited");
+        self.process_tokens.remove(&proc.token);
+        if let Err(e) = self
+            .poll
+            .registry()
+            .deregister(&mut SourceFd(&proc.pidfd.as_raw_fd()))
+        {
+            tracing::warn!(?e, pid, "failed to deregister pidfd");
+        }
+        let waiters = std::mem::take(&mut proc.waiters);
+        match status {
+            Ok(status) if waiters.is_empty() && !proc.killed => {
+                proc.status = Some(status);
+                return;
+            }
+            Ok(status) => {
+                for tid in waiters {
+                    self.wake_with(tid, SyscallReply::ProcessExited(Ok(status)));
+                }
+            }
+            Err(e) => {
+                for tid in waiters {
+                    let err = std::io::Error::new(e.kind(), e.to_string());
+                    self.wake_with(tid, SyscallReply::ProcessExited(Err(err)));
+                }
+            }
+        }
+        self.finish_process(pid);
+    }
+
+    /// Forget a child that has been waited for and stop watching its pipes.
+    #[cfg(feature = "async-io")]
+    fn finish_process(&mut self, pid: ProcessId) {
+        if let Some(proc) = self.processes.remove(&pid) {
+            for id in proc.pipes {
+                self.deregister_io(id);
+            }
+        }
+    }
+
+    /// Kill the children of a task that has ended. A child that has not
+    /// exited yet is sent `SIGKILL` and reaped once its pidfd turns readable,
+    /// so the scheduler thread never blocks on it.
+    #[cfg(feature = "async-io")]
+    fn end_processes(&mut self, owner: TaskId) {
+        let orphans: Vec<ProcessId> = self
+            .processes
+            .iter()
+            .filter(|(_, proc)| proc.owner == owner && !proc.killed)
+            .map(|(&pid, _)| pid)
+            .collect();
+        for pid in orphans {
+            let proc = self.processes.get_mut(&pid).expect("orphaned process");
+            let waiters = std::mem::take(&mut proc.waiters);
+            for tid in waiters {
+                let reply = SyscallReply::ProcessExited(Err(std::io::ErrorKind::NotFound.into()));
+                self.wake_with(tid, reply);
+            }
+            let proc = self.processes.get_mut(&pid).expect("orphaned process");
+            if proc.status.is_some() {
+                self.finish_process(pid);
+                continue;
+            }
+            tracing::warn!(task = %owner, pid, "killing process of ended task");
+            let _ = proc.child.kill();
+            proc.killed = true;
+            for id in std::mem::take(&mut proc.pipes) {
+                self.deregister_io(id);
+            }
+            self.process_exited(pid);
+        }
+    }
+
     /// Return the number of tasks currently in the ready queue.
     pub fn ready_len(&self) -> usize {
         self.ready.len()
@@ -111,44 +691,47 @@ impl Scheduler {
         self.ready.is_empty()
     }
 
+    /// The clock the scheduler runs on.
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    /// Number of channels the scheduler keeps state for. A channel is
+    /// forgotten once it is closed and its buffer drained.
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Number of synchronization objects the scheduler keeps state for. An
+    /// object is forgotten once it is destroyed.
+    pub fn sync_count(&self) -> usize {
+        self.syncs.len()
+    }
+
+    /// Number of task outcomes still waiting for a joiner. An outcome is
+    /// gone once it is joined or its owning [`JoinHandle`](crate::JoinHandle)
+    /// is dropped.
+    pub fn unclaimed_results(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Configure a root task's name, priority and tags before spawning it.
+    pub fn builder(&mut self) -> TaskBuilder<&mut Self> {
+        TaskBuilder::new(self)
+    }
+
     /// Spawn a new coroutine task with a specific priority.
     ///
     /// # Safety
     /// This function uses `may::coroutine::spawn`, which is unsafe because it may break Rust's safety guarantees
     /// if the spawned coroutine accesses data that is not properly synchronized or outlives its stack frame.
     /// The caller must ensure that the closure and its captured data are safe to use in this context.
-    pub unsafe fn spawn_with_priority<F>(&mut self, pri: u8, f: F) -> TaskId
+    pub unsafe fn spawn_with_priority<F, T>(&mut self, pri: u8, f: F) -> JoinHandle<T>
     where
-        F: FnOnce(TaskContext) + Send + 'static,
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+        T: Send + 'static,
     {
-        let tid = self.next_id;
-        self.next_id += 1;
-
-        let ctx = TaskContext {
-            tid,
-            syscall_tx: self.syscall_tx.clone(),
-        };
-
-        let handle: JoinHandle<()> = unsafe { may::coroutine::spawn(move || f(ctx)) };
-
-        self.states.insert(tid, TaskState::Running);
-        self.tasks.insert(
-            tid,
-            Task {
-                tid,
-                pri,
-                handle,
-                state: TaskState::Running,
-            },
-        );
-        let entry = ReadyEntry {
-            pri,
-            seq: self.seq,
-            tid,
-        };
-        self.seq += 1;
-        self.ready.push(entry);
-        tid
+        unsafe { self.builder().priority(pri).spawn(f) }
     }
 
     /// Spawn a new coroutine task with default priority (10).
@@ -157,98 +740,101 @@ impl Scheduler {
     /// This function uses `may::coroutine::spawn`, which is unsafe because it may break Rust's safety guarantees
     /// if the spawned coroutine accesses data that is not properly synchronized or outlives its stack frame.
     /// The caller must ensure that the closure and its captured data are safe to use in this context.
-    pub unsafe fn spawn<F>(&mut self, f: F) -> TaskId
+    pub unsafe fn spawn<F, T>(&mut self, f: F) -> JoinHandle<T>
     where
-        F: FnOnce(TaskContext) + Send + 'static,
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+        T: Send + 'static,
     {
         unsafe { self.spawn_with_priority(10, f) }
     }
 
     /// Run the scheduler loop, processing system calls from tasks.
+    ///
+    /// Schedulers built with [`Scheduler::with_workers`] run one dispatch
+    /// loop per worker instead.
     #[cfg(not(feature = "async-io"))]
     pub fn run(&mut self) -> Vec<TaskId> {
+        if self.sim.is_some() {
+            return self.run_simulation();
+        }
+        if self.workers > 1 {
+            return self.run_workers();
+        }
         let mut done_order = Vec::new();
-        while !self.tasks.is_empty() {
-            while let Some(&Reverse((wake_at, tid))) = self.sleepers.peek() {
-                if wake_at <= self.clock.now() {
-                    self.sleepers.pop();
-                    self.push_ready(tid);
-                } else {
-                    break;
-                }
-            }
-
-            while let Some(&Reverse((wake_at, waiter, target))) = self.timeout_waiters.peek() {
-                if wake_at <= self.clock.now() {
-                    self.timeout_waiters.pop();
-                    if self.wait_map.remove_waiter(target, waiter) {
-                        self.push_ready(waiter);
-                    }
-                } else {
-                    break;
-                }
-            }
-
-            while let Some(&Reverse((wake_at, waiter, target))) = self.timeout_waiters.peek() {
-                if wake_at <= self.clock.now() {
-                    self.timeout_waiters.pop();
-                    if self.wait_map.remove_waiter(target, waiter) {
-                        self.push_ready(waiter);
-                    }
-                } else {
-                    break;
-                }
+        while self.keep_running() {
+            self.apply_control(&mut done_order);
+            if !self.keep_running() {
+                break;
             }
+            self.wake_due(&mut done_order);
 
             while let Ok((call_tid, syscall)) = self.syscall_rx.try_recv() {
                 self.handle_syscall(call_tid, syscall, &mut done_order);
             }
 
             while let Ok(io_id) = self.io_rx.try_recv() {
-                for tid in self.wait_map.complete_io(io_id) {
-                    self.push_ready(tid);
-                }
+                self.io_ready(io_id, IoTrigger::WakeAll);
             }
 
             let tid = match self.ready.pop() {
                 Some(id) => id,
                 None => {
-                    if let Some(wake_at) = self.next_wake_instant() {
-                        if wake_at > self.clock.now() {
-                            let diff = wake_at.duration_since(self.clock.now());
-                            self.clock.tick(diff);
-                        }
+                    if let Some(wake_at) = self.next_wake_instant()
+                        && self.skip_idle(wake_at)
+                    {
                         continue;
                     }
-                    match self.io_rx.recv_timeout(Duration::from_secs(5)) {
-                        Ok(io_id) => {
-                            for tid in self.wait_map.complete_io(io_id) {
-                                self.push_ready(tid);
+                    let timeout = self.idle_timeout();
+                    let (syscalls, io, control) = (
+                        self.syscall_rx.clone(),
+                        self.io_rx.clone(),
+                        self.control_rx.clone(),
+                    );
+                    select! {
+                        recv(syscalls) -> msg => match msg {
+                            Ok((call_tid, syscall)) => {
+                                self.handle_syscall(call_tid, syscall, &mut done_order);
+                            }
+                            Err(_) => break,
+                        },
+                        recv(io) -> msg => {
+                            if let Ok(io_id) = msg {
+                                self.io_ready(io_id, IoTrigger::WakeAll);
+                            }
+                        }
+                        recv(control) -> msg => {
+                            if let Ok(cmd) = msg {
+                                self.apply(cmd, &mut done_order);
+                            }
+                        }
+                        default(timeout) => {
+                            self.check_stalls();
+                            // Wait on as long as a task may still yield or
+                            // a wall-time budget ran out.
+                            if timeout == IDLE_TIMEOUT && self.may_quit_idle() {
+                                break;
                             }
-                            continue;
                         }
-                        Err(_) => break,
                     }
+                    continue;
                 }
             };
 
-            if !self.tasks.contains_key(&tid) {
+            if !self.is_runnable(tid) {
                 continue;
             }
 
-            let _task = self.tasks.get_mut(&tid).expect("task not found");
+            self.resume(tid);
 
-            match self.syscall_rx.recv_timeout(Duration::from_secs(5)) {
+            match self.syscall_rx.recv_timeout(self.stall_timeout) {
                 Ok((call_tid, syscall)) => {
                     if call_tid != tid && self.tasks.contains_key(&tid) {
                         self.push_ready(tid);
                     }
                     self.handle_syscall(call_tid, syscall, &mut done_order);
                 }
-                Err(RecvTimeoutError::Timeout) => {
-                    tracing::warn!("scheduler idle timeout");
-                    break;
-                }
+                // Leave the task running and carry on with the others.
+                Err(RecvTimeoutError::Timeout) => self.check_stalls(),
                 Err(RecvTimeoutError::Disconnected) => break,
             }
         }
@@ -257,19 +843,28 @@ impl Scheduler {
 
     #[cfg(feature = "async-io")]
     pub fn run(&mut self) -> Vec<TaskId> {
+        if self.sim.is_some() {
+            return self.run_simulation();
+        }
+        if self.workers > 1 {
+            return self.run_workers();
+        }
         let mut done_order = Vec::new();
         let mut events = Events::with_capacity(8);
-        while !self.tasks.is_empty() {
-            let timeout = if self.ready.is_empty() {
-                if let Some(wake_at) = self.next_wake_instant() {
-                    let now = self.clock.now();
-                    if wake_at > now {
-                        wake_at - now
-                    } else {
-                        Duration::ZERO
-                    }
+        while self.keep_running() {
+            self.apply_control(&mut done_order);
+            if !self.keep_running() {
+                break;
+            }
+            // Virtual sleeps never block on the poller; only wait for real
+            // readiness when nothing else can make progress, and only
+            // briefly while a resumed task may still yield.
+            let waits = self.next_wake_instant().is_none() || !self.clock.is_virtual();
+            let timeout = if self.ready.is_empty() && waits {
+                if self.resumed_at.is_empty() {
+                    self.idle_timeout()
                 } else {
-                    Duration::from_secs(5)
+                    self.idle_timeout().min(RUNNING_POLL)
                 }
             } else {
                 Duration::ZERO
@@ -282,34 +877,27 @@ impl Scheduler {
 
             if events.is_empty()
                 && self.ready.is_empty()
-                && self.sleepers.is_empty()
-                && self.timeout_waiters.is_empty()
-                && timeout == Duration::from_secs(5)
+                && self.next_wake_instant().is_none()
+                && timeout == IDLE_TIMEOUT
+                && self.may_quit_idle()
             {
                 break;
             }
 
-            if events.is_empty() && self.ready.is_empty() && timeout > Duration::ZERO {
-                self.clock.tick(timeout);
+            if events.is_empty()
+                && self.ready.is_empty()
+                && let Some(wake_at) = self.next_wake_instant()
+            {
+                self.skip_idle(wake_at);
             }
 
             for ev in events.iter() {
-                if let Some(src) = self.sources.get(&ev.token()) {
-                    for tid in self.wait_map.complete_io(src.id()) {
-                        self.push_ready(tid);
-                    }
-                }
+                self.dispatch_event(ev.token());
             }
             events.clear();
 
-            while let Some(&Reverse((wake_at, tid))) = self.sleepers.peek() {
-                if wake_at <= self.clock.now() {
-                    self.sleepers.pop();
-                    self.push_ready(tid);
-                } else {
-                    break;
-                }
-            }
+            self.check_stalls();
+            self.wake_due(&mut done_order);
 
             while let Ok((call_tid, syscall)) = self.syscall_rx.try_recv() {
                 self.handle_syscall(call_tid, syscall, &mut done_order);
@@ -320,33 +908,46 @@ impl Scheduler {
                 None => continue,
             };
 
-            if !self.tasks.contains_key(&tid) {
+            if !self.is_runnable(tid) {
                 continue;
             }
 
-            let _task = self.tasks.get_mut(&tid).expect("task not found");
+            self.resume(tid);
 
-            match self.syscall_rx.recv_timeout(Duration::from_secs(5)) {
+            match self.syscall_rx.recv_timeout(self.stall_timeout) {
                 Ok((call_tid, syscall)) => {
                     if call_tid != tid && self.tasks.contains_key(&tid) {
                         self.push_ready(tid);
                     }
                     self.handle_syscall(call_tid, syscall, &mut done_order);
                 }
-                Err(RecvTimeoutError::Timeout) => {
-                    tracing::warn!("scheduler idle timeout");
-                    break;
-                }
+                // Leave the task running and carry on with the others.
+                Err(RecvTimeoutError::Timeout) => self.check_stalls(),
                 Err(RecvTimeoutError::Disconnected) => break,
             }
         }
         done_order
     }
 
+    /// Move the scheduler to a new thread running [`Scheduler::serve`].
+    ///
+    /// The returned handle submits and controls tasks while the loop runs;
+    /// the thread yields the completion order once a handle drains or shuts
+    /// the scheduler down.
+    pub fn serve_in_background(mut self) -> (SchedulerHandle, thread::JoinHandle<Vec<TaskId>>) {
+        let handle = self.handle();
+        let thread = thread::Builder::new()
+            .name("scheduler".into())
+            .spawn(move || self.serve())
+            .expect("failed to spawn scheduler thread");
+        (handle, thread)
+    }
+
     /// Start the scheduler loop on a dedicated thread.
     ///
     /// The provided `barrier` is used to coordinate when the loop begins.
-    /// Tasks should be spawned before the barrier is released.
+    /// Tasks should be spawned before the barrier is released. Prefer
+    /// [`Scheduler::serve_in_background`], which needs no `unsafe`.
     ///
     /// # Safety
     /// This method spawns a thread that operates on `&mut self`. The caller
@@ -379,120 +980,2112 @@ impl Default for Scheduler {
 impl Scheduler {
     /// Directly push a task ID into the ready queue without deduplication.
     pub fn ready_push_duplicate_for_test(&mut self, tid: TaskId) {
-        let pri = self.tasks.get(&tid).map(|t| t.pri).unwrap_or(10);
-        self.ready.force_push(ReadyEntry {
-            pri,
-            seq: self.seq,
-            tid,
-        });
-        self.seq += 1;
+        let entry = self.ready_entry(tid);
+        self.ready.force_push(entry);
     }
 
-    /// Insert `tid` into the ready queue respecting its priority.
-    fn push_ready(&mut self, tid: TaskId) {
-        if let Some(task) = self.tasks.get(&tid) {
-            let entry = ReadyEntry {
-                pri: task.pri,
-                seq: self.seq,
-                tid,
-            };
-            self.seq += 1;
-            self.ready.push(entry);
-        }
+    /// Create the coroutine for a new task and queue it as ready.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_with_priority`].
+    pub(crate) unsafe fn spawn_task(
+        &mut self,
+        pri: u8,
+        parent: Option<TaskId>,
+        meta: TaskMeta,
+        limits: TaskLimits,
+        f: TaskFn,
+    ) -> TaskId {
+        let tid = self.next_id.fetch_add(1, Ordering::Relaxed);
+        unsafe { self.spawn_task_as(tid, pri, parent, meta, limits, f) };
+        tid
     }
 
-    /// Return the earliest instant at which a sleeping task should be woken.
-    fn next_wake_instant(&self) -> Option<Instant> {
-        let sleep = self.sleepers.peek().map(|Reverse((when, _))| *when);
-        let timeout = self
-            .timeout_waiters
-            .peek()
-            .map(|Reverse((when, _, _))| *when);
-        match (sleep, timeout) {
-            (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
-            (Some(a), None) => Some(a),
-            (None, Some(b)) => Some(b),
-            (None, None) => None,
-        }
+    /// Spawn a root task of the registered kind `task`.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_with_priority`].
+    pub(crate) unsafe fn spawn_registered_task(
+        &mut self,
+        pri: u8,
+        meta: TaskMeta,
+        limits: TaskLimits,
+        task: TaskDescriptor,
+    ) -> Result<TaskId, CheckpointError> {
+        let f = self.registry.build(&task)?;
+        let tid = unsafe { self.spawn_task(pri, None, meta, limits, f) };
+        self.descriptors.insert(tid, task);
+        Ok(tid)
     }
 
-    /// Process a syscall emitted by `tid`, updating scheduler state and queueing follow-up work.
-    fn handle_syscall(&mut self, tid: TaskId, syscall: SystemCall, done: &mut Vec<TaskId>) {
-        let mut requeue = true;
-        match syscall {
-            SystemCall::Log(msg) => tracing::info!(task = %tid, "{}", msg),
-            SystemCall::Sleep(dur) => {
-                tracing::info!(task = %tid, "sleeping {:?}", dur);
-                let wake_at = self.clock.now() + dur;
-                self.sleepers.push(Reverse((wake_at, tid)));
-                requeue = false;
+    /// Spawn a task under an id that was already assigned.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_with_priority`].
+    unsafe fn spawn_task_as(
+        &mut self,
+        tid: TaskId,
+        pri: u8,
+        parent: Option<TaskId>,
+        mut meta: TaskMeta,
+        limits: TaskLimits,
+        f: TaskFn,
+    ) {
+        // Children inherit tags such as correlation ids from their parent.
+        if let Some(parent) = parent.and_then(|p| self.tasks.get(&p)) {
+            for (key, value) in &parent.meta.tags {
+                meta.tags
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
             }
-            SystemCall::Done => {
-                tracing::info!(task = %tid, "task done");
-                let state = if let Some(task) = self.tasks.remove(&tid) {
-                    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                        task.handle.join()
-                    }));
-                    match res {
-                        Ok(Ok(_)) => TaskState::Finished,
-                        _ => {
-                            pal::emit(TaskEvent::Failed(tid));
-                            TaskState::Failed
-                        }
-                    }
-                } else {
-                    TaskState::Finished
-                };
-                self.states.insert(tid, state);
-                let (waiters, _) = self.wait_map.complete(tid, state);
-                for waiter in waiters {
-                    self.push_ready(waiter);
-                }
-                done.push(tid);
-                requeue = false;
+        }
+        let meta = Arc::new(meta);
+        let span = tracing::info_span!(
+            "task",
+            tid,
+            name = meta.name.as_deref(),
+            tags = ?meta.tags,
+        );
+        self.report(tid, Stage::Spawned, || {
+            let mut spawned = match &meta.name {
+                Some(name) => format!("spawned {name:?}"),
+                None => "spawned".to_string(),
+            };
+            if let Some(parent) = parent {
+                spawned.push_str(&format!(" by task {parent}"));
             }
-            SystemCall::Join(target) => {
-                if self.tasks.contains_key(&target) {
-                    self.wait_map.wait_for(target, tid);
-                    requeue = false;
+            spawned
+        });
+
+        let calls = Arc::new(Mutex::new(CallStack::default()));
+        let cancel = CancelToken::default();
+        let probe = Arc::new(StallProbe::default());
+        let (reply_tx, reply_rx) = may::sync::mpmc::channel();
+        let ctx = TaskContext {
+            tid,
+            syscall_tx: self.syscall_tx.clone(),
+            reply_rx,
+            lockstep: self.sim.is_some(),
+            meta: meta.clone(),
+            span: span.clone(),
+            locals: TaskLocals::default(),
+            calls: calls.clone(),
+            cancel: cancel.clone(),
+            cleanup: CleanupHooks::default(),
+            probe: probe.clone(),
+            handle: self.handle(),
+        };
+
+        let done_tx = self.syscall_tx.clone();
+        let body = move || {
+            let hooks_ctx = ctx.clone();
+            let _entered = hooks_ctx.span.clone().entered();
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                // Simulated tasks wait to be stepped before running at all.
+                if ctx.lockstep {
+                    let _ = ctx.suspend(|| ctx.reply_rx.recv());
+                }
+                f(ctx)
+            }));
+            if hooks_ctx.is_cancelled() {
+                hooks_ctx.cleanup.run(&hooks_ctx);
+            }
+            // Report completion for bodies that return without an explicit
+            // `Done`; a duplicate is ignored by the scheduler.
+            let _ = done_tx.send((tid, SystemCall::Done));
+            match res {
+                Ok(out) => out,
+                Err(payload) => std::panic::resume_unwind(payload),
+            }
+        };
+        let handle = unsafe { may::coroutine::spawn(body) };
+
+        let group = parent
+            .and_then(|p| self.tasks.get(&p))
+            .map_or(0, |t| t.group);
+        if let Some(parent) = parent {
+            self.parents.insert(tid, parent);
+            self.children.entry(parent).or_default().push(tid);
+        }
+        let now = self.clock.now();
+        if let Some(deadline) = limits.deadline {
+            self.task_deadlines.push(Reverse((now + deadline, tid)));
+        }
+        // Host time would make simulated runs irreproducible.
+        if let Some(budget) = limits.wall_time
+            && self.sim.is_none()
+        {
+            self.wall_budgets
+                .push(Reverse((Instant::now() + budget, tid)));
+        }
+        self.states.insert(tid, TaskState::Running);
+        self.tasks.insert(
+            tid,
+            Task {
+                tid,
+                meta,
+                pri,
+                handle,
+                state: TaskState::Running,
+                parent,
+                group,
+                spawned_at: now,
+                syscalls: 0,
+                limits,
+                span,
+                calls,
+                cancel,
+                probe,
+                reply_tx,
+            },
+        );
+        let entry = self.ready_entry(tid);
+        self.ready.push(entry);
+    }
+
+    /// Hand any pending syscall reply to `tid` so a blocked task can continue.
+    fn resume(&mut self, tid: TaskId) {
+        if let Some((reply_tx, reply)) = self.take_reply(tid) {
+            let _ = reply_tx.send(reply);
+        }
+    }
+
+    /// Mark `tid` as resumed and take the reply it is parked on, if any,
+    /// along with the channel the reply goes out on.
+    fn take_reply(
+        &mut self,
+        tid: TaskId,
+    ) -> Option<(may::sync::mpmc::Sender<SyscallReply>, SyscallReply)> {
+        if self.tasks.contains_key(&tid) {
+            self.resumed_at.entry(tid).or_insert_with(Instant::now);
+        }
+        let reply = self.replies.remove(&tid)?;
+        let reply_tx = self.tasks.get(&tid)?.reply_tx.clone();
+        self.parked.remove(&tid);
+        self.deadlines.remove(&tid);
+        Some((reply_tx, reply))
+    }
+
+    /// Returns `true` if a popped `tid` is live and not parked awaiting a reply.
+    ///
+    /// Stale ready entries for parked tasks are skipped so the loop does not
+    /// wait on a task that cannot issue another syscall yet.
+    fn is_runnable(&self, tid: TaskId) -> bool {
+        self.tasks.contains_key(&tid)
+            && (!self.parked.contains(&tid) || self.replies.contains_key(&tid))
+    }
+
+    /// Wake sleepers, expire timeouts, start restarts and cancel hard the
+    /// tasks whose grace period is over.
+    fn wake_due(&mut self, done: &mut Vec<TaskId>) {
+        while let Some(&Reverse((wake_at, tid))) = self.sleepers.peek() {
+            if wake_at > self.clock.now() {
+                break;
+            }
+            self.sleepers.pop();
+            self.push_ready(tid);
+        }
+        self.expire_timeouts();
+        self.fire_restarts();
+        self.fire_timers();
+        self.open_gates();
+        let first = done.len();
+        while let Some(&Reverse((at, tid))) = self.task_deadlines.peek() {
+            if at > self.clock.now() {
+                break;
+            }
+            self.task_deadlines.pop();
+            self.expire(tid, TaskLimit::Deadline, done);
+        }
+        let now = Instant::now();
+        while let Some(&Reverse((at, tid))) = self.wall_budgets.peek() {
+            if at > now {
+                break;
+            }
+            self.wall_budgets.pop();
+            self.expire(tid, TaskLimit::WallTime, done);
+        }
+        while let Some(&Reverse((at, tid, source))) = self.io_timeouts.peek() {
+            if at > now {
+                break;
+            }
+            self.io_timeouts.pop();
+            if self.io_deadlines.get(&tid) == Some(&at)
+                && self.wait_map.remove_io_waiter(source, tid)
+            {
+                self.io_deadlines.remove(&tid);
+                self.wake_with(tid, SyscallReply::IoWaited(IoWake::TimedOut));
+            }
+        }
+        while let Some(&Reverse((at, tid))) = self.hard_cancels.peek() {
+            if at > self.clock.now() {
+                break;
+            }
+            self.hard_cancels.pop();
+            if self.tasks.contains_key(&tid) {
+                tracing::warn!(task = %tid, "grace period over; cancelling hard");
+                self.cancel_task(tid, done);
+            }
+        }
+        if self.trace.is_some() {
+            self.trace_exits(&done[first..]);
+        }
+    }
+
+    /// Wake tasks whose I/O became ready without blocking.
+    #[cfg(not(feature = "async-io"))]
+    fn drain_io(&mut self) {
+        while let Ok(io_id) = self.io_rx.try_recv() {
+            self.io_ready(io_id, IoTrigger::WakeAll);
+        }
+    }
+
+    /// Wake tasks whose I/O became ready without blocking.
+    #[cfg(feature = "async-io")]
+    fn drain_io(&mut self) {
+        let mut events = Events::with_capacity(8);
+        if let Err(e) = self.poll.poll(&mut events, Some(Duration::ZERO)) {
+            tracing::warn!(?e, "poll error");
+            return;
+        }
+        for ev in events.iter() {
+            self.dispatch_event(ev.token());
+        }
+    }
+
+    /// Act on a readiness event from the poller.
+    #[cfg(feature = "async-io")]
+    fn dispatch_event(&mut self, token: Token) {
+        if let Some(src) = self.sources.get(&token) {
+            let (id, trigger) = (src.id(), src.trigger());
+            self.io_ready(id, trigger);
+        } else if let Some(&pid) = self.process_tokens.get(&token) {
+            self.process_exited(pid);
+        }
+    }
+
+    /// Wake the tasks due a readiness event on `source`.
+    fn io_ready(&mut self, source: u64, trigger: IoTrigger) {
+        for tid in self.wait_map.complete_io_with(source, trigger) {
+            self.wake_io(tid, IoWake::Ready);
+        }
+    }
+
+    /// Resume a task taken off an I/O wait, answering it if it is parked on
+    /// [`SystemCall::IoWaitTimeout`].
+    fn wake_io(&mut self, tid: TaskId, why: IoWake) {
+        self.io_deadlines.remove(&tid);
+        if self.parked.contains(&tid) {
+            self.wake_with(tid, SyscallReply::IoWaited(why));
+        } else {
+            self.push_ready(tid);
+        }
+    }
+
+    /// Wake waiters whose timeout has elapsed.
+    fn expire_timeouts(&mut self) {
+        while let Some(&Reverse((wake_at, waiter, target))) = self.timeout_waiters.peek() {
+            if wake_at > self.clock.now() {
+                break;
+            }
+            self.timeout_waiters.pop();
+            // A parked task only honours the deadline of its current request.
+            if self.parked.contains(&waiter) && self.deadlines.get(&waiter) != Some(&wake_at) {
+                continue;
+            }
+            match target {
+                WaitTarget::Task(target) => {
+                    if self.wait_map.remove_waiter(target, waiter) {
+                        if self.result_waiters.remove(&waiter).is_some() {
+                            self.replies
+                                .insert(waiter, SyscallReply::Joined(Err(TaskError::TimedOut)));
+                        }
+                        self.push_ready(waiter);
+                    }
+                }
+                WaitTarget::Recv(chan) => {
+                    if self.wait_map.remove_receiver(chan, waiter) {
+                        let reply = SyscallReply::Received(Err(ChannelError::TimedOut));
+                        self.wake_with(waiter, reply);
+                    }
+                }
+                WaitTarget::Io(source) => {
+                    if self.parked.contains(&waiter)
+                        && self.wait_map.remove_io_waiter(source, waiter)
+                    {
+                        self.wake_with(waiter, SyscallReply::IoWaited(IoWake::TimedOut));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Arm a timeout for the request `tid` is parked on.
+    fn arm_deadline(&mut self, tid: TaskId, dur: Duration, target: WaitTarget) {
+        let wake_at = self.clock.now() + dur;
+        self.deadlines.insert(tid, wake_at);
+        self.timeout_waiters.push(Reverse((wake_at, tid, target)));
+    }
+
+    /// Queue `reply` for a parked task and make it runnable.
+    fn wake_with(&mut self, tid: TaskId, reply: SyscallReply) {
+        self.replies.insert(tid, reply);
+        self.push_ready(tid);
+    }
+
+    /// Deliver `msg` on `chan` for `tid`. Returns `false` if the sender parks.
+    fn channel_send(&mut self, tid: TaskId, chan: ChannelId, msg: Message) -> bool {
+        let Some(state) = self.channels.get_mut(&chan).filter(|c| !c.closed) else {
+            self.replies.insert(tid, SyscallReply::Sent(Err(msg)));
+            return true;
+        };
+        if let Some(receiver) = self.wait_map.pop_receiver(chan) {
+            // Receivers only park on an empty buffer, so hand over directly.
+            self.wake_with(receiver, SyscallReply::Received(Ok(msg)));
+        } else if state.has_room() {
+            state.buf.push_back(msg);
+        } else {
+            state.pending.insert(tid, msg);
+            self.wait_map.wait_send(chan, tid);
+            return false;
+        }
+        self.replies.insert(tid, SyscallReply::Sent(Ok(())));
+        true
+    }
+
+    /// Take the next message on `chan` for `tid`. Returns `false` if the
+    /// receiver parks.
+    fn channel_recv(&mut self, tid: TaskId, chan: ChannelId, timeout: Option<Duration>) -> bool {
+        let Some(state) = self.channels.get_mut(&chan) else {
+            let reply = SyscallReply::Received(Err(ChannelError::Closed));
+            self.replies.insert(tid, reply);
+            return true;
+        };
+        let msg = if let Some(msg) = state.buf.pop_front() {
+            if state.closed {
+                // A closed channel is forgotten once its buffer is drained;
+                // later operations on it see it closed all the same.
+                if state.buf.is_empty() {
+                    self.channels.remove(&chan);
+                }
+            } else if let Some(sender) = self.wait_map.pop_sender(chan) {
+                // Room was freed: move the longest-parked sender into the buffer.
+                let pending = state
+                    .pending
+                    .remove(&sender)
+                    .expect("parked sender message");
+                state.buf.push_back(pending);
+                self.wake_with(sender, SyscallReply::Sent(Ok(())));
+            }
+            Ok(msg)
+        } else if let Some(sender) = self.wait_map.pop_sender(chan) {
+            // Rendezvous with a sender parked on a zero-capacity channel.
+            let msg = state
+                .pending
+                .remove(&sender)
+                .expect("parked sender message");
+            self.wake_with(sender, SyscallReply::Sent(Ok(())));
+            Ok(msg)
+        } else if state.closed {
+            self.channels.remove(&chan);
+            Err(ChannelError::Closed)
+        } else {
+            self.wait_map.wait_recv(chan, tid);
+            if let Some(dur) = timeout {
+                self.arm_deadline(tid, dur, WaitTarget::Recv(chan));
+            }
+            return false;
+        };
+        self.replies.insert(tid, SyscallReply::Received(msg));
+        true
+    }
+
+    /// Close `chan`, failing every receiver and sender parked on it.
+    fn channel_close(&mut self, chan: ChannelId) {
+        let Some(state) = self.channels.get_mut(&chan) else {
+            return;
+        };
+        state.closed = true;
+        let mut pending = std::mem::take(&mut state.pending);
+        let (receivers, senders) = self.wait_map.close_channel(chan);
+        for receiver in receivers {
+            let reply = SyscallReply::Received(Err(ChannelError::Closed));
+            self.wake_with(receiver, reply);
+        }
+        for sender in senders {
+            if let Some(msg) = pending.remove(&sender) {
+                self.wake_with(sender, SyscallReply::Sent(Err(msg)));
+            }
+        }
+        if self.channels[&chan].buf.is_empty() {
+            self.channels.remove(&chan);
+        }
+    }
+
+    /// Park `tid` on a synchronization object in ready-queue order.
+    fn park_sync(&mut self, tid: TaskId, id: SyncId) {
+        let entry = self.ready_entry(tid);
+        self.wait_map.wait_sync(id, entry);
+    }
+
+    /// Lock a mutex or take a semaphore permit. Returns `false` if `tid` parks.
+    fn sync_acquire(&mut self, tid: TaskId, id: SyncId) -> bool {
+        let granted = match self.syncs.get_mut(&id) {
+            Some(SyncState::Mutex { owner }) => {
+                let free = owner.is_none();
+                if free {
+                    *owner = Some(tid);
+                }
+                free
+            }
+            Some(SyncState::Semaphore { permits }) => {
+                let free = *permits > 0;
+                if free {
+                    *permits -= 1;
+                }
+                free
+            }
+            _ => {
+                self.replies.insert(tid, SyscallReply::Unknown(id));
+                return true;
+            }
+        };
+        if granted {
+            self.replies.insert(tid, SyscallReply::Acquired);
+            true
+        } else {
+            self.park_sync(tid, id);
+            false
+        }
+    }
+
+    /// Unlock a mutex held by `tid` or return a semaphore permit, handing it
+    /// straight to the next waiter if there is one.
+    fn sync_release(&mut self, tid: TaskId, id: SyncId) {
+        match self.syncs.get_mut(&id) {
+            Some(SyncState::Mutex { owner }) if *owner == Some(tid) => {
+                let next = self.wait_map.pop_sync(id);
+                *owner = next;
+                if let Some(next) = next {
+                    self.wake_with(next, SyscallReply::Acquired);
+                }
+            }
+            Some(SyncState::Semaphore { permits }) => match self.wait_map.pop_sync(id) {
+                Some(next) => self.wake_with(next, SyscallReply::Acquired),
+                None => *permits += 1,
+            },
+            _ => tracing::warn!(task = %tid, sync = id, "release of unheld object"),
+        }
+    }
+
+    /// Set an event and wake all of its waiters.
+    fn event_set(&mut self, id: SyncId) {
+        if let Some(SyncState::Event { set }) = self.syncs.get_mut(&id) {
+            *set = true;
+            for waiter in self.wait_map.drain_sync(id) {
+                self.wake_with(waiter, SyscallReply::Signalled);
+            }
+        }
+    }
+
+    /// Wait for an event. Returns `false` if `tid` parks.
+    fn event_wait(&mut self, tid: TaskId, id: SyncId) -> bool {
+        match self.syncs.get(&id) {
+            Some(SyncState::Event { set: false }) => {
+                self.park_sync(tid, id);
+                false
+            }
+            Some(SyncState::Event { set: true }) => {
+                self.replies.insert(tid, SyscallReply::Signalled);
+                true
+            }
+            _ => {
+                self.replies.insert(tid, SyscallReply::Unknown(id));
+                true
+            }
+        }
+    }
+
+    /// Arrive at a barrier. Returns `false` if `tid` parks.
+    fn barrier_wait(&mut self, tid: TaskId, id: SyncId) -> bool {
+        let Some(SyncState::Barrier { parties, arrived }) = self.syncs.get_mut(&id) else {
+            self.replies.insert(tid, SyscallReply::Unknown(id));
+            return true;
+        };
+        *arrived += 1;
+        if *arrived < *parties {
+            self.park_sync(tid, id);
+            return false;
+        }
+        *arrived = 0;
+        for waiter in self.wait_map.drain_sync(id) {
+            self.wake_with(waiter, SyscallReply::BarrierPassed { leader: false });
+        }
+        self.replies
+            .insert(tid, SyscallReply::BarrierPassed { leader: true });
+        true
+    }
+
+    /// Forget a synchronization object. Tasks still parked on it resume with
+    /// [`SyscallReply::Unknown`], as a later request naming it would.
+    fn sync_destroy(&mut self, id: SyncId) {
+        if self.syncs.remove(&id).is_none() {
+            return;
+        }
+        for waiter in self.wait_map.drain_sync(id) {
+            self.wake_with(waiter, SyscallReply::Unknown(id));
+        }
+    }
+
+    /// Drop every wait `tid` is parked on, e.g. because it was cancelled.
+    fn forget_waiter(&mut self, tid: TaskId) {
+        self.parked.remove(&tid);
+        self.deadlines.remove(&tid);
+        self.io_deadlines.remove(&tid);
+        self.future_waiters.remove(&tid);
+        #[cfg(feature = "async-io")]
+        for proc in self.processes.values_mut() {
+            proc.waiters.retain(|&w| w != tid);
+        }
+        self.replies.remove(&tid);
+        if let Some(wait) = self.result_waiters.remove(&tid) {
+            for target in wait.targets {
+                self.wait_map.remove_waiter(target, tid);
+            }
+        }
+        self.wait_map.forget_channel_waiter(tid);
+        for state in self.channels.values_mut() {
+            state.pending.remove(&tid);
+        }
+        self.leave_sync_queues(tid);
+        let held: Vec<SyncId> = self
+            .syncs
+            .iter()
+            .filter(|(_, state)| matches!(state, SyncState::Mutex { owner: Some(o) } if *o == tid))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in held {
+            self.sync_release(tid, id);
+        }
+    }
+
+    /// Drop `tid` from every synchronization queue it is parked on.
+    fn leave_sync_queues(&mut self, tid: TaskId) {
+        for id in self.wait_map.forget_sync_waiter(tid) {
+            if let Some(SyncState::Barrier { arrived, .. }) = self.syncs.get_mut(&id) {
+                *arrived -= 1;
+            }
+        }
+    }
+
+    /// Ask `target` and its live descendants to stop. Each gets the grace
+    /// period to wind down before it is cancelled hard.
+    fn request_cancel(&mut self, target: TaskId, done: &mut Vec<TaskId>) {
+        if self.cancel_grace.is_zero() {
+            self.cancel_tree(target, done);
+            return;
+        }
+        let deadline = self.clock.now() + self.cancel_grace;
+        let mut stack = vec![target];
+        while let Some(tid) = stack.pop() {
+            stack.extend(self.children_of(tid).iter().rev().copied());
+            if self
+                .tasks
+                .get(&tid)
+                .is_some_and(|task| task.cancel.request())
+            {
+                self.hard_cancels.push(Reverse((deadline, tid)));
+                self.abort_wait(tid);
+            }
+        }
+    }
+
+    /// Cut short whatever a task being cancelled is blocked on. Parked
+    /// requests are answered with [`SyscallReply::Cancelled`], or get their
+    /// message back if they were sending; sleeps and joins just end.
+    fn abort_wait(&mut self, tid: TaskId) {
+        if self.parked.contains(&tid) {
+            if self.replies.contains_key(&tid) {
+                return;
+            }
+            self.deadlines.remove(&tid);
+            self.io_deadlines.remove(&tid);
+            if let Some(wait) = self.result_waiters.remove(&tid) {
+                for target in wait.targets {
+                    self.wait_map.remove_waiter(target, tid);
+                }
+            }
+            self.wait_map.forget_channel_waiter(tid);
+            self.wait_map.forget_task_waiter(tid);
+            self.future_waiters.remove(&tid);
+            self.gates.remove(&tid);
+            #[cfg(feature = "async-io")]
+            for proc in self.processes.values_mut() {
+                proc.waiters.retain(|&w| w != tid);
+            }
+            let unsent = self
+                .channels
+                .values_mut()
+                .find_map(|state| state.pending.remove(&tid));
+            self.leave_sync_queues(tid);
+            let reply = match unsent {
+                Some(msg) => SyscallReply::Sent(Err(msg)),
+                None => SyscallReply::Cancelled,
+            };
+            self.wake_with(tid, reply);
+            return;
+        }
+        let sleeping = self.sleepers.len();
+        self.sleepers.retain(|Reverse((_, t))| *t != tid);
+        let woke = self.sleepers.len() != sleeping;
+        if self.wait_map.forget_task_waiter(tid) || woke {
+            self.push_ready(tid);
+        }
+    }
+
+    /// Wake everything waiting on `target` after it reached `state`.
+    fn complete_task(&mut self, target: TaskId, state: TaskState) {
+        self.descriptors.remove(&target);
+        self.gates.remove(&target);
+        let error = match (state, self.results.get(&target)) {
+            (TaskState::Failed | TaskState::TimedOut, Some(Err(e))) => Some(e),
+            _ => None,
+        };
+        let stage = match error {
+            Some(_) if state == TaskState::Failed => Stage::Failed,
+            Some(_) => Stage::Cancelled,
+            None if state == TaskState::Cancelled => Stage::Cancelled,
+            None => Stage::Completed,
+        };
+        self.report(target, stage, || match error {
+            Some(e) => e.to_string(),
+            None if stage == Stage::Cancelled => "cancelled".to_string(),
+            None => "finished".to_string(),
+        });
+        self.open_gates();
+        self.timer_run_ended(target);
+        #[cfg(feature = "async-io")]
+        self.end_processes(target);
+        for sub in self.subscribers.remove(&target).unwrap_or_default() {
+            sub.notify(state);
+        }
+        self.wakeups.remove(&target);
+        let (waiters, _) = self.wait_map.complete(target, state);
+        for waiter in waiters {
+            if self
+                .result_waiters
+                .get(&waiter)
+                .is_some_and(|wait| wait.targets.contains(&target))
+            {
+                let wait = self.result_waiters.remove(&waiter).unwrap();
+                for other in wait.targets.iter().filter(|&&t| t != target) {
+                    self.wait_map.remove_waiter(*other, waiter);
+                }
+                let res = self.take_result(target);
+                let reply = if wait.any {
+                    SyscallReply::JoinedAny(target, res)
+                } else {
+                    SyscallReply::Joined(res)
+                };
+                self.replies.insert(waiter, reply);
+            }
+            self.push_ready(waiter);
+        }
+        if self.released.remove(&target) {
+            self.results.remove(&target);
+        }
+    }
+
+    /// Cancel `target` and then every live task below it in the spawn tree.
+    fn cancel_tree(&mut self, target: TaskId, done: &mut Vec<TaskId>) {
+        self.cancel_task(target, done);
+        self.cancel_descendants(target, done);
+    }
+
+    /// Cancel every live descendant of `root`, parents before children.
+    fn cancel_descendants(&mut self, root: TaskId, done: &mut Vec<TaskId>) {
+        let mut stack: Vec<TaskId> = self.children_of(root).iter().rev().copied().collect();
+        while let Some(tid) = stack.pop() {
+            self.cancel_task(tid, done);
+            stack.extend(self.children_of(tid).iter().rev().copied());
+        }
+    }
+
+    /// Stop a single live task and record it as cancelled.
+    fn cancel_task(&mut self, target: TaskId, done: &mut Vec<TaskId>) {
+        if let Some(task) = self.tasks.remove(&target) {
+            unsafe { task.handle.coroutine().cancel() };
+            let _ = task.handle.join();
+            let state = self.record_stopped(target);
+            self.states.insert(target, state);
+            self.complete_task(target, state);
+            self.forget_waiter(target);
+            self.resumed_at.remove(&target);
+            self.stalled.remove(&target);
+            done.push(target);
+            self.child_exited(target, state == TaskState::TimedOut, done);
+        }
+    }
+
+    /// Whether a run loop should go on.
+    fn keep_running(&self) -> bool {
+        let busy = !self.tasks.is_empty()
+            || !self.restarts.is_empty()
+            || !self.timers.is_empty()
+            || !self.control_rx.is_empty();
+        // Killed children are still reaped before the loop ends.
+        #[cfg(feature = "async-io")]
+        let busy = busy || self.processes.values().any(|proc| proc.killed);
+        busy || (self.serving && !self.stopping)
+    }
+
+    /// Whether an idle loop may give up waiting for work. A timed I/O wait
+    /// always ends and an awaited future is woken from outside, so the loop
+    /// stays for them.
+    fn may_quit_idle(&self) -> bool {
+        #[cfg(feature = "async-io")]
+        if !self.process_tokens.is_empty() {
+            return false;
+        }
+        !self.serving
+            && self.next_wake_instant().is_none()
+            && self.resumed_at.is_empty()
+            && self.io_deadlines.is_empty()
+            && self.future_waiters.is_empty()
+    }
+
+    /// Apply the requests queued by handles.
+    fn apply_control(&mut self, done: &mut Vec<TaskId>) {
+        while let Ok(cmd) = self.control_rx.try_recv() {
+            self.apply(cmd, done);
+        }
+        self.retire(done);
+    }
+
+    /// Forget the oldest ended tasks a serving loop no longer retains.
+    fn retire(&mut self, done: &mut Vec<TaskId>) {
+        if !self.serving || done.len() <= self.retention {
+            return;
+        }
+        let excess = done.len() - self.retention;
+        for tid in done.drain(..excess) {
+            self.states.remove(&tid);
+            self.results.remove(&tid);
+            self.released.remove(&tid);
+            self.children.remove(&tid);
+            if let Some(parent) = self.parents.remove(&tid)
+                && let Some(siblings) = self.children.get_mut(&parent)
+            {
+                siblings.retain(|&child| child != tid);
+            }
+        }
+    }
+
+    /// Turn away a spawn that passed a handle's check before a drain or
+    /// shutdown began, recording the task as cancelled.
+    fn reject_spawn(&mut self, tid: TaskId, done: &mut Vec<TaskId>) {
+        tracing::info!(task = %tid, "spawn rejected while stopping");
+        self.states.insert(tid, TaskState::Cancelled);
+        done.push(tid);
+    }
+
+    fn apply(&mut self, cmd: Control, done: &mut Vec<TaskId>) {
+        match cmd {
+            Control::Spawn { tid, .. } | Control::SpawnRegistered { tid, .. } if self.stopping => {
+                self.reject_spawn(tid, done);
+            }
+            Control::Spawn {
+                tid,
+                pri,
+                meta,
+                limits,
+                f,
+            } => {
+                tracing::info!(task = %tid, "spawned from handle");
+                // SAFETY: handles only take `Send + 'static` bodies and own
+                // the rest of the invariant; see `TaskBuilder::spawn`.
+                unsafe { self.spawn_task_as(tid, pri, None, meta, limits, f) };
+            }
+            Control::SpawnRegistered {
+                tid,
+                pri,
+                meta,
+                limits,
+                task,
+                f,
+            } => {
+                tracing::info!(task = %tid, kind = task.kind, "spawned from handle");
+                // SAFETY: as for `Control::Spawn`.
+                unsafe { self.spawn_task_as(tid, pri, None, meta, limits, f) };
+                self.descriptors.insert(tid, task);
+            }
+            Control::Cancel(tid) => self.request_cancel(tid, done),
+            Control::Release(tid) => {
+                if self.tasks.contains_key(&tid) {
+                    self.released.insert(tid);
+                } else {
+                    self.results.remove(&tid);
+                }
+            }
+            Control::Timer { id, spec, f } => {
+                // SAFETY: as for `Control::Spawn`; timer bodies are also
+                // `Sync`.
+                unsafe { self.add_timer(id, spec, f) };
+            }
+            Control::CancelTimer(id) => {
+                self.cancel_timer(id);
+            }
+            Control::State { tid, reply } => {
+                let _ = reply.send(self.task_state(tid));
+            }
+            Control::Snapshot(reply) => {
+                let _ = reply.send(self.snapshot());
+            }
+            Control::Checkpoint(reply) => {
+                let _ = reply.send(self.checkpoint());
+            }
+            Control::Subscribe { tid, sub } => {
+                if self.tasks.contains_key(&tid) {
+                    self.subscribers.entry(tid).or_default().push(sub);
+                } else if let Some(state) = self.task_state(tid) {
+                    sub.notify(state);
+                }
+            }
+            Control::Wake(tid) => {
+                if self.future_waiters.remove(&tid) {
+                    self.wake_with(tid, SyscallReply::Woken);
+                } else if self.tasks.contains_key(&tid) {
+                    self.wakeups.insert(tid);
+                }
+            }
+            Control::AdvanceClock(dur) => {
+                if !self.clock.advance(dur) {
+                    tracing::warn!(?dur, "clock cannot be advanced");
+                }
+            }
+            Control::Drain => {
+                tracing::info!(tasks = self.tasks.len(), "draining");
+                self.stopping = true;
+                self.timers.clear();
+            }
+            Control::Shutdown => {
+                tracing::info!(tasks = self.tasks.len(), "shutting down");
+                self.stopping = true;
+                self.restarts.clear();
+                self.timers.clear();
+                let mut live: Vec<TaskId> = self.tasks.keys().copied().collect();
+                live.sort_unstable();
+                for tid in live {
+                    self.request_cancel(tid, done);
+                }
+            }
+        }
+    }
+
+    /// Record the result of a task that was cancelled before it ended on its
+    /// own and return its final state.
+    fn record_stopped(&mut self, tid: TaskId) -> TaskState {
+        match self.timed_out.remove(&tid) {
+            Some(limit) => {
+                self.results
+                    .insert(tid, Err(TaskError::LimitExceeded(limit)));
+                TaskState::TimedOut
+            }
+            None => {
+                self.results.insert(tid, Err(TaskError::Cancelled));
+                TaskState::Cancelled
+            }
+        }
+    }
+
+    /// Cancel `tid` and its subtree because it exceeded `limit`.
+    fn expire(&mut self, tid: TaskId, limit: TaskLimit, done: &mut Vec<TaskId>) {
+        if !self.tasks.contains_key(&tid) || self.timed_out.contains_key(&tid) {
+            return;
+        }
+        tracing::warn!(task = %tid, %limit, "task exceeded its limit");
+        self.timed_out.insert(tid, limit);
+        self.request_cancel(tid, done);
+    }
+
+    /// How long an idle run loop may block on I/O: until the next wall-time
+    /// budget of a live task or I/O timeout runs out, a running task is due
+    /// for the watchdog or, on a real clock, the next sleeper or timer is
+    /// due, but at most [`IDLE_TIMEOUT`].
+    fn idle_timeout(&self) -> Duration {
+        let now = Instant::now();
+        let wake = self
+            .next_wake_instant()
+            .filter(|_| !self.clock.is_virtual())
+            .map(|at| now + at.saturating_duration_since(self.clock.now()));
+        let budgets = self
+            .wall_budgets
+            .iter()
+            .filter(|Reverse((_, tid))| self.tasks.contains_key(tid))
+            .map(|Reverse((at, _))| *at);
+        let io = self
+            .io_timeouts
+            .iter()
+            .filter(|Reverse((at, tid, _))| self.io_deadlines.get(tid) == Some(at))
+            .map(|Reverse((at, _, _))| *at);
+        // Once the cap is reached, overdue tasks wait for a stalled task to
+        // yield, which wakes the loop anyway; counting them would spin.
+        let reporting = self.stalled.len() < self.max_stalled;
+        let stalls = self
+            .resumed_at
+            .iter()
+            .filter(|(tid, _)| reporting && !self.stalled.contains_key(tid))
+            .map(|(_, at)| *at + self.stall_timeout);
+        budgets
+            .chain(io)
+            .chain(stalls)
+            .chain(wake)
+            .map(|at| at.saturating_duration_since(now))
+            .fold(IDLE_TIMEOUT, Duration::min)
+    }
+
+    /// Record every task that has run longer than the stall timeout since it
+    /// was resumed. Tasks queued behind hogs on a saturated `may` pool are
+    /// not blamed.
+    fn check_stalls(&mut self) {
+        let now = Instant::now();
+        let mut overdue: Vec<TaskId> = self
+            .resumed_at
+            .iter()
+            .filter(|&(tid, at)| {
+                now.duration_since(*at) >= self.stall_timeout && !self.stalled.contains_key(tid)
+            })
+            .map(|(tid, _)| *tid)
+            .collect();
+        overdue.sort_unstable();
+        for tid in overdue {
+            if self.stalled.len() >= self.max_stalled {
+                break;
+            }
+            let Some(task) = self.tasks.get(&tid) else {
+                self.resumed_at.remove(&tid);
+                continue;
+            };
+            let call_stack = task
+                .calls
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .frames()
+                .to_vec();
+            tracing::warn!(
+                task = %tid,
+                name = task.meta.name.as_deref(),
+                frames = %call_stack.join(" > "),
+                "task stalled without yielding",
+            );
+            task.probe.flag();
+            self.stalled.insert(tid, self.stalls_recorded);
+            self.stalls_recorded += 1;
+            self.stalls.push_back(StallReport {
+                tid,
+                name: task.meta.name.clone(),
+                detected_at: self.clock.now() - self.epoch,
+                call_stack,
+                backtrace: None,
+                stalled_for: None,
+            });
+            self.trim_stalls();
+        }
+    }
+
+    /// Drop the oldest stall reports beyond the history.
+    fn trim_stalls(&mut self) {
+        let excess = self.stalls.len().saturating_sub(self.stall_history);
+        self.stalls.drain(..excess);
+        self.stalls.make_contiguous();
+    }
+
+    /// Note that `tid` yielded again, completing its stall report if it had
+    /// stalled. Tasks that went overdue while stalled tasks held every
+    /// worker only get a thread now, so their time starts over.
+    fn yielded(&mut self, tid: TaskId) {
+        let resumed = self.resumed_at.remove(&tid);
+        let saturated = self.stalled.len() >= self.max_stalled;
+        let Some(number) = self.stalled.remove(&tid) else {
+            return;
+        };
+        if saturated {
+            let now = Instant::now();
+            for (other, at) in self.resumed_at.iter_mut() {
+                if !self.stalled.contains_key(other)
+                    && now.duration_since(*at) >= self.stall_timeout
+                {
+                    *at = now;
+                }
+            }
+        }
+        let first = self.stalls_recorded - self.stalls.len() as u64;
+        let Some(report) = number
+            .checked_sub(first)
+            .and_then(|idx| self.stalls.get_mut(idx as usize))
+        else {
+            return;
+        };
+        report.stalled_for = resumed.map(|at| at.elapsed());
+        report.backtrace = self.tasks.get(&tid).and_then(|t| t.probe.take_backtrace());
+        tracing::info!(task = %tid, stalled_for = ?report.stalled_for, "stalled task yielded");
+    }
+
+    /// Update the supervisor of `tid`, if any, after the task ended.
+    fn child_exited(&mut self, tid: TaskId, failed: bool, done: &mut Vec<TaskId>) {
+        let Some((sup, idx)) = self.supervised.remove(&tid) else {
+            return;
+        };
+        let now = self.clock.now();
+        let Some(state) = self.supervisors.get_mut(&sup) else {
+            return;
+        };
+        state.running[idx] = None;
+        if !failed || state.status != SupervisorStatus::Running {
+            return;
+        }
+        let affected = state.affected(idx);
+        match state.record_restart(now) {
+            Some(delay) => {
+                tracing::warn!(task = %tid, supervisor = sup, ?delay, "restarting failed child");
+                let siblings: Vec<TaskId> =
+                    affected.iter().filter_map(|&i| state.running[i]).collect();
+                for i in affected {
+                    self.restarts.push(Reverse((now + delay, sup, i)));
+                }
+                for sibling in siblings {
+                    self.cancel_tree(sibling, done);
+                }
+            }
+            None => {
+                tracing::warn!(supervisor = sup, "restart intensity exceeded; giving up");
+                state.status = SupervisorStatus::GaveUp;
+                let live: Vec<TaskId> = state.running.iter().flatten().copied().collect();
+                for child in live {
+                    self.cancel_tree(child, done);
+                }
+            }
+        }
+    }
+
+    /// Start the supervised children whose backoff has elapsed.
+    fn fire_restarts(&mut self) {
+        while let Some(&Reverse((at, sup, idx))) = self.restarts.peek() {
+            if at > self.clock.now() {
+                break;
+            }
+            self.restarts.pop();
+            if let Some(state) = self.supervisors.get(&sup)
+                && state.status == SupervisorStatus::Running
+                && state.running[idx].is_none()
+            {
+                // SAFETY: the caller of `supervise` vouched for the factories.
+                unsafe { self.start_child(sup, idx) };
+            }
+        }
+    }
+
+    /// Spawn a fresh incarnation of child `idx` of supervisor `sup`.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_with_priority`].
+    unsafe fn start_child(&mut self, sup: SupervisorId, idx: usize) {
+        let state = &self.supervisors[&sup];
+        let spec = &state.spec.children[idx];
+        let (pri, body) = (spec.pri, spec.body());
+        let tid =
+            unsafe { self.spawn_task(pri, None, TaskMeta::default(), TaskLimits::default(), body) };
+        self.supervisors.get_mut(&sup).unwrap().running[idx] = Some(tid);
+        self.supervised.insert(tid, (sup, idx));
+    }
+
+    /// Wall time at virtual time zero for matching cron schedules; simulations
+    /// start at the Unix epoch so their runs are reproducible.
+    fn calendar_base(&self) -> SystemTime {
+        if self.sim.is_some() {
+            UNIX_EPOCH
+        } else {
+            self.wall_epoch
+        }
+    }
+
+    /// Instant of the next tick of timer `id`, if it is still registered.
+    fn timer_due(&self, id: TimerId) -> Option<Instant> {
+        let next = self.timers.get(&id)?.next?;
+        Some(self.epoch + next)
+    }
+
+    /// Register a timer under `id` and arm its first tick.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_timer`].
+    unsafe fn add_timer(&mut self, id: TimerId, spec: TimerSpec, f: TimerFn) {
+        let now = self.clock.now() - self.epoch;
+        let timer = TimerState::new(spec, f, now, self.calendar_base());
+        tracing::info!(timer = id, schedule = ?timer.spec.schedule, "timer registered");
+        if let Some(next) = timer.next {
+            self.timer_ticks.push(Reverse((self.epoch + next, id)));
+        }
+        if timer.exhausted() {
+            tracing::warn!(timer = id, "timer schedule never fires");
+            return;
+        }
+        self.timers.insert(id, timer);
+    }
+
+    /// Start runs of the timers whose next tick is due.
+    fn fire_timers(&mut self) {
+        // One reading of the clock for the whole pass: a tick that a clock
+        // moving in real time makes due mid-pass must wait for the next one.
+        let clock_now = self.clock.now();
+        let (now, base) = (clock_now - self.epoch, self.calendar_base());
+        while let Some(&Reverse((at, id))) = self.timer_ticks.peek() {
+            if at > clock_now {
+                break;
+            }
+            self.timer_ticks.pop();
+            if self.timer_due(id) != Some(at) {
+                continue;
+            }
+            let timer = self.timers.get_mut(&id).expect("due timer");
+            let start = timer.tick(now, base);
+            if let Some(next) = timer.next {
+                self.timer_ticks.push(Reverse((self.epoch + next, id)));
+            }
+            if start {
+                // SAFETY: the caller of `spawn_timer` vouched for the body.
+                unsafe { self.start_timer_run(id) };
+            } else if timer.exhausted() {
+                self.remove_timer(id);
+            }
+        }
+    }
+
+    /// Spawn the task for the next run of timer `id`.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_timer`].
+    unsafe fn start_timer_run(&mut self, id: TimerId) {
+        let timer = self.timers.get_mut(&id).expect("timer to run");
+        let body = timer.body();
+        let meta = TaskMeta {
+            name: timer.spec.name.clone(),
+            ..TaskMeta::default()
+        };
+        let pri = timer.spec.pri;
+        let tid = unsafe { self.spawn_task(pri, None, meta, TaskLimits::default(), body) };
+        self.timers.get_mut(&id).unwrap().running = Some(tid);
+        self.timer_runs.insert(tid, id);
+    }
+
+    /// Let the timer `tid` ran for start its next owed run or retire.
+    fn timer_run_ended(&mut self, tid: TaskId) {
+        let Some(id) = self.timer_runs.remove(&tid) else {
+            return;
+        };
+        let Some(timer) = self.timers.get_mut(&id) else {
+            return;
+        };
+        if timer.run_ended() {
+            // SAFETY: the caller of `spawn_timer` vouched for the body.
+            unsafe { self.start_timer_run(id) };
+        } else if timer.exhausted() {
+            self.remove_timer(id);
+        }
+    }
+
+    /// Forget timer `id` along with its queued ticks.
+    fn remove_timer(&mut self, id: TimerId) -> Option<TimerState> {
+        self.timer_ticks.retain(|&Reverse((_, timer))| timer != id);
+        self.timer_descriptors.remove(&id);
+        self.timers.remove(&id)
+    }
+
+    /// Claim the stored outcome of `target` for a joiner.
+    fn take_result(&mut self, target: TaskId) -> Result<TaskOutput, TaskError> {
+        self.results
+            .remove(&target)
+            .unwrap_or(Err(TaskError::NotFound))
+    }
+
+    /// Build the next ready-queue entry for `tid` from its current priority
+    /// and share group.
+    fn ready_entry(&mut self, tid: TaskId) -> ReadyEntry {
+        let (pri, group) = self.tasks.get(&tid).map_or((10, 0), |t| (t.pri, t.group));
+        let entry = ReadyEntry::new(pri, self.seq, tid).in_group(group);
+        self.seq += 1;
+        entry
+    }
+
+    /// Insert `tid` into the ready queue respecting its priority.
+    fn push_ready(&mut self, tid: TaskId) {
+        if self.tasks.contains_key(&tid) {
+            let entry = self.ready_entry(tid);
+            self.ready.push(entry);
+        }
+    }
+
+    /// Let idle time pass until `wake_at`. A virtual clock jumps there at
+    /// once; a real one has to be waited on, which is left to the caller.
+    fn skip_idle(&mut self, wake_at: Instant) -> bool {
+        if !self.clock.is_virtual() {
+            return false;
+        }
+        let now = self.clock.now();
+        if wake_at > now {
+            self.clock.advance(wake_at - now);
+        }
+        true
+    }
+
+    /// Publish a Process Activity Log event about `tid`, stamped with the
+    /// scheduler's clock. `message` is only built if the event goes anywhere.
+    fn report(&self, tid: TaskId, stage: Stage, message: impl FnOnce() -> String) {
+        self.pal.publish_with(|| TaskEvent {
+            ts: self.calendar_base() + (self.clock.now() - self.epoch),
+            task_id: tid,
+            stage,
+            message: message(),
+        });
+    }
+
+    /// Publish that `tid` waits for `targets` to end.
+    fn report_join(&self, tid: TaskId, targets: &[TaskId]) {
+        self.report(tid, Stage::Joining, || match targets {
+            [target] => format!("joining task {target}"),
+            _ => format!("joining any of tasks {targets:?}"),
+        });
+    }
+
+    /// Return the earliest instant at which a sleeping task should be woken.
+    fn next_wake_instant(&self) -> Option<Instant> {
+        let sleep = self.sleepers.peek().map(|Reverse((when, _))| *when);
+        let timeout = self
+            .timeout_waiters
+            .peek()
+            .map(|Reverse((when, _, _))| *when);
+        let restart = self.restarts.peek().map(|Reverse((when, _, _))| *when);
+        let hard_cancel = self.hard_cancels.peek().map(|Reverse((when, _))| *when);
+        let deadline = self
+            .task_deadlines
+            .iter()
+            .filter(|Reverse((_, tid))| self.tasks.contains_key(tid))
+            .map(|Reverse((when, _))| *when)
+            .min();
+        let timer = self
+            .timer_ticks
+            .iter()
+            .filter(|&&Reverse((at, id))| self.timer_due(id) == Some(at))
+            .map(|Reverse((at, _))| *at)
+            .min();
+        let gate = self
+            .gates
+            .values()
+            .filter_map(PendingWait::until)
+            .map(|until| self.epoch + until)
+            .filter(|&at| at > self.clock.now())
+            .min();
+        [sleep, timeout, restart, hard_cancel, deadline, timer, gate]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Process a syscall emitted by `tid`, updating scheduler state and queueing follow-up work.
+    fn handle_syscall(&mut self, tid: TaskId, syscall: SystemCall, done: &mut Vec<TaskId>) {
+        self.yielded(tid);
+        let _span = self.tasks.get_mut(&tid).map(|task| {
+            task.syscalls += 1;
+            task.span.clone().entered()
+        });
+        let traced = match &self.trace {
+            Some(_) if self.tasks.contains_key(&tid) => {
+                Some((TraceCall::from(&syscall), done.len()))
+            }
+            _ => None,
+        };
+        let expects_reply = syscall.expects_reply();
+        let mut requeue = true;
+        if expects_reply {
+            self.parked.insert(tid);
+        }
+        match syscall {
+            SystemCall::Log(msg) => tracing::info!(task = %tid, "{}", msg),
+            SystemCall::Report { stage, message } => self.report(tid, stage, || message),
+            SystemCall::Sleep(dur) => {
+                tracing::info!(task = %tid, "sleeping {:?}", dur);
+                self.report(tid, Stage::Sleeping, || format!("sleeping {dur:?}"));
+                let wake_at = self.clock.now() + dur;
+                self.sleepers.push(Reverse((wake_at, tid)));
+                requeue = false;
+            }
+            SystemCall::Done => {
+                // Bodies report `Done` again when they return; only the first
+                // one for a live task counts.
+                if let Some(task) = self.tasks.remove(&tid) {
+                    tracing::info!(task = %tid, "task done");
+                    // Close the reply channel first so a task still parked
+                    // after an explicit `Done` can run to completion.
+                    let Task {
+                        handle,
+                        reply_tx,
+                        calls,
+                        cancel,
+                        ..
+                    } = task;
+                    drop(reply_tx);
+                    let res =
+                        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handle.join()));
+                    let state = match res {
+                        // A cancelled task yields no value however it ended.
+                        _ if cancel.is_cancelled() => self.record_stopped(tid),
+                        Ok(Ok(out)) => {
+                            self.results.insert(tid, Ok(out));
+                            TaskState::Finished
+                        }
+                        Ok(Err(payload)) => {
+                            let mut msg = panic_message(payload.as_ref());
+                            let calls = calls.lock().unwrap_or_else(PoisonError::into_inner);
+                            if !calls.panic_frames().is_empty() {
+                                msg = format!("{msg} (in {})", calls.panic_frames().join(" > "));
+                            }
+                            self.results.insert(tid, Err(TaskError::Panicked(msg)));
+                            TaskState::Failed
+                        }
+                        Err(_) => {
+                            self.results
+                                .insert(tid, Err(TaskError::Panicked("join failed".into())));
+                            TaskState::Failed
+                        }
+                    };
+                    self.states.insert(tid, state);
+                    self.complete_task(tid, state);
+                    done.push(tid);
+                    // A failed parent takes its whole subtree down with it.
+                    if state == TaskState::Failed {
+                        self.cancel_descendants(tid, done);
+                    }
+                    let failed = matches!(state, TaskState::Failed | TaskState::TimedOut);
+                    self.child_exited(tid, failed, done);
+                }
+                requeue = false;
+            }
+            SystemCall::Join(target) => {
+                if self.tasks.contains_key(&target) {
+                    self.report_join(tid, &[target]);
+                    self.wait_map.wait_for(target, tid);
+                    requeue = false;
                 }
             }
             SystemCall::JoinTimeout { target, dur } => {
                 if self.tasks.contains_key(&target) {
+                    self.report_join(tid, &[target]);
                     self.wait_map.wait_for(target, tid);
                     let wake_at = self.clock.now() + dur;
-                    self.timeout_waiters.push(Reverse((wake_at, tid, target)));
+                    self.timeout_waiters
+                        .push(Reverse((wake_at, tid, WaitTarget::Task(target))));
                     requeue = false;
                 }
             }
-            SystemCall::Cancel(target) => {
-                if let Some(task) = self.tasks.remove(&target) {
-                    unsafe { task.handle.coroutine().cancel() };
-                    let _ = task.handle.join();
-                    self.states.insert(target, TaskState::Finished);
-                    let (waiters, _) = self.wait_map.complete(target, TaskState::Finished);
-                    for waiter in waiters {
-                        self.push_ready(waiter);
-                    }
-                    self.cancelled.insert(target);
-                    done.push(target);
+            SystemCall::Cancel(target) => self.request_cancel(target, done),
+            SystemCall::CancelTimer(id) => {
+                self.cancel_timer(id);
+            }
+            SystemCall::AwaitFuture => {
+                if self.wakeups.remove(&tid) {
+                    self.replies.insert(tid, SyscallReply::Woken);
+                } else {
+                    self.future_waiters.insert(tid);
+                    requeue = false;
                 }
             }
             SystemCall::IoWait(io_id) => {
-                self.wait_map.wait_io(io_id, tid);
-                requeue = false;
+                requeue = !self.wait_map.try_wait_io(io_id, tid);
+            }
+            SystemCall::IoWaitTimeout { source, dur } => {
+                if !self.wait_map.try_wait_io(source, tid) {
+                    self.replies
+                        .insert(tid, SyscallReply::IoWaited(IoWake::Ready));
+                } else if self.sim.is_some() {
+                    self.arm_deadline(tid, dur, WaitTarget::Io(source));
+                    requeue = false;
+                } else {
+                    // Readiness comes from the host, so the virtual clock,
+                    // which skips idle time, must not cut the wait short.
+                    let at = Instant::now() + dur;
+                    self.io_deadlines.insert(tid, at);
+                    self.io_timeouts.push(Reverse((at, tid, source)));
+                    requeue = false;
+                }
+            }
+            #[cfg(feature = "async-io")]
+            SystemCall::SpawnProcess(cmd) => {
+                let res = self.spawn_process(tid, *cmd);
+                self.replies.insert(tid, SyscallReply::ProcessSpawned(res));
+            }
+            #[cfg(feature = "async-io")]
+            SystemCall::WaitProcess(pid) => {
+                match self.processes.get_mut(&pid).filter(|p| !p.killed) {
+                    Some(proc) if proc.status.is_none() => {
+                        proc.waiters.push(tid);
+                        requeue = false;
+                    }
+                    Some(proc) => {
+                        let status = proc.status.expect("reaped process status");
+                        self.finish_process(pid);
+                        self.replies
+                            .insert(tid, SyscallReply::ProcessExited(Ok(status)));
+                    }
+                    None => {
+                        let res = Err(std::io::ErrorKind::NotFound.into());
+                        self.replies.insert(tid, SyscallReply::ProcessExited(res));
+                    }
+                }
             }
             SystemCall::Yield => {
                 // Cooperative yield: no action required other than requeueing
             }
+            SystemCall::SetPriority { target, pri } => {
+                if target == tid || self.is_descendant(target, tid) {
+                    self.set_priority(target, pri);
+                } else {
+                    tracing::warn!(task = %tid, %target, "ignoring SetPriority outside own subtree");
+                }
+            }
+            SystemCall::JoinResult { target, timeout } => {
+                if self.tasks.contains_key(&target) {
+                    self.report_join(tid, &[target]);
+                    self.wait_map.wait_for(target, tid);
+                    self.result_waiters.insert(
+                        tid,
+                        ResultWait {
+                            targets: vec![target],
+                            any: false,
+                        },
+                    );
+                    if let Some(dur) = timeout {
+                        self.arm_deadline(tid, dur, WaitTarget::Task(target));
+                    }
+                    requeue = false;
+                } else {
+                    let res = self.take_result(target);
+                    self.replies.insert(tid, SyscallReply::Joined(res));
+                }
+            }
+            SystemCall::JoinAny(targets) => {
+                if targets.is_empty() {
+                    self.replies.insert(tid, SyscallReply::Unknown(0));
+                } else if let Some(&target) = targets.iter().find(|t| !self.tasks.contains_key(t)) {
+                    let res = self.take_result(target);
+                    self.replies
+                        .insert(tid, SyscallReply::JoinedAny(target, res));
+                } else {
+                    self.report_join(tid, &targets);
+                    for &target in &targets {
+                        self.wait_map.wait_for(target, tid);
+                    }
+                    self.result_waiters
+                        .insert(tid, ResultWait { targets, any: true });
+                    requeue = false;
+                }
+            }
+            SystemCall::ChannelOpen { cap } => {
+                let chan = self.next_chan;
+                self.next_chan += 1;
+                self.channels.insert(chan, ChannelState::new(cap));
+                self.replies.insert(tid, SyscallReply::ChannelOpened(chan));
+            }
+            SystemCall::Send { chan, msg } => {
+                requeue = self.channel_send(tid, chan, msg);
+            }
+            SystemCall::Recv(chan) => {
+                requeue = self.channel_recv(tid, chan, None);
+            }
+            SystemCall::RecvTimeout { chan, dur } => {
+                requeue = self.channel_recv(tid, chan, Some(dur));
+            }
+            SystemCall::ChannelClose(chan) => self.channel_close(chan),
+            SystemCall::SyncCreate(kind) => {
+                let id = self.next_sync;
+                self.next_sync += 1;
+                self.syncs.insert(id, SyncState::new(kind));
+                self.replies.insert(tid, SyscallReply::SyncCreated(id));
+            }
+            SystemCall::Acquire(id) => {
+                requeue = self.sync_acquire(tid, id);
+            }
+            SystemCall::Release(id) => self.sync_release(tid, id),
+            SystemCall::EventSet(id) => self.event_set(id),
+            SystemCall::EventWait(id) => {
+                requeue = self.event_wait(tid, id);
+            }
+            SystemCall::BarrierWait(id) => {
+                requeue = self.barrier_wait(tid, id);
+            }
+            SystemCall::SyncDestroy(id) => self.sync_destroy(id),
+            SystemCall::Spawn {
+                pri,
+                meta,
+                limits,
+                f,
+            } => {
+                let child = unsafe { self.spawn_task(pri, Some(tid), meta, limits, f) };
+                tracing::info!(task = %tid, child = %child, "spawned child");
+                self.replies.insert(tid, SyscallReply::Spawned(child));
+            }
+            SystemCall::SpawnRegistered {
+                pri,
+                meta,
+                limits,
+                task,
+                f,
+            } => {
+                let child = unsafe { self.spawn_task(pri, Some(tid), meta, limits, f) };
+                tracing::info!(task = %tid, child = %child, kind = task.kind, "spawned child");
+                self.descriptors.insert(child, task);
+                self.replies.insert(tid, SyscallReply::Spawned(child));
+            }
+            SystemCall::AwaitRestore => match self.gates.get(&tid) {
+                Some(wait) if !self.gate_open(wait) => requeue = false,
+                _ => {
+                    self.gates.remove(&tid);
+                    self.replies.insert(tid, SyscallReply::Continue);
+                }
+            },
+        }
+        if self.tasks.get(&tid).is_some_and(|task| {
+            task.limits
+                .syscalls
+                .is_some_and(|budget| task.syscalls > budget)
+        }) {
+            self.expire(tid, TaskLimit::Syscalls, done);
+        }
+        if self
+            .tasks
+            .get(&tid)
+            .is_some_and(|task| task.cancel.aborts_waits())
+        {
+            self.abort_wait(tid);
+        }
+        if let Some((call, exits)) = traced {
+            let outcome = if !self.tasks.contains_key(&tid) {
+                TraceOutcome::Exited
+            } else if let Some(reply) = self.replies.get(&tid) {
+                TraceOutcome::Replied(reply.into())
+            } else if requeue && !expects_reply {
+                TraceOutcome::Continued
+            } else {
+                TraceOutcome::Blocked
+            };
+            self.record_trace(TraceEvent::Syscall {
+                tid,
+                call,
+                at: self.clock.now() - self.epoch,
+                outcome,
+            });
+            self.trace_exits(&done[exits..]);
         }
         if requeue && self.tasks.contains_key(&tid) {
             self.push_ready(tid);
         }
     }
 
+    fn trace_exits(&mut self, exited: &[TaskId]) {
+        for &tid in exited {
+            let state = self.states[&tid];
+            self.record_trace(TraceEvent::Exited {
+                tid,
+                state,
+                at: self.clock.now() - self.epoch,
+            });
+        }
+    }
+
+    fn record_trace(&mut self, event: TraceEvent) {
+        if let Some(sink) = &mut self.trace {
+            sink.record(&event);
+        }
+    }
+
     /// Retrieve the recorded state of a task if known.
     pub fn task_state(&self, tid: TaskId) -> Option<TaskState> {
         self.states.get(&tid).copied()
     }
+
+    /// Capture every live task together with the pending sleeper and
+    /// timeout entries.
+    pub fn snapshot(&self) -> SchedulerSnapshot {
+        let since = |at: Instant| at.saturating_duration_since(self.epoch);
+        let mut sleepers: Vec<SleeperSnapshot> = self
+            .sleepers
+            .iter()
+            .filter(|Reverse((_, tid))| self.tasks.contains_key(tid))
+            .map(|&Reverse((at, tid))| SleeperSnapshot {
+                tid,
+                until: since(at),
+            })
+            .collect();
+        sleepers.sort_by_key(|s| (s.until, s.tid));
+        // Entries stay in the heap after their wait ends; keep current ones.
+        let mut timeouts: Vec<TimeoutSnapshot> = self
+            .timeout_waiters
+            .iter()
+            .filter(|Reverse((at, tid, target))| {
+                self.wait_map.is_waiting(*tid, *target)
+                    && (!self.parked.contains(tid) || self.deadlines.get(tid) == Some(at))
+            })
+            .map(|&Reverse((at, tid, target))| TimeoutSnapshot {
+                tid,
+                target,
+                until: since(at),
+            })
+            .collect();
+        timeouts.sort_by_key(|t| (t.until, t.tid));
+
+        let mut tasks: Vec<TaskSnapshot> = self
+            .tasks
+            .values()
+            .map(|task| {
+                let tid = task.tid;
+                let status = if self.stalled.contains_key(&tid) {
+                    TaskStatus::Stalled
+                } else if self.ready.contains(tid) && self.is_runnable(tid) {
+                    TaskStatus::Ready
+                } else if let Some(t) = timeouts.iter().find(|t| t.tid == tid) {
+                    TaskStatus::TimedWait {
+                        target: t.target,
+                        until: t.until,
+                    }
+                } else if let Some(wait) = self.result_waiters.get(&tid) {
+                    TaskStatus::Joining {
+                        targets: wait.targets.clone(),
+                    }
+                } else if let Some(status) = self.wait_map.blocked_on(tid) {
+                    status
+                } else if let Some(s) = sleepers.iter().find(|s| s.tid == tid) {
+                    TaskStatus::Sleeping { until: s.until }
+                } else {
+                    TaskStatus::Running
+                };
+                TaskSnapshot {
+                    tid,
+                    name: task.meta.name.clone(),
+                    tags: task.meta.tags.clone(),
+                    call_stack: task
+                        .calls
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .frames()
+                        .to_vec(),
+                    pri: task.pri,
+                    group: task.group,
+                    status,
+                    parent: task.parent,
+                    spawned_at: since(task.spawned_at),
+                    syscalls: task.syscalls,
+                }
+            })
+            .collect();
+        tasks.sort_by_key(|t| t.tid);
+        SchedulerSnapshot {
+            now: since(self.clock.now()),
+            tasks,
+            sleepers,
+            timeouts,
+            stalls: self.stalls().to_vec(),
+            timers: self.timers(),
+        }
+    }
+
+    /// Return the task that spawned `tid`, if it was spawned by another task.
+    pub fn parent_of(&self, tid: TaskId) -> Option<TaskId> {
+        self.parents.get(&tid).copied()
+    }
+
+    /// Register a supervisor and start all of its children.
+    ///
+    /// # Safety
+    /// Every child is started with `may::coroutine::spawn`, on registration
+    /// and on each restart; see [`Scheduler::spawn_with_priority`].
+    pub unsafe fn supervise(&mut self, spec: SupervisorSpec) -> SupervisorId {
+        let sup = self.next_supervisor;
+        self.next_supervisor += 1;
+        let children = spec.children.len();
+        self.supervisors.insert(sup, SupervisorState::new(spec));
+        for idx in 0..children {
+            unsafe { self.start_child(sup, idx) };
+        }
+        sup
+    }
+
+    /// Register a timer that starts a task running `f` on every tick of
+    /// `spec`. The run loop keeps going while timers are registered.
+    ///
+    /// # Safety
+    /// Every run is started with `may::coroutine::spawn`; see
+    /// [`Scheduler::spawn_with_priority`].
+    pub unsafe fn spawn_timer<F>(&mut self, spec: TimerSpec, f: F) -> TimerId
+    where
+        F: Fn(TaskContext) + Send + Sync + 'static,
+    {
+        let id = self.next_timer.fetch_add(1, Ordering::Relaxed);
+        unsafe { self.add_timer(id, spec, Arc::new(f)) };
+        id
+    }
+
+    /// Run `f` every `interval`, skipping ticks missed during a run.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_timer`].
+    pub unsafe fn spawn_every<F>(&mut self, interval: Duration, f: F) -> TimerId
+    where
+        F: Fn(TaskContext) + Send + Sync + 'static,
+    {
+        unsafe { self.spawn_timer(TimerSpec::every(interval), f) }
+    }
+
+    /// Run `f` once at virtual time `at` since the scheduler was created.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_timer`].
+    pub unsafe fn spawn_at<F>(&mut self, at: Duration, f: F) -> TimerId
+    where
+        F: Fn(TaskContext) + Send + Sync + 'static,
+    {
+        unsafe { self.spawn_timer(TimerSpec::at(at), f) }
+    }
+
+    /// Run `f` whenever the cron expression `expr` matches; see
+    /// [`CronSchedule`](crate::timer::CronSchedule).
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_timer`].
+    pub unsafe fn spawn_cron<F>(&mut self, expr: &str, f: F) -> Result<TimerId, CronError>
+    where
+        F: Fn(TaskContext) + Send + Sync + 'static,
+    {
+        let spec = TimerSpec::cron(expr)?;
+        Ok(unsafe { self.spawn_timer(spec, f) })
+    }
+
+    /// Stop timer `id` from firing again; a run in progress carries on.
+    /// Returns `false` if no such timer is registered.
+    pub fn cancel_timer(&mut self, id: TimerId) -> bool {
+        let Some(timer) = self.remove_timer(id) else {
+            return false;
+        };
+        tracing::info!(timer = id, "timer cancelled");
+        if let Some(tid) = timer.running {
+            self.timer_runs.remove(&tid);
+        }
+        true
+    }
+
+    /// Make `kind` spawnable by name with
+    /// [`TaskBuilder::spawn_registered`] and restorable from a
+    /// [`Checkpoint`]. The body gets the arguments given at spawn.
+    pub fn register_task<A, F>(&mut self, kind: impl Into<String>, f: F)
+    where
+        A: DeserializeOwned + Send + 'static,
+        F: Fn(TaskContext, A) + Send + Sync + 'static,
+    {
+        self.registry.register(kind.into(), f);
+    }
+
+    /// Register a timer that starts a task of the registered `kind` with
+    /// `args` on every tick, so that checkpoints include it.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_timer`].
+    pub unsafe fn spawn_registered_timer(
+        &mut self,
+        spec: TimerSpec,
+        kind: &str,
+        args: &impl Serialize,
+    ) -> Result<TimerId, CheckpointError> {
+        let task = TaskDescriptor::new(kind, args)?;
+        let f = self.registry.build_timer(&task)?;
+        let id = self.next_timer.fetch_add(1, Ordering::Relaxed);
+        unsafe { self.add_timer(id, spec, f) };
+        self.timer_descriptors.insert(id, task);
+        Ok(id)
+    }
+
+    /// Capture the tasks and timers spawned from registered kinds, what the
+    /// tasks wait for and the virtual clock, for [`Scheduler::restore`].
+    pub fn checkpoint(&self) -> Checkpoint {
+        let snapshot = self.snapshot();
+        let mut tasks = Vec::new();
+        let mut skipped = Vec::new();
+        for snap in &snapshot.tasks {
+            let Some(task) = self.descriptors.get(&snap.tid) else {
+                // Runs of timers are owed again by their timer instead.
+                if !self.timer_runs.contains_key(&snap.tid) {
+                    skipped.push(snap.tid);
+                }
+                continue;
+            };
+            let live = &self.tasks[&snap.tid];
+            tasks.push(TaskCheckpoint {
+                id: snap.tid,
+                parent: snap.parent,
+                priority: snap.pri,
+                meta: (*live.meta).clone(),
+                limits: live.limits,
+                task: task.clone(),
+                // A restored task still held back keeps its old wait.
+                wait: self
+                    .gates
+                    .get(&snap.tid)
+                    .cloned()
+                    .or_else(|| PendingWait::from_status(&snap.status)),
+            });
+        }
+        let mut timers: Vec<TimerCheckpoint> = self
+            .timer_descriptors
+            .iter()
+            .filter_map(|(&id, task)| {
+                let timer = self.timers.get(&id)?;
+                Some(TimerCheckpoint {
+                    id,
+                    spec: timer.spec.clone(),
+                    task: task.clone(),
+                    next_at: timer.next,
+                    pending: timer.pending + u64::from(timer.running.is_some()),
+                    fired: timer.fired,
+                    skipped: timer.skipped,
+                })
+            })
+            .collect();
+        timers.sort_by_key(|t| t.id);
+        Checkpoint {
+            now: snapshot.now,
+            wall_epoch: self.wall_epoch,
+            tasks,
+            timers,
+            skipped,
+        }
+    }
+
+    /// Re-create the tasks and timers of `checkpoint`. The scheduler must
+    /// have no tasks or timers yet and know every kind the checkpoint uses.
+    ///
+    /// Virtual time resumes where the checkpoint left off. Tasks keep their
+    /// ids, parents, priorities, names, tags and limits, and start their body
+    /// over once the sleep or join they were in has ended. Timers keep their
+    /// next tick and counters, and a run that was in progress starts again.
+    /// A task whose parent was not spawned from a registered kind, and so is
+    /// not in the checkpoint, comes back as a root task: cancelling or failing
+    /// its old ancestors no longer reaches it.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_with_priority`].
+    pub unsafe fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), CheckpointError> {
+        if !self.tasks.is_empty() || !self.timers.is_empty() {
+            return Err(CheckpointError::NotEmpty);
+        }
+        // Build every body first so a bad checkpoint changes nothing.
+        let bodies = checkpoint
+            .tasks
+            .iter()
+            .map(|saved| self.registry.build(&saved.task))
+            .collect::<Result<Vec<_>, _>>()?;
+        let timer_bodies = checkpoint
+            .timers
+            .iter()
+            .map(|saved| self.registry.build_timer(&saved.task))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.resume_clock(checkpoint.now);
+        self.wall_epoch = checkpoint.wall_epoch;
+        for (saved, body) in checkpoint.tasks.iter().zip(bodies) {
+            let body: TaskFn = match &saved.wait {
+                Some(wait) => {
+                    self.gates.insert(saved.id, wait.clone());
+                    Box::new(
+                        move |ctx: TaskContext| match ctx.request(SystemCall::AwaitRestore) {
+                            SyscallReply::Cancelled => Box::new(()) as TaskOutput,
+                            _ => body(ctx),
+                        },
+                    )
+                }
+                None => body,
+            };
+            // Parents come first by id, so a missing one was skipped.
+            let parent = saved.parent.filter(|p| self.tasks.contains_key(p));
+            let meta = saved.meta.clone();
+            unsafe {
+                self.spawn_task_as(saved.id, saved.priority, parent, meta, saved.limits, body)
+            };
+            self.descriptors.insert(saved.id, saved.task.clone());
+        }
+        for (saved, f) in checkpoint.timers.iter().zip(timer_bodies) {
+            unsafe { self.add_timer(saved.id, saved.spec.clone(), f) };
+            let Some(timer) = self.timers.get_mut(&saved.id) else {
+                continue;
+            };
+            timer.next = saved.next_at;
+            timer.fired = saved.fired;
+            timer.skipped = saved.skipped;
+            timer.pending = saved.pending;
+            if let Some(next) = saved.next_at {
+                self.timer_ticks
+                    .push(Reverse((self.epoch + next, saved.id)));
+            }
+            self.timer_descriptors.insert(saved.id, saved.task.clone());
+            // SAFETY: the caller vouched for the bodies.
+            unsafe { self.timer_run_owed(saved.id) };
+        }
+
+        let last_task = checkpoint.tasks.iter().map(|t| t.id);
+        if let Some(last) = last_task.chain(checkpoint.skipped.iter().copied()).max() {
+            self.next_id.fetch_max(last + 1, Ordering::Relaxed);
+        }
+        if let Some(last) = checkpoint.timers.iter().map(|t| t.id).max() {
+            self.next_timer.fetch_max(last + 1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Move virtual time forward to `now`. A clock that cannot be moved has
+    /// virtual time zero moved back instead.
+    fn resume_clock(&mut self, now: Duration) {
+        let Some(ahead) = now.checked_sub(self.clock.now() - self.epoch) else {
+            return;
+        };
+        if !self.clock.advance(ahead)
+            && let Some(epoch) = self.epoch.checked_sub(ahead)
+        {
+            self.epoch = epoch;
+        }
+    }
+
+    /// Start a restored timer's owed run, or retire it if it has none left.
+    ///
+    /// # Safety
+    /// See [`Scheduler::spawn_timer`].
+    unsafe fn timer_run_owed(&mut self, id: TimerId) {
+        let timer = self.timers.get_mut(&id).expect("restored timer");
+        if timer.pending > 0 {
+            timer.pending -= 1;
+            unsafe { self.start_timer_run(id) };
+        } else if timer.exhausted() {
+            self.remove_timer(id);
+        }
+    }
+
+    /// Whether the wait a restored task was in before the checkpoint is over.
+    fn gate_open(&self, wait: &PendingWait) -> bool {
+        let now = self.clock.now() - self.epoch;
+        if wait.until().is_some_and(|until| until <= now) {
+            return true;
+        }
+        match wait {
+            PendingWait::Sleep { .. } => false,
+            PendingWait::Join { targets, .. } => targets
+                .iter()
+                .any(|target| !self.tasks.contains_key(target)),
+        }
+    }
+
+    /// Let the restored tasks whose old wait is over start their bodies.
+    fn open_gates(&mut self) {
+        let mut open: Vec<TaskId> = self
+            .gates
+            .iter()
+            .filter(|&(tid, wait)| {
+                self.parked.contains(tid) && !self.replies.contains_key(tid) && self.gate_open(wait)
+            })
+            .map(|(&tid, _)| tid)
+            .collect();
+        open.sort_unstable();
+        for tid in open {
+            self.gates.remove(&tid);
+            self.wake_with(tid, SyscallReply::Continue);
+        }
+    }
+
+    /// Registered timers ordered by id.
+    pub fn timers(&self) -> Vec<TimerSnapshot> {
+        let mut timers: Vec<TimerSnapshot> = self
+            .timers
+            .iter()
+            .map(|(&id, timer)| timer.snapshot(id))
+            .collect();
+        timers.sort_by_key(|t| t.id);
+        timers
+    }
+
+    /// Return whether `sup` is still restarting its children.
+    pub fn supervisor_status(&self, sup: SupervisorId) -> Option<SupervisorStatus> {
+        self.supervisors.get(&sup).map(|state| state.status)
+    }
+
+    /// Number of restarts `sup` has performed so far.
+    pub fn supervisor_restarts(&self, sup: SupervisorId) -> usize {
+        self.supervisors
+            .get(&sup)
+            .map_or(0, |state| state.total_restarts)
+    }
+
+    /// Current task of each child of `sup`, `None` while the child is down.
+    pub fn supervised_children(&self, sup: SupervisorId) -> &[Option<TaskId>] {
+        self.supervisors
+            .get(&sup)
+            .map(|state| state.running.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Order the ready queue with `policy` from now on.
+    pub fn set_policy(&mut self, policy: Box<dyn SchedulingPolicy>) {
+        self.ready.set_policy(policy);
+    }
+
+    /// Change the priority of `tid`, reordering it if it is already queued.
+    pub fn set_priority(&mut self, tid: TaskId, pri: u8) {
+        if let Some(task) = self.tasks.get_mut(&tid) {
+            task.pri = pri;
+            if self.ready.remove(tid) {
+                self.push_ready(tid);
+            }
+            let entry = self.ready_entry(tid);
+            self.wait_map.requeue_sync(entry);
+        }
+    }
+
+    /// Current priority of a live task.
+    pub fn priority_of(&self, tid: TaskId) -> Option<u8> {
+        self.tasks.get(&tid).map(|t| t.pri)
+    }
+
+    /// Charge `tid` to fair-share `group`. Tasks it spawns afterwards inherit
+    /// the group.
+    pub fn set_share_group(&mut self, tid: TaskId, group: ShareGroup) {
+        if let Some(task) = self.tasks.get_mut(&tid) {
+            task.group = group;
+            if self.ready.remove(tid) {
+                self.push_ready(tid);
+            }
+        }
+    }
+
+    /// Returns `true` if `tid` was spawned, directly or transitively, by
+    /// `ancestor`.
+    fn is_descendant(&self, mut tid: TaskId, ancestor: TaskId) -> bool {
+        while let Some(&parent) = self.parents.get(&tid) {
+            if parent == ancestor {
+                return true;
+            }
+            tid = parent;
+        }
+        false
+    }
+
+    /// Return the tasks spawned by `tid` in creation order.
+    pub fn children_of(&self, tid: TaskId) -> &[TaskId] {
+        self.children
+            .get(&tid)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
 }
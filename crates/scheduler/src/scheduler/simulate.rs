@@ -0,0 +1,90 @@
+//! Lockstep driver for [`Scheduler::simulated`] and [`Scheduler::replaying`].
+use std::time::Duration;
+
+use crossbeam::channel::RecvTimeoutError;
+
+use super::Scheduler;
+use crate::simulation::{Picker, SimOutcome};
+use crate::syscall::SyscallReply;
+use crate::task::TaskId;
+
+/// How long a stepped task may run before it must issue its next syscall.
+const STALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Scheduler {
+    /// Step tasks one at a time until they all finish or the run gets stuck.
+    pub(super) fn run_simulation(&mut self) -> Vec<TaskId> {
+        let mut done_order = Vec::new();
+        let outcome = loop {
+            self.wake_due(&mut done_order);
+            let mut runnable: Vec<TaskId> = self
+                .ready
+                .iter()
+                .map(|entry| entry.tid)
+                .filter(|&tid| self.is_runnable(tid))
+                .collect();
+            if runnable.is_empty() {
+                if let Some(wake_at) = self.next_wake_instant()
+                    && self.skip_idle(wake_at)
+                {
+                    continue;
+                }
+                if self.tasks.is_empty() {
+                    break SimOutcome::Completed;
+                }
+                let mut live: Vec<TaskId> = self.tasks.keys().copied().collect();
+                live.sort_unstable();
+                break SimOutcome::Deadlock(live);
+            }
+            // Queue order depends on the policy; pick from a canonical order.
+            runnable.sort_unstable();
+
+            let sim = self.sim.as_mut().expect("simulation state");
+            if sim.schedule.len() >= sim.max_steps {
+                break SimOutcome::StepLimit;
+            }
+            let step = sim.schedule.len();
+            let tid = match &mut sim.picker {
+                Picker::Seeded(rng) => runnable[rng.below(runnable.len())],
+                Picker::Replay(queue) => match queue.pop_front() {
+                    Some(tid) if runnable.contains(&tid) => tid,
+                    Some(expected) => break SimOutcome::Diverged { step, expected },
+                    None => runnable[0],
+                },
+            };
+            sim.schedule.push(tid);
+
+            self.ready.remove(tid);
+            let reply = self.replies.remove(&tid).unwrap_or(SyscallReply::Continue);
+            self.parked.remove(&tid);
+            self.deadlines.remove(&tid);
+            if let Some(task) = self.tasks.get(&tid) {
+                let _ = task.reply_tx.send(reply);
+            }
+
+            // Exactly one task is running; wait for its next syscall. Calls
+            // from other tasks can only be leftovers of tasks that already
+            // ended, e.g. the final `Done` after an explicit one.
+            loop {
+                match self.syscall_rx.recv_timeout(STALL_TIMEOUT) {
+                    Ok((call_tid, call)) => {
+                        self.handle_syscall(call_tid, call, &mut done_order);
+                        if call_tid == tid {
+                            break;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        let sim = self.sim.as_mut().expect("simulation state");
+                        sim.outcome = Some(SimOutcome::Stalled(tid));
+                        return done_order;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        unreachable!("scheduler holds a syscall sender")
+                    }
+                }
+            }
+        };
+        self.sim.as_mut().expect("simulation state").outcome = Some(outcome);
+        done_order
+    }
+}
@@ -0,0 +1,263 @@
+//! Multi-worker dispatch loop.
+//!
+//! Each worker owns a local [`ReadyQueue`]. Tasks made ready while a worker
+//! handles syscalls land in that worker's queue; an idle worker steals the
+//! sibling head its own policy would run next. Scheduler state is shared
+//! behind one lock, so wakeup semantics match [`Scheduler::run`].
+//!
+//! The lock covers syscall handling and the bookkeeping of a resume; the
+//! reply that wakes a task is sent after it is released, and picking the
+//! next task only touches the local queues. A worker with nothing to run
+//! parks on the syscall, I/O and control channels like the single loop,
+//! and is nudged when a sibling queues more tasks than it can run itself.
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async-io")]
+use crossbeam::channel::never;
+use crossbeam::channel::{Sender, bounded, select};
+
+#[cfg(feature = "async-io")]
+use super::RUNNING_POLL;
+use super::{IDLE_TIMEOUT, Scheduler};
+use crate::io::IoTrigger;
+use crate::policy::SchedulingPolicy;
+use crate::ready_queue::{ReadyEntry, ReadyQueue};
+use crate::task::TaskId;
+
+/// Per-worker ready queues with stealing.
+pub(crate) struct WorkQueues {
+    locals: Vec<Mutex<ReadyQueue>>,
+}
+
+impl WorkQueues {
+    pub fn new(workers: usize, policy: &dyn SchedulingPolicy) -> Self {
+        Self {
+            locals: (0..workers)
+                .map(|_| Mutex::new(ReadyQueue::with_policy(policy.fork())))
+                .collect(),
+        }
+    }
+
+    /// Queue `entry` on worker `w`.
+    pub fn push(&self, w: usize, entry: ReadyEntry) {
+        self.locals[w].lock().unwrap().push(entry);
+    }
+
+    /// Pop from worker `w`'s own queue, stealing from a sibling if it is
+    /// empty.
+    pub fn pop(&self, w: usize) -> Option<TaskId> {
+        if let Some(tid) = self.locals[w].lock().unwrap().pop() {
+            return Some(tid);
+        }
+        self.steal(w)
+    }
+
+    /// Take the head of another worker's queue, letting the thief's policy
+    /// choose among the heads.
+    fn steal(&self, thief: usize) -> Option<TaskId> {
+        loop {
+            let (heads, victims): (Vec<ReadyEntry>, Vec<usize>) = self
+                .locals
+                .iter()
+                .enumerate()
+                .filter(|&(v, _)| v != thief)
+                .filter_map(|(v, q)| q.lock().unwrap().peek().map(|e| (*e, v)))
+                .unzip();
+            if heads.is_empty() {
+                return None;
+            }
+            let pick = self.locals[thief].lock().unwrap().policy().select(&heads);
+            let victim = victims[pick];
+            // The head may have been taken since we looked; look again.
+            if let Some(tid) = self.locals[victim].lock().unwrap().pop() {
+                return Some(tid);
+            }
+        }
+    }
+
+    /// Returns `true` if no worker has a queued task.
+    pub fn is_empty(&self) -> bool {
+        self.locals.iter().all(|q| q.lock().unwrap().is_empty())
+    }
+}
+
+/// State shared by all workers behind one lock.
+struct Shared<'a> {
+    sched: &'a mut Scheduler,
+    done: Vec<TaskId>,
+    /// Last time any worker handled a syscall or resumed a task.
+    last_progress: Instant,
+}
+
+/// Wake up to `n` parked workers.
+fn nudge(wake_tx: &Sender<()>, n: usize) {
+    for _ in 0..n {
+        if wake_tx.try_send(()).is_err() {
+            break;
+        }
+    }
+}
+
+impl Scheduler {
+    /// How long an idle worker may park. With `async-io`, readiness only
+    /// shows up by polling, so a worker parks briefly while anything is
+    /// registered with the poller.
+    fn park_timeout(&self) -> Duration {
+        let timeout = self.idle_timeout();
+        #[cfg(feature = "async-io")]
+        if !self.sources.is_empty() || !self.process_tokens.is_empty() {
+            return timeout.min(RUNNING_POLL);
+        }
+        timeout
+    }
+
+    /// Drive the scheduler with `self.workers` dispatch loops.
+    pub(super) fn run_workers(&mut self) -> Vec<TaskId> {
+        let workers = self.workers;
+        let queues = WorkQueues::new(workers, self.ready.policy());
+        let (syscall_rx, control_rx) = (self.syscall_rx.clone(), self.control_rx.clone());
+        #[cfg(not(feature = "async-io"))]
+        let io_rx = self.io_rx.clone();
+        // Readiness comes from the poller instead.
+        #[cfg(feature = "async-io")]
+        let io_rx = never();
+        // One token per worker is enough to wake them all.
+        let (wake_tx, wake_rx) = bounded(workers);
+        // Tasks popped by a worker but not yet resumed.
+        let in_flight = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let core = Mutex::new(Shared {
+            sched: self,
+            done: Vec::new(),
+            last_progress: Instant::now(),
+        });
+
+        std::thread::scope(|s| {
+            for w in 0..workers {
+                let (core, queues, stop, in_flight) = (&core, &queues, &stop, &in_flight);
+                let (syscall_rx, io_rx, control_rx) = (&syscall_rx, &io_rx, &control_rx);
+                let (wake_tx, wake_rx) = (&wake_tx, &wake_rx);
+                s.spawn(move || {
+                    while !stop.load(Ordering::Acquire) {
+                        let queued = {
+                            let mut guard = core.lock().unwrap();
+                            let Shared {
+                                sched,
+                                done,
+                                last_progress,
+                            } = &mut *guard;
+                            if !sched.keep_running() {
+                                stop.store(true, Ordering::Release);
+                                nudge(wake_tx, workers);
+                                break;
+                            }
+                            sched.apply_control(done);
+                            sched.wake_due(done);
+                            sched.drain_io();
+                            while let Ok((tid, call)) = syscall_rx.try_recv() {
+                                sched.handle_syscall(tid, call, done);
+                                *last_progress = Instant::now();
+                            }
+                            let mut queued = 0usize;
+                            while let Some(entry) = sched.ready.pop_entry() {
+                                queues.push(w, entry);
+                                queued += 1;
+                            }
+                            queued
+                        };
+                        // Let parked siblings steal what this worker cannot
+                        // run right away.
+                        nudge(wake_tx, queued.saturating_sub(1).min(workers - 1));
+
+                        in_flight.fetch_add(1, Ordering::AcqRel);
+                        if let Some(tid) = queues.pop(w) {
+                            let resume = {
+                                let mut guard = core.lock().unwrap();
+                                guard.last_progress = Instant::now();
+                                if guard.sched.is_runnable(tid) {
+                                    guard.sched.take_reply(tid)
+                                } else {
+                                    None
+                                }
+                            };
+                            if let Some((reply_tx, reply)) = resume {
+                                let _ = reply_tx.send(reply);
+                            }
+                            in_flight.fetch_sub(1, Ordering::AcqRel);
+                            continue;
+                        }
+                        in_flight.fetch_sub(1, Ordering::AcqRel);
+
+                        let timeout = {
+                            let mut guard = core.lock().unwrap();
+                            let sched = &mut *guard.sched;
+                            // The syscalls handled above may have ended the
+                            // last task; stop at the top of the loop.
+                            if !sched.keep_running() {
+                                continue;
+                            }
+                            // Advance virtual time only once every worker is idle.
+                            if in_flight.load(Ordering::Acquire) == 0
+                                && syscall_rx.is_empty()
+                                && sched.ready.is_empty()
+                                && queues.is_empty()
+                                && let Some(wake_at) = sched.next_wake_instant()
+                                && sched.skip_idle(wake_at)
+                            {
+                                continue;
+                            }
+                            sched.park_timeout()
+                        };
+
+                        select! {
+                            recv(syscall_rx) -> msg => match msg {
+                                Ok((tid, call)) => {
+                                    let mut guard = core.lock().unwrap();
+                                    let Shared {
+                                        sched,
+                                        done,
+                                        last_progress,
+                                    } = &mut *guard;
+                                    sched.handle_syscall(tid, call, done);
+                                    *last_progress = Instant::now();
+                                }
+                                Err(_) => break,
+                            },
+                            recv(io_rx) -> msg => {
+                                if let Ok(io_id) = msg {
+                                    let mut guard = core.lock().unwrap();
+                                    guard.sched.io_ready(io_id, IoTrigger::WakeAll);
+                                }
+                            }
+                            recv(control_rx) -> msg => {
+                                if let Ok(cmd) = msg {
+                                    let mut guard = core.lock().unwrap();
+                                    let Shared { sched, done, .. } = &mut *guard;
+                                    sched.apply(cmd, done);
+                                }
+                            }
+                            recv(wake_rx) -> _ => {}
+                            default(timeout) => {
+                                let mut guard = core.lock().unwrap();
+                                guard.sched.check_stalls();
+                                // Keep going while a resumed task may still
+                                // yield, however long it hogs its worker.
+                                if guard.last_progress.elapsed() >= IDLE_TIMEOUT
+                                    && guard.sched.may_quit_idle()
+                                {
+                                    tracing::warn!(worker = w, "scheduler idle timeout");
+                                    stop.store(true, Ordering::Release);
+                                    nudge(wake_tx, workers);
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        core.into_inner().unwrap().done
+    }
+}
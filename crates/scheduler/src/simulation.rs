@@ -0,0 +1,78 @@
+//! Deterministic simulation of a scheduler run.
+//!
+//! In simulation mode every task runs in lockstep with the scheduler: after
+//! each syscall the task parks until the scheduler steps it again, so exactly
+//! one task executes at a time. The next task is drawn from the runnable set
+//! with a seeded RNG instead of by priority, letting a fuzzer explore
+//! interleavings. The chosen schedule is recorded and either the seed or the
+//! schedule itself replays the run exactly.
+use std::collections::VecDeque;
+
+use crate::task::TaskId;
+
+/// Small, stable PRNG (SplitMix64) so recorded seeds replay identically
+/// across toolchains and dependency upgrades.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform index in `0..n`; `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// How a simulated run ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimOutcome {
+    /// Every task finished.
+    Completed,
+    /// Live tasks remain but none can ever become runnable.
+    Deadlock(Vec<TaskId>),
+    /// The stepped task did not issue a syscall within the stall timeout,
+    /// e.g. because it blocked on something outside the scheduler.
+    Stalled(TaskId),
+    /// The step limit was reached.
+    StepLimit,
+    /// A replayed schedule picked a task that was not runnable at `step`.
+    Diverged { step: usize, expected: TaskId },
+}
+
+/// Where the next task to step comes from.
+pub(crate) enum Picker {
+    Seeded(SimRng),
+    Replay(VecDeque<TaskId>),
+}
+
+/// Scheduler-side state of a simulated run.
+pub(crate) struct Simulation {
+    pub picker: Picker,
+    pub schedule: Vec<TaskId>,
+    pub max_steps: usize,
+    pub outcome: Option<SimOutcome>,
+}
+
+impl Simulation {
+    pub fn new(picker: Picker) -> Self {
+        Self {
+            picker,
+            schedule: Vec::new(),
+            max_steps: 100_000,
+            outcome: None,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Point-in-time view of a scheduler for introspection.
+//!
+//! Times are virtual and measured from the scheduler's creation, so a
+//! snapshot serializes without reference to the host clock.
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::channel::ChannelId;
+use crate::policy::ShareGroup;
+use crate::sync::SyncId;
+use crate::task::TaskId;
+use crate::timer::TimerSnapshot;
+use crate::wait_map::WaitTarget;
+use crate::watchdog::StallReport;
+
+/// What a live task is doing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskStatus {
+    /// Queued to run.
+    Ready,
+    /// Executing, or parked on a reply the scheduler has not produced yet.
+    Running,
+    /// Sleeping until the given time.
+    Sleeping { until: Duration },
+    /// Waiting for other tasks to finish.
+    Joining { targets: Vec<TaskId> },
+    /// Waiting for readiness of an I/O source.
+    IoWait { source: u64 },
+    /// Waiting for a message on a channel.
+    Receiving { chan: ChannelId },
+    /// Waiting for room on a channel.
+    Sending { chan: ChannelId },
+    /// Parked on a lock, semaphore, event or barrier.
+    Synchronizing { id: SyncId },
+    /// Waiting on `target` until the timeout at `until` fires.
+    TimedWait { target: WaitTarget, until: Duration },
+    /// Running past the stall timeout without yielding.
+    Stalled,
+}
+
+/// One live task.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskSnapshot {
+    pub tid: TaskId,
+    pub name: Option<String>,
+    pub tags: BTreeMap<String, String>,
+    pub pri: u8,
+    pub group: ShareGroup,
+    pub status: TaskStatus,
+    /// Nested calls the task is inside, outermost first.
+    pub call_stack: Vec<String>,
+    pub parent: Option<TaskId>,
+    pub spawned_at: Duration,
+    /// Syscalls handled for this task so far.
+    pub syscalls: u64,
+}
+
+/// Pending entry of the sleeper heap.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SleeperSnapshot {
+    pub tid: TaskId,
+    pub until: Duration,
+}
+
+/// Pending entry of the timeout heap.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeoutSnapshot {
+    pub tid: TaskId,
+    pub target: WaitTarget,
+    pub until: Duration,
+}
+
+/// Result of [`Scheduler::snapshot`](crate::Scheduler::snapshot).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchedulerSnapshot {
+    /// Current virtual time.
+    pub now: Duration,
+    /// Live tasks ordered by id.
+    pub tasks: Vec<TaskSnapshot>,
+    /// Sleepers of live tasks in wake order.
+    pub sleepers: Vec<SleeperSnapshot>,
+    /// Timeouts of live tasks in expiry order.
+    pub timeouts: Vec<TimeoutSnapshot>,
+    /// Stalls the watchdog detected so far, oldest first.
+    pub stalls: Vec<StallReport>,
+    /// Registered timers ordered by id.
+    pub timers: Vec<TimerSnapshot>,
+}
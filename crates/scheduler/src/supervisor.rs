@@ -0,0 +1,175 @@
+//! Supervisors restart failed tasks according to a restart strategy.
+//!
+//! A supervisor owns a fixed list of child specifications. When a child
+//! panics the scheduler restarts it (and, depending on the strategy, some of
+//! its siblings) after an exponential backoff measured on the scheduler's
+//! virtual clock. Too many restarts within the intensity window make the
+//! supervisor give up and cancel its remaining children. Children that
+//! return or are cancelled from outside are not restarted.
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::syscall::TaskFn;
+use crate::task::{TaskContext, TaskId, TaskOutput};
+
+/// Identifier of a supervisor registered with [`Scheduler::supervise`].
+///
+/// [`Scheduler::supervise`]: crate::Scheduler::supervise
+pub type SupervisorId = u64;
+
+/// Which children are restarted when one of them fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestartStrategy {
+    /// Restart only the failed child.
+    OneForOne,
+    /// Cancel every other live child and restart all of them.
+    OneForAll,
+    /// Cancel the live children declared after the failed one and restart
+    /// the failed child together with them.
+    RestForOne,
+}
+
+/// Lifecycle of a supervisor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SupervisorStatus {
+    /// Children are running or waiting to be restarted.
+    Running,
+    /// The restart intensity was exceeded and every child was cancelled.
+    GaveUp,
+}
+
+type Factory = Arc<dyn Fn(TaskContext) + Send + Sync + 'static>;
+
+/// Child started, and restarted, by a supervisor.
+#[derive(Clone)]
+pub(crate) struct ChildSpec {
+    pub pri: u8,
+    factory: Factory,
+}
+
+impl ChildSpec {
+    /// Build a fresh body for the next incarnation of the child.
+    pub fn body(&self) -> TaskFn {
+        let factory = Arc::clone(&self.factory);
+        Box::new(move |ctx| {
+            factory(ctx);
+            Box::new(()) as TaskOutput
+        })
+    }
+}
+
+/// Description of a supervisor and the children it manages.
+#[derive(Clone)]
+pub struct SupervisorSpec {
+    pub(crate) strategy: RestartStrategy,
+    pub(crate) children: Vec<ChildSpec>,
+    max_restarts: usize,
+    window: Duration,
+    backoff_base: Duration,
+    backoff_max: Duration,
+}
+
+impl SupervisorSpec {
+    /// Create a spec with no children, allowing 3 restarts per 5 seconds and
+    /// a backoff doubling from 10ms up to 1s.
+    pub fn new(strategy: RestartStrategy) -> Self {
+        Self {
+            strategy,
+            children: Vec::new(),
+            max_restarts: 3,
+            window: Duration::from_secs(5),
+            backoff_base: Duration::from_millis(10),
+            backoff_max: Duration::from_secs(1),
+        }
+    }
+
+    /// Add a child running `f` with the given priority. `f` is called again
+    /// for every restart.
+    pub fn child<F>(mut self, pri: u8, f: F) -> Self
+    where
+        F: Fn(TaskContext) + Send + Sync + 'static,
+    {
+        self.children.push(ChildSpec {
+            pri,
+            factory: Arc::new(f),
+        });
+        self
+    }
+
+    /// Give up once more than `max_restarts` restarts happen within `window`.
+    pub fn intensity(mut self, max_restarts: usize, window: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.window = window;
+        self
+    }
+
+    /// Delay the first restart by `base`, doubling for each further restart
+    /// inside the intensity window up to `max`.
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.backoff_base = base;
+        self.backoff_max = max;
+        self
+    }
+}
+
+/// Scheduler-side state of a supervisor.
+pub(crate) struct SupervisorState {
+    pub spec: SupervisorSpec,
+    /// Current incarnation of each child, `None` while it is down.
+    pub running: Vec<Option<TaskId>>,
+    /// Times of the restarts still inside the intensity window.
+    recent: VecDeque<Instant>,
+    pub total_restarts: usize,
+    pub status: SupervisorStatus,
+}
+
+impl SupervisorState {
+    pub fn new(spec: SupervisorSpec) -> Self {
+        let running = vec![None; spec.children.len()];
+        Self {
+            spec,
+            running,
+            recent: VecDeque::new(),
+            total_restarts: 0,
+            status: SupervisorStatus::Running,
+        }
+    }
+
+    /// Indices of the children to restart after child `idx` failed: the
+    /// failed child plus the live siblings its strategy takes down.
+    pub fn affected(&self, idx: usize) -> Vec<usize> {
+        let range = match self.spec.strategy {
+            RestartStrategy::OneForOne => idx..idx + 1,
+            RestartStrategy::OneForAll => 0..self.running.len(),
+            RestartStrategy::RestForOne => idx..self.running.len(),
+        };
+        range
+            .filter(|&i| i == idx || self.running[i].is_some())
+            .collect()
+    }
+
+    /// Record a restart at `now` and return the backoff before it happens,
+    /// or `None` if the intensity limit is exceeded.
+    pub fn record_restart(&mut self, now: Instant) -> Option<Duration> {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&at| now.duration_since(at) > self.spec.window)
+        {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= self.spec.max_restarts {
+            return None;
+        }
+        let exp = u32::try_from(self.recent.len()).unwrap_or(u32::MAX).min(31);
+        let delay = self
+            .spec
+            .backoff_base
+            .saturating_mul(1 << exp)
+            .min(self.spec.backoff_max);
+        self.recent.push_back(now);
+        self.total_restarts += 1;
+        Some(delay)
+    }
+}
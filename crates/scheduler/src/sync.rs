@@ -0,0 +1,253 @@
+//! Cooperative synchronization primitives that park tasks in the scheduler.
+//!
+//! Unlike `std::sync` types these never block the OS thread underneath
+//! `may`; a task waiting on a lock or signal is parked in the [`WaitMap`]
+//! and resumed in priority order, FIFO among equal priorities.
+//!
+//! Handles are plain ids, so the scheduler cannot tell when the last copy is
+//! gone: an object lives until one of its handles calls `destroy`. Tasks
+//! still parked on it, and later requests naming it, panic.
+//!
+//! [`WaitMap`]: crate::WaitMap
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::unwind_cancelled;
+use crate::syscall::{SyscallReply, SystemCall};
+use crate::task::{TaskContext, TaskId};
+
+/// Identifier of a scheduler-managed synchronization object.
+pub type SyncId = u64;
+
+/// Kind of object created by [`SystemCall::SyncCreate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncKind {
+    /// Mutual exclusion lock with a single owner.
+    Mutex,
+    /// Counting semaphore starting with the given number of permits.
+    Semaphore(usize),
+    /// One-shot event; once set every current and future waiter passes.
+    Event,
+    /// Reusable barrier releasing waiters once the given number arrive.
+    Barrier(usize),
+}
+
+/// Scheduler-side state of a synchronization object.
+pub(crate) enum SyncState {
+    Mutex { owner: Option<TaskId> },
+    Semaphore { permits: usize },
+    Event { set: bool },
+    Barrier { parties: usize, arrived: usize },
+}
+
+impl SyncState {
+    pub fn new(kind: SyncKind) -> Self {
+        match kind {
+            SyncKind::Mutex => Self::Mutex { owner: None },
+            SyncKind::Semaphore(permits) => Self::Semaphore { permits },
+            SyncKind::Event => Self::Event { set: false },
+            SyncKind::Barrier(parties) => Self::Barrier {
+                parties: parties.max(1),
+                arrived: 0,
+            },
+        }
+    }
+}
+
+fn create(ctx: &TaskContext, kind: SyncKind) -> SyncId {
+    match ctx.request(SystemCall::SyncCreate(kind)) {
+        SyscallReply::SyncCreated(id) => id,
+        other => panic!("unexpected reply to SyncCreate: {other:?}"),
+    }
+}
+
+fn destroy(ctx: &TaskContext, id: SyncId) {
+    ctx.syscall(SystemCall::SyncDestroy(id));
+}
+
+fn acquire(ctx: &TaskContext, id: SyncId) {
+    match ctx.request(SystemCall::Acquire(id)) {
+        SyscallReply::Acquired => {}
+        SyscallReply::Cancelled => unwind_cancelled(),
+        SyscallReply::Unknown(_) => panic!("no such mutex or semaphore: {id}"),
+        other => panic!("unexpected reply to Acquire({id}): {other:?}"),
+    }
+}
+
+/// Cooperative mutual exclusion lock.
+///
+/// Locking is not re-entrant: a task that locks a mutex it already holds
+/// parks forever.
+#[derive(Clone, Copy, Debug)]
+pub struct Mutex {
+    id: SyncId,
+}
+
+impl Mutex {
+    /// Create an unlocked mutex.
+    pub fn new(ctx: &TaskContext) -> Self {
+        Self::from_id(create(ctx, SyncKind::Mutex))
+    }
+
+    /// Wrap an existing mutex id.
+    pub fn from_id(id: SyncId) -> Self {
+        Self { id }
+    }
+
+    /// Identifier of the underlying object.
+    pub fn id(&self) -> SyncId {
+        self.id
+    }
+
+    /// Park until the lock is free and take it. The lock is released when
+    /// the guard is dropped.
+    pub fn lock<'a>(&self, ctx: &'a TaskContext) -> MutexGuard<'a> {
+        acquire(ctx, self.id);
+        MutexGuard { ctx, id: self.id }
+    }
+
+    /// Free the mutex. A guard still held on it releases nothing when dropped.
+    pub fn destroy(self, ctx: &TaskContext) {
+        destroy(ctx, self.id);
+    }
+}
+
+/// Held lock on a [`Mutex`]; unlocks on drop.
+pub struct MutexGuard<'a> {
+    ctx: &'a TaskContext,
+    id: SyncId,
+}
+
+impl Drop for MutexGuard<'_> {
+    fn drop(&mut self) {
+        self.ctx.syscall(SystemCall::Release(self.id));
+    }
+}
+
+/// Cooperative counting semaphore.
+#[derive(Clone, Copy, Debug)]
+pub struct Semaphore {
+    id: SyncId,
+}
+
+impl Semaphore {
+    /// Create a semaphore holding `permits` permits.
+    pub fn new(ctx: &TaskContext, permits: usize) -> Self {
+        Self::from_id(create(ctx, SyncKind::Semaphore(permits)))
+    }
+
+    /// Wrap an existing semaphore id.
+    pub fn from_id(id: SyncId) -> Self {
+        Self { id }
+    }
+
+    /// Identifier of the underlying object.
+    pub fn id(&self) -> SyncId {
+        self.id
+    }
+
+    /// Park until a permit is available and take it. The permit is returned
+    /// when the guard is dropped.
+    pub fn acquire<'a>(&self, ctx: &'a TaskContext) -> SemaphorePermit<'a> {
+        acquire(ctx, self.id);
+        SemaphorePermit { ctx, id: self.id }
+    }
+
+    /// Free the semaphore. Outstanding permits return nothing when dropped.
+    pub fn destroy(self, ctx: &TaskContext) {
+        destroy(ctx, self.id);
+    }
+}
+
+/// Permit taken from a [`Semaphore`]; returned on drop.
+pub struct SemaphorePermit<'a> {
+    ctx: &'a TaskContext,
+    id: SyncId,
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.ctx.syscall(SystemCall::Release(self.id));
+    }
+}
+
+/// One-shot event that releases every waiter once set.
+#[derive(Clone, Copy, Debug)]
+pub struct Event {
+    id: SyncId,
+}
+
+impl Event {
+    /// Create an unset event.
+    pub fn new(ctx: &TaskContext) -> Self {
+        Self::from_id(create(ctx, SyncKind::Event))
+    }
+
+    /// Wrap an existing event id.
+    pub fn from_id(id: SyncId) -> Self {
+        Self { id }
+    }
+
+    /// Identifier of the underlying object.
+    pub fn id(&self) -> SyncId {
+        self.id
+    }
+
+    /// Set the event, waking every parked waiter.
+    pub fn set(&self, ctx: &TaskContext) {
+        ctx.syscall(SystemCall::EventSet(self.id));
+    }
+
+    /// Park until the event is set. Returns immediately if it already is.
+    pub fn wait(&self, ctx: &TaskContext) {
+        match ctx.request(SystemCall::EventWait(self.id)) {
+            SyscallReply::Signalled => {}
+            SyscallReply::Cancelled => unwind_cancelled(),
+            SyscallReply::Unknown(id) => panic!("no such event: {id}"),
+            other => panic!("unexpected reply to EventWait: {other:?}"),
+        }
+    }
+
+    /// Free the event.
+    pub fn destroy(self, ctx: &TaskContext) {
+        destroy(ctx, self.id);
+    }
+}
+
+/// Reusable barrier for a fixed number of tasks.
+#[derive(Clone, Copy, Debug)]
+pub struct Barrier {
+    id: SyncId,
+}
+
+impl Barrier {
+    /// Create a barrier that releases once `parties` tasks are waiting.
+    pub fn new(ctx: &TaskContext, parties: usize) -> Self {
+        Self::from_id(create(ctx, SyncKind::Barrier(parties)))
+    }
+
+    /// Wrap an existing barrier id.
+    pub fn from_id(id: SyncId) -> Self {
+        Self { id }
+    }
+
+    /// Identifier of the underlying object.
+    pub fn id(&self) -> SyncId {
+        self.id
+    }
+
+    /// Park until all parties arrive. Returns `true` for exactly one task per
+    /// generation: the last to arrive.
+    pub fn wait(&self, ctx: &TaskContext) -> bool {
+        match ctx.request(SystemCall::BarrierWait(self.id)) {
+            SyscallReply::BarrierPassed { leader } => leader,
+            SyscallReply::Cancelled => unwind_cancelled(),
+            SyscallReply::Unknown(id) => panic!("no such barrier: {id}"),
+            other => panic!("unexpected reply to BarrierWait: {other:?}"),
+        }
+    }
+
+    /// Free the barrier.
+    pub fn destroy(self, ctx: &TaskContext) {
+        destroy(ctx, self.id);
+    }
+}
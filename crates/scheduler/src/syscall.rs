@@ -1,12 +1,34 @@
 use crate::TaskId;
+use crate::channel::{ChannelError, ChannelId, Message};
+use crate::checkpoint::TaskDescriptor;
+use crate::io::IoWake;
+use crate::pal::Stage;
+#[cfg(feature = "async-io")]
+use crate::process::{ChildProcess, ProcessId};
+use crate::sync::{SyncId, SyncKind};
+use crate::task::{TaskContext, TaskError, TaskLimits, TaskMeta, TaskOutput};
+use crate::timer::TimerId;
+use std::fmt;
+#[cfg(feature = "async-io")]
+use std::io;
+#[cfg(feature = "async-io")]
+use std::process::{Command, ExitStatus};
 use std::time::Duration;
 
+/// Boxed task body handed to the scheduler by [`SystemCall::Spawn`].
+pub type TaskFn = Box<dyn FnOnce(TaskContext) -> TaskOutput + Send + 'static>;
+
 /// Represents a system call yielded by a coroutine task.
-#[derive(Debug, Clone)]
+///
+/// Not `Clone`: [`SystemCall::Spawn`] and its kin carry a one-shot task body
+/// that can be handed to the scheduler only once.
 pub enum SystemCall {
     /// Print a log message
     Log(String),
 
+    /// Publish a Process Activity Log event about the calling task
+    Report { stage: Stage, message: String },
+
     /// Sleep for the given duration (blocking for now)
     Sleep(Duration),
 
@@ -19,12 +41,265 @@ pub enum SystemCall {
     /// Block until the given I/O resource is ready
     IoWait(u64),
 
+    /// Wait for an I/O resource to become ready but give up after a
+    /// timeout; the caller is resumed with [`SyscallReply::IoWaited`]
+    IoWaitTimeout { source: u64, dur: Duration },
+
+    /// Stop a timer from firing again
+    CancelTimer(TimerId),
+
+    /// Park until the waker of a future the task is polling fires; the
+    /// caller is resumed with [`SyscallReply::Woken`]
+    AwaitFuture,
+
+    /// Start a child process watched by the scheduler; the caller is resumed
+    /// with [`SyscallReply::ProcessSpawned`]
+    #[cfg(feature = "async-io")]
+    SpawnProcess(Box<Command>),
+
+    /// Wait for a child process to exit; the caller is resumed with
+    /// [`SyscallReply::ProcessExited`]
+    #[cfg(feature = "async-io")]
+    WaitProcess(ProcessId),
+
     /// Cooperatively yield control back to the scheduler
     Yield,
 
-    /// Cancel another task immediately
+    /// Ask another task and every live descendant to stop; see [`crate::cancel`]
     Cancel(TaskId),
 
     /// Wait for a task to finish but resume after a timeout
     JoinTimeout { target: TaskId, dur: Duration },
+
+    /// Change the priority of the caller or one of its descendants
+    SetPriority { target: TaskId, pri: u8 },
+
+    /// Spawn a child task; the caller is resumed with [`SyscallReply::Spawned`]
+    Spawn {
+        pri: u8,
+        meta: TaskMeta,
+        limits: TaskLimits,
+        f: TaskFn,
+    },
+
+    /// Spawn a child task of a registered kind; the caller is resumed with
+    /// [`SyscallReply::Spawned`]
+    SpawnRegistered {
+        pri: u8,
+        meta: TaskMeta,
+        limits: TaskLimits,
+        task: TaskDescriptor,
+        f: TaskFn,
+    },
+
+    /// Hold a restored task until the wait it was in at checkpoint time is
+    /// over; the caller is resumed with [`SyscallReply::Continue`]
+    AwaitRestore,
+
+    /// Wait for a task to finish and claim its value, optionally giving up
+    /// after a timeout; the caller is resumed with [`SyscallReply::Joined`]
+    JoinResult {
+        target: TaskId,
+        timeout: Option<Duration>,
+    },
+
+    /// Wait for the first of several tasks to finish and claim its value;
+    /// the caller is resumed with [`SyscallReply::JoinedAny`]
+    JoinAny(Vec<TaskId>),
+
+    /// Create a channel with an optional capacity; the caller is resumed
+    /// with [`SyscallReply::ChannelOpened`]
+    ChannelOpen { cap: Option<usize> },
+
+    /// Send a message, parking while the channel is full
+    Send { chan: ChannelId, msg: Message },
+
+    /// Receive a message, parking until one is available
+    Recv(ChannelId),
+
+    /// Receive a message but resume after a timeout
+    RecvTimeout { chan: ChannelId, dur: Duration },
+
+    /// Close a channel and wake every task parked on it
+    ChannelClose(ChannelId),
+
+    /// Create a mutex, semaphore, event or barrier; the caller is resumed
+    /// with [`SyscallReply::SyncCreated`]
+    SyncCreate(SyncKind),
+
+    /// Lock a mutex or take a semaphore permit, parking until available
+    Acquire(SyncId),
+
+    /// Unlock a mutex or return a semaphore permit
+    Release(SyncId),
+
+    /// Set a one-shot event, waking all of its waiters
+    EventSet(SyncId),
+
+    /// Park until an event is set
+    EventWait(SyncId),
+
+    /// Park until every party of a barrier has arrived
+    BarrierWait(SyncId),
+
+    /// Free a synchronization object, waking its waiters with
+    /// [`SyscallReply::Unknown`]
+    SyncDestroy(SyncId),
+}
+
+impl fmt::Debug for SystemCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Log(msg) => f.debug_tuple("Log").field(msg).finish(),
+            Self::Report { stage, message } => f
+                .debug_struct("Report")
+                .field("stage", stage)
+                .field("message", message)
+                .finish(),
+            Self::Sleep(dur) => f.debug_tuple("Sleep").field(dur).finish(),
+            Self::Join(tid) => f.debug_tuple("Join").field(tid).finish(),
+            Self::Done => f.write_str("Done"),
+            Self::IoWait(id) => f.debug_tuple("IoWait").field(id).finish(),
+            Self::IoWaitTimeout { source, dur } => f
+                .debug_struct("IoWaitTimeout")
+                .field("source", source)
+                .field("dur", dur)
+                .finish(),
+            #[cfg(feature = "async-io")]
+            Self::SpawnProcess(cmd) => f.debug_tuple("SpawnProcess").field(cmd).finish(),
+            #[cfg(feature = "async-io")]
+            Self::WaitProcess(pid) => f.debug_tuple("WaitProcess").field(pid).finish(),
+            Self::Yield => f.write_str("Yield"),
+            Self::Cancel(tid) => f.debug_tuple("Cancel").field(tid).finish(),
+            Self::CancelTimer(id) => f.debug_tuple("CancelTimer").field(id).finish(),
+            Self::AwaitFuture => f.write_str("AwaitFuture"),
+            Self::JoinTimeout { target, dur } => f
+                .debug_struct("JoinTimeout")
+                .field("target", target)
+                .field("dur", dur)
+                .finish(),
+            Self::SetPriority { target, pri } => f
+                .debug_struct("SetPriority")
+                .field("target", target)
+                .field("pri", pri)
+                .finish(),
+            Self::Spawn {
+                pri, meta, limits, ..
+            } => f
+                .debug_struct("Spawn")
+                .field("pri", pri)
+                .field("meta", meta)
+                .field("limits", limits)
+                .finish_non_exhaustive(),
+            Self::SpawnRegistered {
+                pri,
+                meta,
+                limits,
+                task,
+                ..
+            } => f
+                .debug_struct("SpawnRegistered")
+                .field("pri", pri)
+                .field("meta", meta)
+                .field("limits", limits)
+                .field("task", task)
+                .finish_non_exhaustive(),
+            Self::AwaitRestore => f.write_str("AwaitRestore"),
+            Self::JoinResult { target, timeout } => f
+                .debug_struct("JoinResult")
+                .field("target", target)
+                .field("timeout", timeout)
+                .finish(),
+            Self::JoinAny(targets) => f.debug_tuple("JoinAny").field(targets).finish(),
+            Self::ChannelOpen { cap } => f.debug_struct("ChannelOpen").field("cap", cap).finish(),
+            Self::Send { chan, .. } => f
+                .debug_struct("Send")
+                .field("chan", chan)
+                .finish_non_exhaustive(),
+            Self::Recv(chan) => f.debug_tuple("Recv").field(chan).finish(),
+            Self::RecvTimeout { chan, dur } => f
+                .debug_struct("RecvTimeout")
+                .field("chan", chan)
+                .field("dur", dur)
+                .finish(),
+            Self::ChannelClose(chan) => f.debug_tuple("ChannelClose").field(chan).finish(),
+            Self::SyncCreate(kind) => f.debug_tuple("SyncCreate").field(kind).finish(),
+            Self::Acquire(id) => f.debug_tuple("Acquire").field(id).finish(),
+            Self::Release(id) => f.debug_tuple("Release").field(id).finish(),
+            Self::EventSet(id) => f.debug_tuple("EventSet").field(id).finish(),
+            Self::EventWait(id) => f.debug_tuple("EventWait").field(id).finish(),
+            Self::BarrierWait(id) => f.debug_tuple("BarrierWait").field(id).finish(),
+            Self::SyncDestroy(id) => f.debug_tuple("SyncDestroy").field(id).finish(),
+        }
+    }
+}
+
+impl SystemCall {
+    /// Returns `true` if the caller parks until the scheduler replies.
+    pub fn expects_reply(&self) -> bool {
+        #[cfg(feature = "async-io")]
+        if matches!(self, Self::SpawnProcess(_) | Self::WaitProcess(_)) {
+            return true;
+        }
+        matches!(
+            self,
+            Self::Spawn { .. }
+                | Self::SpawnRegistered { .. }
+                | Self::AwaitRestore
+                | Self::IoWaitTimeout { .. }
+                | Self::AwaitFuture
+                | Self::JoinResult { .. }
+                | Self::JoinAny(_)
+                | Self::ChannelOpen { .. }
+                | Self::Send { .. }
+                | Self::Recv(_)
+                | Self::RecvTimeout { .. }
+                | Self::SyncCreate(_)
+                | Self::Acquire(_)
+                | Self::EventWait(_)
+                | Self::BarrierWait(_)
+        )
+    }
+}
+
+/// Value handed back to a task blocked in [`TaskContext::request`].
+#[derive(Debug)]
+pub enum SyscallReply {
+    /// Identifier of the task created by [`SystemCall::Spawn`].
+    Spawned(TaskId),
+    /// Outcome of the task awaited by [`SystemCall::JoinResult`].
+    Joined(Result<TaskOutput, TaskError>),
+    /// First task to finish among those awaited by [`SystemCall::JoinAny`],
+    /// with its outcome.
+    JoinedAny(TaskId, Result<TaskOutput, TaskError>),
+    /// Identifier of the channel created by [`SystemCall::ChannelOpen`].
+    ChannelOpened(ChannelId),
+    /// Outcome of [`SystemCall::Send`]; a closed channel hands the message back.
+    Sent(Result<(), Message>),
+    /// Outcome of [`SystemCall::Recv`] or [`SystemCall::RecvTimeout`].
+    Received(Result<Message, ChannelError>),
+    /// Identifier of the object created by [`SystemCall::SyncCreate`].
+    SyncCreated(SyncId),
+    /// The lock or permit requested by [`SystemCall::Acquire`] is held.
+    Acquired,
+    /// The event awaited by [`SystemCall::EventWait`] is set.
+    Signalled,
+    /// All parties reached the barrier; `leader` marks the last arrival.
+    BarrierPassed { leader: bool },
+    /// The object named by the request does not exist.
+    Unknown(u64),
+    /// The future awaited through [`SystemCall::AwaitFuture`] was woken.
+    Woken,
+    /// Why the wait of [`SystemCall::IoWaitTimeout`] ended.
+    IoWaited(IoWake),
+    /// Outcome of [`SystemCall::SpawnProcess`].
+    #[cfg(feature = "async-io")]
+    ProcessSpawned(io::Result<ChildProcess>),
+    /// Exit status awaited by [`SystemCall::WaitProcess`].
+    #[cfg(feature = "async-io")]
+    ProcessExited(io::Result<ExitStatus>),
+    /// A simulated task may continue after a syscall without a result.
+    Continue,
+    /// The wait was cut short because the caller is being cancelled.
+    Cancelled,
 }
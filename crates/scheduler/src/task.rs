@@ -1,23 +1,111 @@
-use crate::syscall::SystemCall;
+use crate::bridge::TaskWaker;
+use crate::builder::TaskBuilder;
+use crate::call_stack::CallStack;
+use crate::cancel::{CancelToken, CleanupHooks};
+use crate::handle::{Control, SchedulerHandle};
+use crate::io::IoWake;
+use crate::local::TaskLocals;
+use crate::pal::Stage;
+use crate::policy::ShareGroup;
+#[cfg(feature = "async-io")]
+use crate::process::ChildProcess;
+use crate::syscall::{SyscallReply, SystemCall};
+use crate::timer::TimerId;
+use crate::watchdog::StallProbe;
 use crossbeam::channel::Sender;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 
 /// Unique identifier for a task.
 pub type TaskId = u64;
 
+/// Type-erased value returned by a task body.
+pub type TaskOutput = Box<dyn Any + Send>;
+
 /// A wrapper around a running coroutine and its metadata.
 pub struct Task {
     /// Unique identifier for the task.
     pub tid: TaskId,
+    /// Name and tags given at spawn.
+    pub meta: Arc<TaskMeta>,
     /// Scheduling priority (0 = highest).
     pub pri: u8,
     /// Coroutine handle backing the task.
-    pub handle: may::coroutine::JoinHandle<()>,
+    pub handle: may::coroutine::JoinHandle<TaskOutput>,
     /// Current lifecycle state of the task.
     pub state: TaskState,
+    /// Task that spawned this one via [`SystemCall::Spawn`], if any.
+    pub parent: Option<TaskId>,
+    /// Fair-share group the task is charged to; inherited from the parent.
+    pub group: ShareGroup,
+    /// Virtual time at which the task was spawned.
+    pub spawned_at: Instant,
+    /// Number of syscalls the scheduler handled for this task.
+    pub syscalls: u64,
+    /// Deadline and budgets given at spawn.
+    pub limits: TaskLimits,
+    /// Span wrapping the scheduler's work on behalf of this task.
+    pub span: tracing::Span,
+    /// Nested calls the task is inside, shared with its context.
+    pub(crate) calls: Arc<Mutex<CallStack>>,
+    /// Raised when cancellation of the task is requested.
+    pub(crate) cancel: CancelToken,
+    /// Lets the watchdog ask the task for a backtrace.
+    pub(crate) probe: Arc<StallProbe>,
+    /// Channel used to resume the task after a blocking request.
+    pub(crate) reply_tx: may::sync::mpmc::Sender<SyscallReply>,
+}
+
+/// Descriptive metadata attached to a task at spawn.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskMeta {
+    /// Human-readable name, if one was given.
+    pub name: Option<String>,
+    /// Key/value labels such as correlation or request ids.
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Bounds on how long and how much a task may run, set with
+/// [`TaskBuilder::deadline`] and friends. A task exceeding any of them is
+/// cancelled and ends as [`TaskState::TimedOut`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskLimits {
+    /// Virtual time after spawn by which the task must finish.
+    pub deadline: Option<Duration>,
+    /// Maximum number of syscalls the task may make.
+    pub syscalls: Option<u64>,
+    /// Real time after spawn by which the task must finish.
+    pub wall_time: Option<Duration>,
+}
+
+/// Which of a task's [`TaskLimits`] it exceeded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskLimit {
+    Deadline,
+    Syscalls,
+    WallTime,
+}
+
+impl fmt::Display for TaskLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Deadline => "deadline",
+            Self::Syscalls => "syscall budget",
+            Self::WallTime => "wall-time budget",
+        })
+    }
 }
 
 /// Represents the lifecycle state of a task.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TaskState {
     /// The task is currently running or ready to run.
     Running,
@@ -25,6 +113,157 @@ pub enum TaskState {
     Finished,
     /// The task terminated due to a panic.
     Failed,
+    /// The task was cancelled before it ended on its own.
+    Cancelled,
+    /// The task was cancelled for exceeding its deadline or a budget.
+    TimedOut,
+}
+
+/// Reason a task did not produce a value for its joiner.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskError {
+    /// The task panicked; carries the panic message.
+    Panicked(String),
+    /// The task was cancelled before it finished, or the waiting task was
+    /// cancelled while it waited.
+    Cancelled,
+    /// The joiner gave up waiting before the task finished.
+    TimedOut,
+    /// No result is available: the id is unknown or the result was already
+    /// claimed by another joiner.
+    NotFound,
+    /// The task returned a value of another type than the joiner expected.
+    WrongType,
+    /// The task was cancelled for exceeding one of its [`TaskLimits`].
+    LimitExceeded(TaskLimit),
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Panicked(msg) => write!(f, "task panicked: {msg}"),
+            Self::Cancelled => f.write_str("task was cancelled"),
+            Self::TimedOut => f.write_str("timed out waiting for task"),
+            Self::NotFound => f.write_str("task result not available"),
+            Self::WrongType => f.write_str("task returned a different type"),
+            Self::LimitExceeded(limit) => write!(f, "task exceeded its {limit}"),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+/// Extract a readable message from a panic payload.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        (*msg).to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// Typed handle to a spawned task's result.
+///
+/// The handle wraps the [`TaskId`]; the value itself is kept by the
+/// scheduler until the handle is joined. Joining consumes the handle, and
+/// dropping a handle returned by a spawn tells the scheduler to forget the
+/// task's outcome.
+pub struct JoinHandle<T> {
+    tid: TaskId,
+    /// Set for the handle that owns the outcome.
+    owner: Option<SchedulerHandle>,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<T: Send + 'static> JoinHandle<T> {
+    /// Wrap an existing task id. Joining fails with [`TaskError::WrongType`]
+    /// unless the task's body returns `T`. The wrapper does not own the
+    /// outcome, so dropping it leaves the outcome for another joiner.
+    pub fn new(tid: TaskId) -> Self {
+        Self {
+            tid,
+            owner: None,
+            _ty: PhantomData,
+        }
+    }
+
+    /// Handle owning the outcome of `tid`, released through `owner` on drop.
+    pub(crate) fn owning(tid: TaskId, owner: SchedulerHandle) -> Self {
+        Self {
+            tid,
+            owner: Some(owner),
+            _ty: PhantomData,
+        }
+    }
+
+    /// Give up ownership of the outcome, e.g. to a [`TaskGroup`](crate::TaskGroup).
+    pub(crate) fn disown(mut self) -> Self {
+        self.owner = None;
+        self
+    }
+
+    /// Identifier of the underlying task.
+    pub fn id(&self) -> TaskId {
+        self.tid
+    }
+
+    /// Park the calling task until the target finishes and claim its value.
+    pub fn join(mut self, ctx: &TaskContext) -> Result<T, TaskError> {
+        self.claim(ctx, None)
+    }
+
+    /// Like [`JoinHandle::join`] but give up after `dur` of scheduler time.
+    /// The handle is kept so the task can be joined again after a timeout.
+    pub fn join_timeout(&mut self, ctx: &TaskContext, dur: Duration) -> Result<T, TaskError> {
+        self.claim(ctx, Some(dur))
+    }
+
+    fn claim(&mut self, ctx: &TaskContext, timeout: Option<Duration>) -> Result<T, TaskError> {
+        match ctx.request(SystemCall::JoinResult {
+            target: self.tid,
+            timeout,
+        }) {
+            SyscallReply::Joined(res) => {
+                // A joiner that gave up leaves the outcome for the drop to
+                // release.
+                if res.as_ref().err() != Some(&TaskError::TimedOut) {
+                    self.owner = None;
+                }
+                res.and_then(downcast_output)
+            }
+            SyscallReply::Cancelled => Err(TaskError::Cancelled),
+            other => panic!("unexpected reply to JoinResult: {other:?}"),
+        }
+    }
+
+    /// Cancel the target together with every task it spawned. The handle
+    /// can still join it to see how it ended.
+    pub fn cancel(&self, ctx: &TaskContext) {
+        ctx.syscall(SystemCall::Cancel(self.tid));
+    }
+}
+
+/// Recover the concrete value returned by a task body.
+pub(crate) fn downcast_output<T: 'static>(out: TaskOutput) -> Result<T, TaskError> {
+    out.downcast::<T>()
+        .map(|value| *value)
+        .map_err(|_| TaskError::WrongType)
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        if let Some(owner) = self.owner.take() {
+            owner.send(Control::Release(self.tid));
+        }
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("JoinHandle").field(&self.tid).finish()
+    }
 }
 
 /// Shared context passed into each task.
@@ -34,22 +273,249 @@ pub enum TaskState {
 pub struct TaskContext {
     pub tid: TaskId,
     pub syscall_tx: Sender<(TaskId, SystemCall)>,
+    pub reply_rx: may::sync::mpmc::Receiver<SyscallReply>,
+    /// Park after every syscall until the scheduler steps this task again;
+    /// set for tasks of a simulated scheduler.
+    pub(crate) lockstep: bool,
+    pub(crate) meta: Arc<TaskMeta>,
+    pub(crate) span: tracing::Span,
+    pub(crate) locals: TaskLocals,
+    pub(crate) calls: Arc<Mutex<CallStack>>,
+    pub(crate) cancel: CancelToken,
+    pub(crate) cleanup: CleanupHooks,
+    pub(crate) probe: Arc<StallProbe>,
+    /// Lets wakers of futures polled by [`TaskContext::block_on`] reach the
+    /// scheduler from any thread.
+    pub(crate) handle: SchedulerHandle,
 }
 
 impl TaskContext {
     /// Submit a system call from the current task.
     pub fn syscall(&self, call: SystemCall) {
-        self.syscall_tx
-            .send((self.tid, call))
-            .expect("Failed to send system call");
+        self.send(call);
+        if self.lockstep {
+            // The reply channel closes if the call ended this task.
+            let _ = self.suspend(|| self.reply_rx.recv());
+            return;
+        }
         // Yield after sending the syscall so the scheduler can handle it
         // promptly. `may::coroutine::yield_now` already falls back to
         // `std::thread::yield_now` when not in a coroutine context.
-        may::coroutine::yield_now();
+        self.suspend(may::coroutine::yield_now);
+    }
+
+    /// Submit a system call and park until the scheduler replies.
+    ///
+    /// Only the coroutine is parked; the `may` worker thread keeps running
+    /// other tasks while the reply is pending.
+    pub fn request(&self, call: SystemCall) -> SyscallReply {
+        self.send(call);
+        self.suspend(|| self.reply_rx.recv())
+            .expect("scheduler dropped reply channel")
+    }
+
+    /// Run `park`, which may suspend the coroutine, outside the task's span.
+    ///
+    /// The body runs inside the span, but the subscriber tracks entered spans
+    /// per thread, and the coroutine may resume on another worker while other
+    /// tasks run on this one.
+    pub(crate) fn suspend<T>(&self, park: impl FnOnce() -> T) -> T {
+        self.span
+            .with_subscriber(|(id, dispatch)| dispatch.exit(id));
+        let out = park();
+        self.span
+            .with_subscriber(|(id, dispatch)| dispatch.enter(id));
+        out
+    }
+
+    fn send(&self, call: SystemCall) {
+        self.probe.capture_if_flagged();
+        self.syscall_tx
+            .send((self.tid, call))
+            .expect("Failed to send system call");
+    }
+
+    /// Name given to this task at spawn.
+    pub fn name(&self) -> Option<&str> {
+        self.meta.name.as_deref()
+    }
+
+    /// Value of the tag `key`, set at spawn or inherited from the parent.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.meta.tags.get(key).map(String::as_str)
+    }
+
+    /// Name and tags of this task.
+    pub fn meta(&self) -> &TaskMeta {
+        &self.meta
+    }
+
+    /// Span carrying this task's id, name and tags. The body runs inside it,
+    /// so events the task logs carry them too.
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// Store a task-local value, returning the previous value of that type.
+    pub fn set_local<T: Send + 'static>(&self, value: T) -> Option<T> {
+        self.locals.insert(value)
+    }
+
+    /// Remove and return the task-local value of type `T`.
+    pub fn take_local<T: Send + 'static>(&self) -> Option<T> {
+        self.locals.remove()
+    }
+
+    /// Run `f` with the task-local value of type `T`, if one is set.
+    pub fn with_local<T: Send + 'static, R>(&self, f: impl FnOnce(Option<&mut T>) -> R) -> R {
+        self.locals.with(f)
+    }
+
+    /// Whether cancellation of this task was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Return [`TaskError::Cancelled`] if cancellation was requested, for
+    /// use with `?` at convenient stopping points.
+    pub fn check_cancelled(&self) -> Result<(), TaskError> {
+        if self.is_cancelled() {
+            Err(TaskError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Token observing this task's cancellation, for helpers that do not
+    /// hold the context.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Register a hook to run on this task if it is cancelled, after the
+    /// body returns or unwinds. Hooks run most recent first and may block.
+    pub fn on_cancel(&self, hook: impl FnOnce(&TaskContext) + Send + 'static) {
+        self.cleanup.push(Box::new(hook));
+    }
+
+    /// Run the sub-coroutine `f` as a nested call named `name`.
+    ///
+    /// `f` runs on this task, so its syscalls are issued as this task and
+    /// its value is returned to the caller. The frame appears in snapshots
+    /// and in the failure report if the task panics inside it.
+    pub fn call<T>(&self, name: impl Into<String>, f: impl FnOnce(&TaskContext) -> T) -> T {
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(name);
+        let _frame = CallFrame(self);
+        f(self)
+    }
+
+    /// Configure a child task before spawning it.
+    pub fn builder(&self) -> TaskBuilder<&Self> {
+        TaskBuilder::new(self)
     }
 
     /// Yield back to the scheduler without performing a system call.
     pub fn yield_now(&self) {
         self.syscall(SystemCall::Yield);
     }
+
+    /// Wait up to `dur` for the I/O source `source` to become ready and say
+    /// why the wait ended.
+    pub fn io_wait_timeout(&self, source: u64, dur: Duration) -> IoWake {
+        match self.request(SystemCall::IoWaitTimeout { source, dur }) {
+            SyscallReply::IoWaited(why) => why,
+            SyscallReply::Cancelled => IoWake::Cancelled,
+            other => panic!("unexpected reply to IoWaitTimeout: {other:?}"),
+        }
+    }
+
+    /// Start `cmd` as a child process watched by the scheduler; see
+    /// [`crate::process`].
+    #[cfg(feature = "async-io")]
+    pub fn spawn_process(&self, cmd: std::process::Command) -> std::io::Result<ChildProcess> {
+        match self.request(SystemCall::SpawnProcess(Box::new(cmd))) {
+            SyscallReply::ProcessSpawned(res) => res,
+            SyscallReply::Cancelled => Err(std::io::Error::other("task cancelled")),
+            other => panic!("unexpected reply to SpawnProcess: {other:?}"),
+        }
+    }
+
+    /// Drive `fut` to completion on this task's coroutine. While it is
+    /// pending the task parks and other tasks run; its waker resumes the
+    /// task. Fails with [`TaskError::Cancelled`] if the task is cancelled
+    /// meanwhile.
+    ///
+    /// The future is polled outside any async runtime, so one that needs
+    /// runtime context, such as Tokio I/O, should be spawned on its runtime
+    /// and the returned join handle awaited here instead.
+    pub fn block_on<F: Future>(&self, fut: F) -> Result<F::Output, TaskError> {
+        let waker = Waker::from(Arc::new(TaskWaker {
+            tid: self.tid,
+            handle: self.handle.clone(),
+        }));
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = pin!(fut);
+        loop {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return Ok(out);
+            }
+            match self.request(SystemCall::AwaitFuture) {
+                SyscallReply::Woken => {}
+                SyscallReply::Cancelled => return Err(TaskError::Cancelled),
+                other => panic!("unexpected reply to AwaitFuture: {other:?}"),
+            }
+        }
+    }
+
+    /// Tell Process Activity Log subscribers what this task is doing, e.g.
+    /// that it is [`Stage::WaitingForLlm`]; see [`crate::pal`].
+    pub fn report(&self, stage: Stage, message: impl Into<String>) {
+        self.syscall(SystemCall::Report {
+            stage,
+            message: message.into(),
+        });
+    }
+
+    /// Stop a timer from firing again; see [`crate::timer`].
+    pub fn cancel_timer(&self, id: TimerId) {
+        self.syscall(SystemCall::CancelTimer(id));
+    }
+
+    /// Change the priority of this task or one of its descendants.
+    pub fn set_priority(&self, target: TaskId, pri: u8) {
+        self.syscall(SystemCall::SetPriority { target, pri });
+    }
+
+    /// Spawn a child task with the given priority from inside this task.
+    ///
+    /// The child is recorded as a descendant of the current task and a
+    /// handle to its result is returned once the scheduler has created it.
+    ///
+    /// # Safety
+    /// The child is started with `may::coroutine::spawn`; see
+    /// [`Scheduler::spawn_with_priority`](crate::Scheduler::spawn_with_priority)
+    /// for the requirements on the closure and its captured data.
+    pub unsafe fn spawn<F, T>(&self, pri: u8, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce(TaskContext) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        unsafe { self.builder().priority(pri).spawn(f) }
+    }
+}
+
+/// Pops the innermost call frame when a call returns or unwinds.
+struct CallFrame<'a>(&'a TaskContext);
+
+impl Drop for CallFrame<'_> {
+    fn drop(&mut self) {
+        self.0
+            .calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop(std::thread::panicking());
+    }
 }
@@ -0,0 +1,459 @@
+//! Timers that spawn a task on a schedule.
+//!
+//! A timer fires on the scheduler's virtual clock: every fixed interval, once
+//! at a given time, or whenever a cron expression matches. Each tick starts a
+//! fresh task running the timer's body. When a tick comes due while the
+//! previous run is still going, the timer's [`MissedTicks`] policy decides
+//! what happens to it.
+//!
+//! Cron expressions are matched in UTC against the wall-clock time at which
+//! the scheduler was created plus the virtual time elapsed since; simulations
+//! start at the Unix epoch instead so their runs stay reproducible.
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::syscall::TaskFn;
+use crate::task::{TaskContext, TaskId, TaskOutput};
+
+/// Identifier of a timer registered with
+/// [`Scheduler::spawn_timer`](crate::Scheduler::spawn_timer).
+pub type TimerId = u64;
+
+/// When a timer fires.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Schedule {
+    /// Every interval, starting one interval after registration.
+    Every(Duration),
+    /// Once, at this virtual time since the scheduler was created.
+    At(Duration),
+    /// At every minute the expression matches.
+    Cron(CronSchedule),
+}
+
+/// What a timer does with ticks that come due while its previous run is
+/// still going.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MissedTicks {
+    /// Drop them.
+    #[default]
+    Skip,
+    /// Run each of them, one after another, once the previous run ends.
+    CatchUp,
+    /// Run once after the previous run ends, however many were missed.
+    Coalesce,
+}
+
+/// Body a timer runs on every tick.
+pub(crate) type TimerFn = Arc<dyn Fn(TaskContext) + Send + Sync + 'static>;
+
+/// Description of a timer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimerSpec {
+    pub(crate) schedule: Schedule,
+    pub(crate) missed: MissedTicks,
+    pub(crate) pri: u8,
+    pub(crate) name: Option<String>,
+}
+
+impl TimerSpec {
+    /// Fire every `interval`. Panics if `interval` is zero.
+    pub fn every(interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "timer interval must not be zero");
+        Self::new(Schedule::Every(interval))
+    }
+
+    /// Fire once at virtual time `at` since the scheduler was created, or at
+    /// once if that has passed.
+    pub fn at(at: Duration) -> Self {
+        Self::new(Schedule::At(at))
+    }
+
+    /// Fire whenever the cron expression `expr` matches.
+    pub fn cron(expr: &str) -> Result<Self, CronError> {
+        Ok(Self::new(Schedule::Cron(CronSchedule::parse(expr)?)))
+    }
+
+    fn new(schedule: Schedule) -> Self {
+        Self {
+            schedule,
+            missed: MissedTicks::default(),
+            pri: 10,
+            name: None,
+        }
+    }
+
+    /// Handle ticks missed during a run with `policy`.
+    pub fn missed(mut self, policy: MissedTicks) -> Self {
+        self.missed = policy;
+        self
+    }
+
+    /// Priority of the tasks the timer starts.
+    pub fn priority(mut self, pri: u8) -> Self {
+        self.pri = pri;
+        self
+    }
+
+    /// Name the timer and the tasks it starts.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// State of a timer as seen by [`Scheduler::timers`](crate::Scheduler::timers).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimerSnapshot {
+    pub id: TimerId,
+    pub name: Option<String>,
+    pub schedule: Schedule,
+    pub missed: MissedTicks,
+    /// Virtual time of the next tick, `None` once the schedule is exhausted.
+    pub next_at: Option<Duration>,
+    /// Task of the run in progress.
+    pub running: Option<TaskId>,
+    /// Ticks waiting for the current run to end.
+    pub pending: u64,
+    /// Runs started so far.
+    pub fired: u64,
+    /// Ticks dropped by the missed-tick policy.
+    pub skipped: u64,
+}
+
+/// Scheduler-side state of a timer.
+pub(crate) struct TimerState {
+    pub spec: TimerSpec,
+    factory: TimerFn,
+    /// Virtual time of the next tick.
+    pub next: Option<Duration>,
+    pub running: Option<TaskId>,
+    pub pending: u64,
+    pub fired: u64,
+    pub skipped: u64,
+}
+
+impl TimerState {
+    /// Create a timer registered at virtual time `now`; `base` is the wall
+    /// time at virtual time zero.
+    pub fn new(spec: TimerSpec, factory: TimerFn, now: Duration, base: SystemTime) -> Self {
+        let next = match &spec.schedule {
+            Schedule::Every(interval) => Some(now + *interval),
+            Schedule::At(at) => Some(*at),
+            Schedule::Cron(cron) => cron.next_virtual(now, base),
+        };
+        Self {
+            spec,
+            factory,
+            next,
+            running: None,
+            pending: 0,
+            fired: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Account for every tick due by `now`, advancing `next` past them, and
+    /// return whether a run should start now.
+    pub fn tick(&mut self, now: Duration, base: SystemTime) -> bool {
+        let Some(at) = self.next.filter(|&at| at <= now) else {
+            return false;
+        };
+        let due = match &self.spec.schedule {
+            Schedule::Every(interval) => {
+                // Count the ticks arithmetically: after a large clock jump a
+                // short interval may have come due billions of times.
+                let interval = interval.as_nanos();
+                let due = (now - at).as_nanos() / interval + 1;
+                self.next = at.checked_add(from_nanos(due * interval));
+                u64::try_from(due).unwrap_or(u64::MAX)
+            }
+            Schedule::At(_) => {
+                self.next = None;
+                1
+            }
+            Schedule::Cron(cron) => {
+                // Cron ticks are at least a minute apart, so this loops at
+                // most once per minute skipped.
+                let mut due = 0;
+                while let Some(at) = self.next.filter(|&at| at <= now) {
+                    due += 1;
+                    self.next = cron.next_virtual(at, base);
+                }
+                due
+            }
+        };
+        let idle = self.running.is_none();
+        match self.spec.missed {
+            MissedTicks::Skip => {
+                self.skipped = self.skipped.saturating_add(due - u64::from(idle));
+                idle
+            }
+            MissedTicks::CatchUp => {
+                self.pending = self.pending.saturating_add(due - u64::from(idle));
+                idle
+            }
+            MissedTicks::Coalesce => {
+                let owed = self.pending.saturating_add(due - u64::from(idle));
+                self.pending = owed.min(1);
+                self.skipped = self.skipped.saturating_add(owed - self.pending);
+                idle
+            }
+        }
+    }
+
+    /// Note that the current run ended and return whether the next one
+    /// should start right away.
+    pub fn run_ended(&mut self) -> bool {
+        self.running = None;
+        if self.pending > 0 {
+            self.pending -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the timer will never fire again.
+    pub fn exhausted(&self) -> bool {
+        self.next.is_none() && self.running.is_none() && self.pending == 0
+    }
+
+    /// Body of the next run.
+    pub fn body(&mut self) -> TaskFn {
+        self.fired += 1;
+        let factory = Arc::clone(&self.factory);
+        Box::new(move |ctx| {
+            factory(ctx);
+            Box::new(()) as TaskOutput
+        })
+    }
+
+    pub fn snapshot(&self, id: TimerId) -> TimerSnapshot {
+        TimerSnapshot {
+            id,
+            name: self.spec.name.clone(),
+            schedule: self.spec.schedule.clone(),
+            missed: self.spec.missed,
+            next_at: self.next,
+            running: self.running,
+            pending: self.pending,
+            fired: self.fired,
+            skipped: self.skipped,
+        }
+    }
+}
+
+/// `nanos` as a duration, saturating at the largest one.
+fn from_nanos(nanos: u128) -> Duration {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+    match u64::try_from(nanos / NANOS_PER_SEC) {
+        Ok(secs) => Duration::new(secs, (nanos % NANOS_PER_SEC) as u32),
+        Err(_) => Duration::MAX,
+    }
+}
+
+/// A cron expression was malformed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronError {
+    pub expr: String,
+    pub reason: String,
+}
+
+impl fmt::Display for CronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid cron expression {:?}: {}",
+            self.expr, self.reason
+        )
+    }
+}
+
+impl std::error::Error for CronError {}
+
+/// Standard five-field cron expression (minute, hour, day of month, month,
+/// day of week), matched in UTC.
+///
+/// Fields accept `*`, numbers, ranges `a-b`, steps `*/n` or `a-b/n`, and
+/// comma-separated lists. Day of week counts from Sunday as 0 (7 is Sunday
+/// too). When both day fields are restricted, a day matching either one
+/// qualifies. `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are
+/// accepted as shorthands.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// How far ahead to look for a match; long enough for any date that exists,
+/// such as Feb 29 falling on a given weekday.
+const SEARCH_DAYS: u64 = 366 * 28;
+
+impl CronSchedule {
+    /// Parse `expr`.
+    pub fn parse(expr: &str) -> Result<Self, CronError> {
+        let err = |reason: String| CronError {
+            expr: expr.to_string(),
+            reason,
+        };
+        let fields = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(err(format!("expected 5 fields, got {}", fields.len())));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7).map_err(err)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expr: expr.to_string(),
+            minutes: parse_field(minute, 0, 59).map_err(err)?,
+            hours: parse_field(hour, 0, 23).map_err(err)?,
+            days: parse_field(day, 1, 31).map_err(err)?,
+            months: parse_field(month, 1, 12).map_err(err)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// The expression as written.
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    /// First matching minute strictly after `t`, or `None` if there is none
+    /// (e.g. February 30) or `t` precedes the Unix epoch.
+    pub fn next_after(&self, t: SystemTime) -> Option<SystemTime> {
+        let first = t.duration_since(UNIX_EPOCH).ok()?.as_secs() / 60 + 1;
+        let start_day = first / 1440;
+        for day in start_day..=start_day + SEARCH_DAYS {
+            if !self.matches_day(day) {
+                continue;
+            }
+            let from = if day == start_day { first % 1440 } else { 0 };
+            for minute in from..1440 {
+                if self.hours & (1 << (minute / 60)) != 0
+                    && self.minutes & (1 << (minute % 60)) != 0
+                {
+                    let secs = (day * 1440 + minute) * 60;
+                    return Some(UNIX_EPOCH + Duration::from_secs(secs));
+                }
+            }
+        }
+        None
+    }
+
+    /// [`Self::next_after`] on virtual time, where `base` is the wall time at
+    /// virtual time zero.
+    fn next_virtual(&self, after: Duration, base: SystemTime) -> Option<Duration> {
+        let next = self.next_after(base + after)?;
+        next.duration_since(base).ok()
+    }
+
+    fn matches_day(&self, day: u64) -> bool {
+        let (_, month, mday) = civil_date(day);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01 was a Thursday.
+        let weekday = (day + 4) % 7;
+        let by_day = self.days & (1 << mday) != 0;
+        let by_weekday = self.weekdays & (1 << weekday) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => by_day || by_weekday,
+            _ => by_day && by_weekday,
+        }
+    }
+}
+
+impl fmt::Debug for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CronSchedule").field(&self.expr).finish()
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(cron: CronSchedule) -> Self {
+        cron.expr
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = CronError;
+
+    fn try_from(expr: String) -> Result<Self, Self::Error> {
+        Self::parse(&expr)
+    }
+}
+
+/// Parse one cron field into a bit set of the values it allows.
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64, String> {
+    let number = |s: &str| -> Result<u64, String> {
+        let n: u64 = s.parse().map_err(|_| format!("bad number {s:?}"))?;
+        if (min..=max).contains(&n) {
+            Ok(n)
+        } else {
+            Err(format!("{n} is outside {min}-{max}"))
+        }
+    };
+    let mut bits = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u64>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("bad step {step:?}")),
+            },
+            None => (item, None),
+        };
+        let (lo, hi) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((lo, hi)) => (number(lo)?, number(hi)?),
+                None => {
+                    let n = number(range)?;
+                    (n, if step.is_some() { max } else { n })
+                }
+            },
+        };
+        if lo > hi {
+            return Err(format!("empty range {range:?}"));
+        }
+        for n in (lo..=hi).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+/// Year, month (1-12) and day of month of `day` days after the Unix epoch.
+pub(crate) fn civil_date(day: u64) -> (u64, u64, u64) {
+    // Howard Hinnant's civil_from_days, restricted to dates after 1970.
+    let z = day + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let mday = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + u64::from(month <= 2);
+    (year, month, mday)
+}
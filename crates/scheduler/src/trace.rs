@@ -0,0 +1,350 @@
+//! Syscall traces for auditing and deterministic replay.
+//!
+//! A scheduler with a [`TraceSink`] reports every syscall it handles for a
+//! live task and every task that exits, stamped with virtual time. Traces can
+//! be persisted as JSON lines and fed to [`replay_trace`], which re-runs the
+//! same program in lockstep along the recorded schedule and reports the first
+//! event that differs.
+//!
+//! Traces are not written to the `wal` crate yet; a [`TraceSink`] is where a
+//! write-ahead log would plug in.
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::channel::{ChannelError, ChannelId};
+use crate::io::IoWake;
+use crate::pal::Stage;
+use crate::scheduler::Scheduler;
+use crate::sync::{SyncId, SyncKind};
+use crate::syscall::{SyscallReply, SystemCall};
+use crate::task::{TaskError, TaskId, TaskState};
+use crate::timer::TimerId;
+
+/// A traced [`SystemCall`], without task bodies, messages or commands.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceCall {
+    Log(String),
+    Report {
+        stage: Stage,
+        message: String,
+    },
+    Sleep(Duration),
+    Join(TaskId),
+    Done,
+    IoWait(u64),
+    IoWaitTimeout {
+        source: u64,
+        dur: Duration,
+    },
+    CancelTimer(TimerId),
+    AwaitFuture,
+    /// Starts the named program.
+    SpawnProcess(String),
+    WaitProcess(u32),
+    Yield,
+    Cancel(TaskId),
+    JoinTimeout {
+        target: TaskId,
+        dur: Duration,
+    },
+    SetPriority {
+        target: TaskId,
+        pri: u8,
+    },
+    Spawn {
+        pri: u8,
+    },
+    SpawnRegistered {
+        pri: u8,
+        kind: String,
+    },
+    AwaitRestore,
+    JoinResult {
+        target: TaskId,
+        timeout: Option<Duration>,
+    },
+    JoinAny(Vec<TaskId>),
+    ChannelOpen {
+        cap: Option<usize>,
+    },
+    Send {
+        chan: ChannelId,
+    },
+    Recv(ChannelId),
+    RecvTimeout {
+        chan: ChannelId,
+        dur: Duration,
+    },
+    ChannelClose(ChannelId),
+    SyncCreate(SyncKind),
+    Acquire(SyncId),
+    Release(SyncId),
+    EventSet(SyncId),
+    EventWait(SyncId),
+    BarrierWait(SyncId),
+    SyncDestroy(SyncId),
+}
+
+impl From<&SystemCall> for TraceCall {
+    fn from(call: &SystemCall) -> Self {
+        match call {
+            SystemCall::Log(msg) => Self::Log(msg.clone()),
+            SystemCall::Report { stage, message } => Self::Report {
+                stage: *stage,
+                message: message.clone(),
+            },
+            SystemCall::Sleep(dur) => Self::Sleep(*dur),
+            SystemCall::Join(tid) => Self::Join(*tid),
+            SystemCall::Done => Self::Done,
+            SystemCall::IoWait(id) => Self::IoWait(*id),
+            &SystemCall::IoWaitTimeout { source, dur } => Self::IoWaitTimeout { source, dur },
+            SystemCall::CancelTimer(id) => Self::CancelTimer(*id),
+            SystemCall::AwaitFuture => Self::AwaitFuture,
+            #[cfg(feature = "async-io")]
+            SystemCall::SpawnProcess(cmd) => {
+                Self::SpawnProcess(cmd.get_program().to_string_lossy().into_owned())
+            }
+            #[cfg(feature = "async-io")]
+            SystemCall::WaitProcess(pid) => Self::WaitProcess(*pid),
+            SystemCall::Yield => Self::Yield,
+            SystemCall::Cancel(tid) => Self::Cancel(*tid),
+            &SystemCall::JoinTimeout { target, dur } => Self::JoinTimeout { target, dur },
+            &SystemCall::SetPriority { target, pri } => Self::SetPriority { target, pri },
+            &SystemCall::Spawn { pri, .. } => Self::Spawn { pri },
+            SystemCall::SpawnRegistered { pri, task, .. } => Self::SpawnRegistered {
+                pri: *pri,
+                kind: task.kind.clone(),
+            },
+            SystemCall::AwaitRestore => Self::AwaitRestore,
+            &SystemCall::JoinResult { target, timeout } => Self::JoinResult { target, timeout },
+            SystemCall::JoinAny(targets) => Self::JoinAny(targets.clone()),
+            &SystemCall::ChannelOpen { cap } => Self::ChannelOpen { cap },
+            &SystemCall::Send { chan, .. } => Self::Send { chan },
+            SystemCall::Recv(chan) => Self::Recv(*chan),
+            &SystemCall::RecvTimeout { chan, dur } => Self::RecvTimeout { chan, dur },
+            SystemCall::ChannelClose(chan) => Self::ChannelClose(*chan),
+            SystemCall::SyncCreate(kind) => Self::SyncCreate(*kind),
+            SystemCall::Acquire(id) => Self::Acquire(*id),
+            SystemCall::Release(id) => Self::Release(*id),
+            SystemCall::EventSet(id) => Self::EventSet(*id),
+            SystemCall::EventWait(id) => Self::EventWait(*id),
+            SystemCall::BarrierWait(id) => Self::BarrierWait(*id),
+            SystemCall::SyncDestroy(id) => Self::SyncDestroy(*id),
+        }
+    }
+}
+
+/// A traced [`SyscallReply`], without task values, messages or handles.
+/// I/O errors are kept as their [`io::ErrorKind`] rendering.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceReply {
+    Spawned(TaskId),
+    Joined(Result<(), TaskError>),
+    JoinedAny(TaskId, Result<(), TaskError>),
+    ChannelOpened(ChannelId),
+    /// Whether the message was delivered.
+    Sent(bool),
+    Received(Result<(), ChannelError>),
+    SyncCreated(SyncId),
+    Acquired,
+    Signalled,
+    BarrierPassed {
+        leader: bool,
+    },
+    Unknown(u64),
+    Woken,
+    IoWaited(IoWake),
+    /// Pid of the started child.
+    ProcessSpawned(Result<u32, String>),
+    /// Exit code of the child, if it exited normally.
+    ProcessExited(Result<Option<i32>, String>),
+    Continue,
+    Cancelled,
+}
+
+impl From<&SyscallReply> for TraceReply {
+    fn from(reply: &SyscallReply) -> Self {
+        match reply {
+            SyscallReply::Spawned(tid) => Self::Spawned(*tid),
+            SyscallReply::Joined(res) => Self::Joined(elide(res)),
+            SyscallReply::JoinedAny(tid, res) => Self::JoinedAny(*tid, elide(res)),
+            SyscallReply::ChannelOpened(chan) => Self::ChannelOpened(*chan),
+            SyscallReply::Sent(res) => Self::Sent(res.is_ok()),
+            SyscallReply::Received(res) => Self::Received(elide(res)),
+            SyscallReply::SyncCreated(id) => Self::SyncCreated(*id),
+            SyscallReply::Acquired => Self::Acquired,
+            SyscallReply::Signalled => Self::Signalled,
+            &SyscallReply::BarrierPassed { leader } => Self::BarrierPassed { leader },
+            SyscallReply::Unknown(id) => Self::Unknown(*id),
+            SyscallReply::Woken => Self::Woken,
+            SyscallReply::IoWaited(wake) => Self::IoWaited(*wake),
+            #[cfg(feature = "async-io")]
+            SyscallReply::ProcessSpawned(res) => Self::ProcessSpawned(
+                res.as_ref()
+                    .map(|child| child.id())
+                    .map_err(|e| e.kind().to_string()),
+            ),
+            #[cfg(feature = "async-io")]
+            SyscallReply::ProcessExited(res) => Self::ProcessExited(
+                res.as_ref()
+                    .map(|status| status.code())
+                    .map_err(|e| e.kind().to_string()),
+            ),
+            SyscallReply::Continue => Self::Continue,
+            SyscallReply::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+fn elide<T, E: Clone>(res: &Result<T, E>) -> Result<(), E> {
+    res.as_ref().map(|_| ()).map_err(E::clone)
+}
+
+/// What happened to the caller of a traced syscall.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceOutcome {
+    /// The caller was requeued and may continue.
+    Continued,
+    /// The caller waits for a wakeup or a reply.
+    Blocked,
+    /// The caller was answered immediately.
+    Replied(TraceReply),
+    /// The call ended the caller.
+    Exited,
+}
+
+/// One entry of a scheduler trace.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceEvent {
+    /// `tid` issued `call`.
+    Syscall {
+        tid: TaskId,
+        call: TraceCall,
+        at: Duration,
+        outcome: TraceOutcome,
+    },
+    /// `tid` left the scheduler in `state`; these events form `done_order`.
+    Exited {
+        tid: TaskId,
+        state: TaskState,
+        at: Duration,
+    },
+}
+
+/// Destination for trace events.
+pub trait TraceSink: Send {
+    fn record(&mut self, event: &TraceEvent);
+}
+
+/// Sink keeping events in memory; clones share the same buffer.
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    events: Arc<Mutex<Vec<TraceEvent>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events recorded so far.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl TraceSink for MemorySink {
+    fn record(&mut self, event: &TraceEvent) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
+
+/// Sink appending one JSON object per line to a writer.
+pub struct JsonLinesSink<W> {
+    out: W,
+}
+
+impl<W: Write + Send> JsonLinesSink<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Send> TraceSink for JsonLinesSink<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        let res = serde_json::to_writer(&mut self.out, event)
+            .map_err(io::Error::from)
+            .and_then(|()| self.out.write_all(b"\n"));
+        if let Err(e) = res {
+            tracing::warn!(?e, "failed to write trace event");
+        }
+    }
+}
+
+/// Read a trace written by [`JsonLinesSink`].
+pub fn read_json_lines(input: impl BufRead) -> io::Result<Vec<TraceEvent>> {
+    input
+        .lines()
+        .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// First point where a replay differed from the recorded trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the first differing event.
+    pub index: usize,
+    /// Recorded event, or `None` if the replay produced extra events.
+    pub expected: Option<TraceEvent>,
+    /// Replayed event, or `None` if the replay stopped early.
+    pub actual: Option<TraceEvent>,
+}
+
+/// Re-run a program along a recorded trace and compare the result.
+///
+/// `setup` must spawn the same tasks as the recorded run. The tasks are
+/// stepped in lockstep in the order their syscalls appear in `trace`, so the
+/// replay checks `done_order`, task states and every syscall outcome. Traces
+/// of simulated schedulers always replay; traces of free-running ones do if
+/// tasks only continued after the scheduler handled their previous call.
+pub fn replay_trace(
+    trace: &[TraceEvent],
+    setup: impl FnOnce(&mut Scheduler),
+) -> Result<(), Box<Divergence>> {
+    let schedule = trace
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::Syscall { tid, .. } => Some(*tid),
+            TraceEvent::Exited { .. } => None,
+        })
+        .collect();
+    let sink = MemorySink::new();
+    let mut sched = Scheduler::replaying(schedule);
+    sched.set_trace_sink(Box::new(sink.clone()));
+    setup(&mut sched);
+    sched.run();
+
+    let actual = sink.events();
+    let index = trace
+        .iter()
+        .zip(&actual)
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or(trace.len().min(actual.len()));
+    if index == trace.len() && index == actual.len() {
+        return Ok(());
+    }
+    Err(Box::new(Divergence {
+        index,
+        expected: trace.get(index).cloned(),
+        actual: actual.get(index).cloned(),
+    }))
+}
@@ -1,20 +1,54 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
+use crate::channel::ChannelId;
+use crate::io::IoTrigger;
+use serde::{Deserialize, Serialize};
+
+use crate::ready_queue::{ReadyEntry, ReadyQueue};
+use crate::snapshot::TaskStatus;
+use crate::sync::SyncId;
 use crate::task::{TaskId, TaskState};
 
+/// Resource a parked task is waiting on, used to key timeouts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum WaitTarget {
+    /// Completion of another task.
+    Task(TaskId),
+    /// A message on a channel.
+    Recv(ChannelId),
+    /// Readiness of an I/O source.
+    Io(u64),
+}
+
 /// Map of tasks waiting on other tasks to complete.
 #[derive(Default)]
 pub struct WaitMap {
     join_waiters: HashMap<TaskId, Vec<TaskId>>, // target -> waiting tasks
     io_waiters: HashMap<u64, Vec<TaskId>>,      // source_id -> waiting tasks
+    recv_waiters: HashMap<ChannelId, VecDeque<TaskId>>, // channel -> parked receivers
+    send_waiters: HashMap<ChannelId, VecDeque<TaskId>>, // channel -> parked senders
+    sync_waiters: HashMap<SyncId, ReadyQueue>,  // sync object -> parked tasks
+    io_pending: HashMap<u64, u64>,              // sources ready with nobody waiting -> stamp
+    io_pending_order: VecDeque<(u64, u64)>,     // (source, stamp), oldest first
+    io_stamp: u64,
 }
 
+/// Most sources whose readiness is remembered while nobody waits on them;
+/// the oldest is forgotten first.
+pub const IO_PENDING_LIMIT: usize = 1024;
+
 impl WaitMap {
     /// Create a new empty `WaitMap`.
     pub fn new() -> Self {
         Self {
             join_waiters: HashMap::new(),
             io_waiters: HashMap::new(),
+            recv_waiters: HashMap::new(),
+            send_waiters: HashMap::new(),
+            sync_waiters: HashMap::new(),
+            io_pending: HashMap::new(),
+            io_pending_order: VecDeque::new(),
+            io_stamp: 0,
         }
     }
 
@@ -30,15 +64,96 @@ impl WaitMap {
     }
 
     /// Record that `waiter` is waiting for the I/O resource `source_id`.
+    #[deprecated(note = "use `try_wait_io`, which consumes readiness that arrived earlier")]
     pub fn wait_io(&mut self, source_id: u64, waiter: TaskId) {
         self.io_waiters.entry(source_id).or_default().push(waiter);
     }
 
     /// Notify tasks waiting on an I/O resource.
+    #[deprecated(note = "use `complete_io_with`, which keeps readiness nobody waits for")]
     pub fn complete_io(&mut self, source_id: u64) -> Vec<TaskId> {
         self.io_waiters.remove(&source_id).unwrap_or_default()
     }
 
+    /// Record that `waiter` is waiting for the I/O resource `source_id`.
+    /// Returns `false` without recording anything if readiness arrived
+    /// while nobody waited; the waiter consumes it instead.
+    pub fn try_wait_io(&mut self, source_id: u64, waiter: TaskId) -> bool {
+        if self.io_pending.remove(&source_id).is_some() {
+            return false;
+        }
+        self.io_waiters.entry(source_id).or_default().push(waiter);
+        true
+    }
+
+    /// Take the tasks to wake for a readiness event on an I/O resource: all
+    /// of them for [`IoTrigger::WakeAll`], the longest-waiting one for
+    /// [`IoTrigger::WakeOne`]. Readiness nobody waits for is kept for the
+    /// next waiter, up to [`IO_PENDING_LIMIT`] sources.
+    pub fn complete_io_with(&mut self, source_id: u64, trigger: IoTrigger) -> Vec<TaskId> {
+        let Some(list) = self.io_waiters.get_mut(&source_id) else {
+            self.keep_io_pending(source_id);
+            return Vec::new();
+        };
+        let woken = match trigger {
+            IoTrigger::WakeAll => std::mem::take(list),
+            IoTrigger::WakeOne => vec![list.remove(0)],
+        };
+        if list.is_empty() {
+            self.io_waiters.remove(&source_id);
+        }
+        woken
+    }
+
+    /// Remember readiness of `source_id`, forgetting the oldest remembered
+    /// source past [`IO_PENDING_LIMIT`].
+    fn keep_io_pending(&mut self, source_id: u64) {
+        if self.io_pending.contains_key(&source_id) {
+            return;
+        }
+        self.io_stamp += 1;
+        self.io_pending.insert(source_id, self.io_stamp);
+        self.io_pending_order.push_back((source_id, self.io_stamp));
+        while self.io_pending.len() > IO_PENDING_LIMIT {
+            let (oldest, stamp) = self.io_pending_order.pop_front().expect("pending order");
+            if self.io_pending.get(&oldest) == Some(&stamp) {
+                self.io_pending.remove(&oldest);
+            }
+        }
+        // Consumed readiness lingers in the order; drop it now and then.
+        if self.io_pending_order.len() > 2 * IO_PENDING_LIMIT {
+            let pending = &self.io_pending;
+            self.io_pending_order
+                .retain(|(id, stamp)| pending.get(id) == Some(stamp));
+        }
+    }
+
+    /// Returns `true` if readiness of `source_id` is waiting to be consumed.
+    pub fn io_pending(&self, source_id: u64) -> bool {
+        self.io_pending.contains_key(&source_id)
+    }
+
+    /// Remove a waiter whose I/O wait timed out.
+    pub fn remove_io_waiter(&mut self, source_id: u64, waiter: TaskId) -> bool {
+        if let Some(list) = self.io_waiters.get_mut(&source_id)
+            && let Some(pos) = list.iter().position(|&w| w == waiter)
+        {
+            list.remove(pos);
+            if list.is_empty() {
+                self.io_waiters.remove(&source_id);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forget an I/O resource, returning the tasks that waited on it.
+    pub fn forget_io(&mut self, source_id: u64) -> Vec<TaskId> {
+        self.io_pending.remove(&source_id);
+        self.io_waiters.remove(&source_id).unwrap_or_default()
+    }
+
     /// Remove a specific waiter from a target's wait list.
     pub fn remove_waiter(&mut self, target: TaskId, waiter: TaskId) -> bool {
         if let Some(list) = self.join_waiters.get_mut(&target)
@@ -53,4 +168,186 @@ impl WaitMap {
             false
         }
     }
+
+    /// Drop `waiter` from every join and I/O wait list. Returns `true` if it
+    /// was waiting on any.
+    pub fn forget_task_waiter(&mut self, waiter: TaskId) -> bool {
+        let mut found = false;
+        for waits in [&mut self.join_waiters, &mut self.io_waiters] {
+            waits.retain(|_, list| {
+                let before = list.len();
+                list.retain(|&w| w != waiter);
+                found |= list.len() != before;
+                !list.is_empty()
+            });
+        }
+        found
+    }
+
+    /// Park `waiter` until a message arrives on `chan`.
+    pub fn wait_recv(&mut self, chan: ChannelId, waiter: TaskId) {
+        self.recv_waiters.entry(chan).or_default().push_back(waiter);
+    }
+
+    /// Park `waiter` until there is room on `chan`.
+    pub fn wait_send(&mut self, chan: ChannelId, waiter: TaskId) {
+        self.send_waiters.entry(chan).or_default().push_back(waiter);
+    }
+
+    /// Take the longest-waiting receiver on `chan`.
+    pub fn pop_receiver(&mut self, chan: ChannelId) -> Option<TaskId> {
+        pop_front(&mut self.recv_waiters, chan)
+    }
+
+    /// Take the longest-waiting sender on `chan`.
+    pub fn pop_sender(&mut self, chan: ChannelId) -> Option<TaskId> {
+        pop_front(&mut self.send_waiters, chan)
+    }
+
+    /// Remove a receiver whose wait timed out.
+    pub fn remove_receiver(&mut self, chan: ChannelId, waiter: TaskId) -> bool {
+        remove_from(&mut self.recv_waiters, chan, waiter)
+    }
+
+    /// Remove every task parked on `chan`, returning `(receivers, senders)`.
+    pub fn close_channel(&mut self, chan: ChannelId) -> (Vec<TaskId>, Vec<TaskId>) {
+        let recv = self.recv_waiters.remove(&chan).unwrap_or_default();
+        let send = self.send_waiters.remove(&chan).unwrap_or_default();
+        (recv.into(), send.into())
+    }
+
+    /// Park a task on a synchronization object. Waiters are ordered like the
+    /// ready queue: by priority, then FIFO.
+    pub fn wait_sync(&mut self, id: SyncId, entry: ReadyEntry) {
+        self.sync_waiters.entry(id).or_default().push(entry);
+    }
+
+    /// Requeue a task parked on synchronization objects under a new entry,
+    /// e.g. after its priority changed.
+    pub fn requeue_sync(&mut self, entry: ReadyEntry) {
+        for queue in self.sync_waiters.values_mut() {
+            if queue.remove(entry.tid) {
+                queue.push(entry);
+            }
+        }
+    }
+
+    /// Take the next waiter on a synchronization object.
+    pub fn pop_sync(&mut self, id: SyncId) -> Option<TaskId> {
+        let queue = self.sync_waiters.get_mut(&id)?;
+        let tid = queue.pop();
+        if queue.is_empty() {
+            self.sync_waiters.remove(&id);
+        }
+        tid
+    }
+
+    /// Take every waiter on a synchronization object in wakeup order.
+    pub fn drain_sync(&mut self, id: SyncId) -> Vec<TaskId> {
+        let mut queue = self.sync_waiters.remove(&id).unwrap_or_default();
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    /// Drop `waiter` from every channel queue, e.g. when it is cancelled.
+    pub fn forget_channel_waiter(&mut self, waiter: TaskId) {
+        for queues in [&mut self.recv_waiters, &mut self.send_waiters] {
+            queues.retain(|_, list| {
+                list.retain(|&w| w != waiter);
+                !list.is_empty()
+            });
+        }
+    }
+
+    /// Drop `waiter` from every synchronization queue, returning the objects
+    /// it was parked on.
+    pub fn forget_sync_waiter(&mut self, waiter: TaskId) -> Vec<SyncId> {
+        let mut parked_on = Vec::new();
+        self.sync_waiters.retain(|&id, queue| {
+            if queue.remove(waiter) {
+                parked_on.push(id);
+            }
+            !queue.is_empty()
+        });
+        parked_on
+    }
+
+    /// Describe what `waiter` is parked on, if anything.
+    pub fn blocked_on(&self, waiter: TaskId) -> Option<TaskStatus> {
+        let find = |map: &HashMap<u64, Vec<TaskId>>| {
+            map.iter()
+                .find(|(_, list)| list.contains(&waiter))
+                .map(|(&key, _)| key)
+        };
+        let find_queue = |map: &HashMap<ChannelId, VecDeque<TaskId>>| {
+            map.iter()
+                .find(|(_, list)| list.contains(&waiter))
+                .map(|(&key, _)| key)
+        };
+        if let Some(target) = find(&self.join_waiters) {
+            return Some(TaskStatus::Joining {
+                targets: vec![target],
+            });
+        }
+        if let Some(source) = find(&self.io_waiters) {
+            return Some(TaskStatus::IoWait { source });
+        }
+        if let Some(chan) = find_queue(&self.recv_waiters) {
+            return Some(TaskStatus::Receiving { chan });
+        }
+        if let Some(chan) = find_queue(&self.send_waiters) {
+            return Some(TaskStatus::Sending { chan });
+        }
+        self.sync_waiters
+            .iter()
+            .find(|(_, queue)| queue.contains(waiter))
+            .map(|(&id, _)| TaskStatus::Synchronizing { id })
+    }
+
+    /// Whether `waiter` is still parked on `target`.
+    pub fn is_waiting(&self, waiter: TaskId, target: WaitTarget) -> bool {
+        match target {
+            WaitTarget::Task(target) => self
+                .join_waiters
+                .get(&target)
+                .is_some_and(|list| list.contains(&waiter)),
+            WaitTarget::Recv(chan) => self
+                .recv_waiters
+                .get(&chan)
+                .is_some_and(|list| list.contains(&waiter)),
+            WaitTarget::Io(source) => self
+                .io_waiters
+                .get(&source)
+                .is_some_and(|list| list.contains(&waiter)),
+        }
+    }
+}
+
+fn pop_front<K: std::hash::Hash + Eq + Copy>(
+    map: &mut HashMap<K, VecDeque<TaskId>>,
+    key: K,
+) -> Option<TaskId> {
+    let list = map.get_mut(&key)?;
+    let tid = list.pop_front();
+    if list.is_empty() {
+        map.remove(&key);
+    }
+    tid
+}
+
+fn remove_from<K: std::hash::Hash + Eq + Copy>(
+    map: &mut HashMap<K, VecDeque<TaskId>>,
+    key: K,
+    waiter: TaskId,
+) -> bool {
+    if let Some(list) = map.get_mut(&key)
+        && let Some(pos) = list.iter().position(|&w| w == waiter)
+    {
+        list.remove(pos);
+        if list.is_empty() {
+            map.remove(&key);
+        }
+        true
+    } else {
+        false
+    }
 }
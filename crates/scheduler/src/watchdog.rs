@@ -0,0 +1,72 @@
+//! Detection of tasks that hog their time slice.
+//!
+//! Tasks are cooperative, so the scheduler cannot interrupt one that runs
+//! without making a syscall. Instead of giving up on the whole run, the
+//! watchdog notices a resumed task that stays silent past the stall timeout,
+//! records a [`StallReport`] and lets the loop carry on with other tasks.
+//!
+//! A coroutine's stack cannot be walked from the scheduler thread, so the
+//! report starts out with the task's nested-call frames. The task itself
+//! captures a backtrace at its next syscall, i.e. where the hogging code
+//! finally yielded, if backtraces are enabled (`RUST_BACKTRACE`).
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::task::TaskId;
+
+/// A task that ran longer than the stall timeout without yielding.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StallReport {
+    pub tid: TaskId,
+    pub name: Option<String>,
+    /// Virtual time at which the stall was detected.
+    pub detected_at: Duration,
+    /// Nested calls the task was inside when the stall was detected.
+    pub call_stack: Vec<String>,
+    /// Backtrace taken where the task next yielded, if one was captured.
+    pub backtrace: Option<String>,
+    /// Real time between resuming the task and its next syscall; `None`
+    /// while the task is still stalled.
+    pub stalled_for: Option<Duration>,
+}
+
+/// Shared between a task and the scheduler so a stalled task can leave a
+/// backtrace behind when it yields again.
+#[derive(Debug, Default)]
+pub(crate) struct StallProbe {
+    flagged: AtomicBool,
+    backtrace: Mutex<Option<String>>,
+}
+
+impl StallProbe {
+    /// Ask the task to capture a backtrace at its next syscall.
+    pub fn flag(&self) {
+        self.flagged.store(true, Ordering::Release);
+    }
+
+    /// Called by the task before each syscall.
+    pub fn capture_if_flagged(&self) {
+        if !self.flagged.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let trace = Backtrace::capture();
+        if trace.status() == BacktraceStatus::Captured {
+            *self
+                .backtrace
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(trace.to_string());
+        }
+    }
+
+    pub fn take_backtrace(&self) -> Option<String> {
+        self.flagged.store(false, Ordering::Release);
+        self.backtrace
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
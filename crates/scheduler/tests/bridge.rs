@@ -0,0 +1,82 @@
+use crossbeam::channel::unbounded;
+use futures::channel::oneshot;
+use futures::executor::block_on;
+use scheduler::{Channel, Scheduler, TaskError, task::TaskContext, task::TaskState};
+use serial_test::file_serial;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+#[file_serial]
+fn block_on_parks_only_the_awaiting_task() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    let (value_tx, value_rx) = oneshot::channel();
+    let awaited = tx.clone();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            assert_eq!(ctx.block_on(async { 7 }), Ok(7));
+            let value = ctx.block_on(value_rx).unwrap().unwrap();
+            awaited.send(("awaited", value)).unwrap();
+        });
+    }
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            for _ in 0..3 {
+                ctx.yield_now();
+            }
+            tx.send(("other", 0)).unwrap();
+        });
+    }
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        value_tx.send(42).unwrap();
+    });
+    sched.run();
+
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![("other", 0), ("awaited", 42)]
+    );
+}
+
+#[test]
+#[file_serial]
+fn completion_future_resolves_when_the_task_ends() {
+    let (handle, server) = Scheduler::new().serve_in_background();
+    let (tx, rx) = unbounded();
+    let worker = handle
+        .spawn(|ctx: TaskContext| {
+            let idle = Channel::<()>::unbounded(&ctx);
+            let _ = idle.recv_timeout(&ctx, Duration::from_millis(20));
+        })
+        .unwrap();
+    let stuck = handle
+        .spawn(move |ctx: TaskContext| {
+            let never = futures::future::pending::<()>();
+            tx.send(ctx.block_on(never)).unwrap();
+        })
+        .unwrap();
+
+    assert_eq!(
+        block_on(handle.completion(worker)),
+        Some(TaskState::Finished)
+    );
+    // Already ended, and never known.
+    assert_eq!(
+        block_on(handle.completion(worker)),
+        Some(TaskState::Finished)
+    );
+    assert_eq!(block_on(handle.completion(9999)), None);
+
+    handle.cancel(stuck).unwrap();
+    assert_eq!(
+        block_on(handle.completion(stuck)),
+        Some(TaskState::Cancelled)
+    );
+    assert_eq!(rx.recv(), Ok(Err(TaskError::Cancelled)));
+
+    handle.drain();
+    server.join().unwrap();
+    assert_eq!(block_on(handle.completion(worker)), None);
+}
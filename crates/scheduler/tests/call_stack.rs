@@ -0,0 +1,90 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Channel, Scheduler, SystemCall, TaskError,
+    sync::Event,
+    task::{TaskContext, TaskId},
+};
+use serial_test::file_serial;
+use std::time::Duration;
+
+/// Sub-coroutine that blocks on the scheduler before returning a value.
+fn fetch(ctx: &TaskContext, chan: Channel<u32>) -> (TaskId, u32) {
+    ctx.syscall(SystemCall::Sleep(Duration::from_millis(5)));
+    (ctx.tid, chan.recv(ctx).unwrap())
+}
+
+#[test]
+#[file_serial]
+fn nested_calls_block_and_return_values() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    let tid = unsafe {
+        sched
+            .spawn(move |ctx: TaskContext| {
+                let chan = Channel::<u32>::unbounded(&ctx);
+                let producer = ctx.spawn(10, move |ctx: TaskContext| {
+                    chan.send(&ctx, 20).unwrap();
+                    chan.send(&ctx, 22).unwrap();
+                });
+                let total = ctx.call("sum", |ctx| {
+                    let (a_tid, a) = ctx.call("fetch", |ctx| fetch(ctx, chan));
+                    let (b_tid, b) = ctx.call("fetch", |ctx| fetch(ctx, chan));
+                    tx.send(vec![a_tid, b_tid]).unwrap();
+                    a + b
+                });
+                producer.join(&ctx).unwrap();
+                tx.send(vec![total as u64]).unwrap();
+            })
+            .id()
+    };
+    sched.run();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![vec![tid, tid], vec![42]]
+    );
+}
+
+#[test]
+#[file_serial]
+fn snapshot_shows_active_frames() {
+    let mut sched = Scheduler::simulated(0);
+    unsafe {
+        sched.spawn(|ctx: TaskContext| {
+            let never = Event::new(&ctx);
+            ctx.call("serve", |ctx| {
+                ctx.call("accept", |ctx| never.wait(ctx));
+            });
+        });
+    }
+    sched.run();
+    let snap = sched.snapshot();
+    assert_eq!(snap.tasks[0].call_stack, vec!["serve", "accept"]);
+}
+
+#[test]
+#[file_serial]
+fn panic_report_names_the_frames_it_unwound() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let nested = ctx.spawn(10, |ctx: TaskContext| {
+                ctx.call("outer", |ctx| ctx.call("inner", |_| panic!("boom")))
+            });
+            let returned = ctx.spawn(10, |ctx: TaskContext| {
+                ctx.call("outer", |ctx| ctx.yield_now());
+                panic!("later");
+            });
+            tx.send(nested.join(&ctx).unwrap_err()).unwrap();
+            tx.send(returned.join(&ctx).unwrap_err()).unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![
+            TaskError::Panicked("boom (in outer > inner)".into()),
+            TaskError::Panicked("later".into()),
+        ]
+    );
+}
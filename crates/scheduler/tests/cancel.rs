@@ -1,4 +1,9 @@
-use scheduler::{Scheduler, SystemCall, task::TaskContext};
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Channel, ChannelError, Scheduler, SystemCall, TaskError,
+    sync::{Event, Mutex},
+    task::TaskContext,
+};
 use serial_test::file_serial;
 use std::sync::{Arc, Barrier};
 use std::thread;
@@ -13,18 +18,22 @@ fn cancel_child() {
         let handle = unsafe { sched.start(s, barrier.clone()) };
 
         let child = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Sleep(Duration::from_millis(100)));
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Sleep(Duration::from_millis(100)));
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         let parent = unsafe {
-            sched.spawn(move |ctx: TaskContext| {
-                ctx.syscall(SystemCall::Cancel(child));
-                ctx.syscall(SystemCall::Join(child));
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Cancel(child));
+                    ctx.syscall(SystemCall::Join(child));
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         barrier.wait();
@@ -35,3 +44,115 @@ fn cancel_child() {
     assert_eq!(order.first().copied(), Some(child));
     assert!(order.contains(&parent));
 }
+
+#[test]
+#[file_serial]
+fn cancelled_task_sees_errors_releases_locks_and_runs_hooks() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let lock = Mutex::new(&ctx);
+            let idle = Channel::<u32>::unbounded(&ctx);
+            let wal = Channel::<&str>::bounded(&ctx, 0);
+            let hook_tx = tx.clone();
+            let worker = ctx.spawn(10, move |ctx: TaskContext| {
+                ctx.on_cancel(move |ctx| wal.send(ctx, "first-registered").unwrap());
+                let marker_tx = hook_tx.clone();
+                ctx.on_cancel(move |ctx| {
+                    marker_tx
+                        .send(format!("hook sees {}", ctx.is_cancelled()))
+                        .unwrap();
+                    wal.send(ctx, "marker").unwrap();
+                });
+                let _guard = lock.lock(&ctx);
+                let res = idle.recv(&ctx);
+                hook_tx.send(format!("recv {res:?}")).unwrap();
+                // Waits that cannot report an error unwind instead.
+                Event::new(&ctx).wait(&ctx);
+                unreachable!("the wait unwinds");
+            });
+            ctx.syscall(SystemCall::Yield);
+            worker.cancel(&ctx);
+            // Hooks may block: both markers arrive, most recent first.
+            tx.send(wal.recv(&ctx).unwrap().into()).unwrap();
+            tx.send(wal.recv(&ctx).unwrap().into()).unwrap();
+            tx.send(format!("join {:?}", worker.join(&ctx))).unwrap();
+            let _guard = lock.lock(&ctx);
+            tx.send("lock released".into()).unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<String>>(),
+        vec![
+            "recv Err(Cancelled)",
+            "hook sees true",
+            "marker",
+            "first-registered",
+            "join Err(Cancelled)",
+            "lock released",
+        ]
+    );
+}
+
+#[test]
+#[file_serial]
+fn waits_after_cancel_return_immediately() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let idle = Channel::<u32>::unbounded(&ctx);
+            let child = ctx.spawn(10, |ctx: TaskContext| Event::new(&ctx).wait(&ctx));
+            ctx.syscall(SystemCall::Cancel(ctx.tid));
+            let recv = idle.recv(&ctx);
+            tx.send((recv, ctx.check_cancelled(), child.join(&ctx)))
+                .unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(
+        rx.try_recv().unwrap(),
+        (
+            Err(ChannelError::Cancelled),
+            Err(TaskError::Cancelled),
+            Err(TaskError::Cancelled)
+        )
+    );
+}
+
+#[test]
+#[file_serial]
+fn grace_period_falls_back_to_hard_cancel() {
+    for grace in [Duration::ZERO, Duration::from_millis(50)] {
+        let mut sched = Scheduler::new();
+        sched.set_cancel_grace(grace);
+        let (tx, rx) = unbounded();
+        let hooks = tx.clone();
+        unsafe {
+            sched.spawn(move |ctx: TaskContext| {
+                let stuck = ctx.spawn(10, move |ctx: TaskContext| {
+                    ctx.on_cancel(move |ctx| {
+                        hooks.send("hook started").unwrap();
+                        // A hook that never finishes is cut off by the grace.
+                        Event::new(ctx).wait(ctx);
+                    });
+                    Event::new(&ctx).wait(&ctx);
+                });
+                ctx.syscall(SystemCall::Yield);
+                stuck.cancel(&ctx);
+                assert_eq!(stuck.join(&ctx), Err(TaskError::Cancelled));
+                tx.send("joined").unwrap();
+            });
+        }
+        sched.run();
+        let expected = if grace.is_zero() {
+            vec!["joined"]
+        } else {
+            vec!["hook started", "joined"]
+        };
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), expected);
+        assert!(sched.snapshot().now >= grace);
+    }
+}
@@ -0,0 +1,95 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Channel, ChannelError, Scheduler, SendError,
+    task::{TaskContext, TaskState},
+};
+use serial_test::file_serial;
+use std::time::Duration;
+
+#[test]
+#[file_serial]
+fn bounded_channel_parks_sender_until_received() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let chan = Channel::<u32>::bounded(&ctx, 1);
+            let log = tx.clone();
+            ctx.spawn(10, move |ctx: TaskContext| {
+                for i in 0..3 {
+                    chan.send(&ctx, i).unwrap();
+                    log.send(format!("sent {i}")).unwrap();
+                }
+                chan.close(&ctx);
+            });
+            while let Ok(v) = chan.recv(&ctx) {
+                tx.send(format!("recv {v}")).unwrap();
+            }
+            tx.send("closed".to_string()).unwrap();
+        });
+    }
+    let order = sched.run();
+    assert_eq!(order.len(), 2);
+    let events: Vec<String> = rx.try_iter().collect();
+    let received: Vec<&String> = events.iter().filter(|e| e.starts_with("recv")).collect();
+    assert_eq!(received, ["recv 0", "recv 1", "recv 2"]);
+    assert_eq!(events.last().map(String::as_str), Some("closed"));
+}
+
+#[test]
+#[file_serial]
+fn recv_timeout_and_send_after_close() {
+    let mut sched = Scheduler::new();
+    let tid = unsafe {
+        sched
+            .spawn(|ctx: TaskContext| {
+                let chan = Channel::<&'static str>::unbounded(&ctx);
+                let waited = chan.recv_timeout(&ctx, Duration::from_secs(30));
+                assert_eq!(waited, Err(ChannelError::TimedOut));
+
+                chan.send(&ctx, "queued").unwrap();
+                chan.close(&ctx);
+                assert_eq!(chan.send(&ctx, "late"), Err(SendError("late")));
+                assert_eq!(chan.recv(&ctx), Ok("queued"));
+                assert_eq!(chan.recv(&ctx), Err(ChannelError::Closed));
+            })
+            .id()
+    };
+    sched.run();
+    assert_eq!(sched.task_state(tid), Some(TaskState::Finished));
+}
+
+#[test]
+#[file_serial]
+fn closed_channels_are_forgotten_once_drained() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            for _ in 0..10 {
+                let chan = Channel::<u32>::unbounded(&ctx);
+                chan.close(&ctx);
+            }
+            let buffered = Channel::<u32>::unbounded(&ctx);
+            buffered.send(&ctx, 7).unwrap();
+            buffered.close(&ctx);
+            let open = Channel::<u32>::unbounded(&ctx);
+            open.send(&ctx, 1).unwrap();
+            tx.send(buffered).unwrap();
+        });
+    }
+    sched.run();
+    // The buffered channel keeps its message until it is received.
+    assert_eq!(sched.channel_count(), 2);
+
+    let buffered = rx.recv().unwrap();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            assert_eq!(buffered.recv(&ctx), Ok(7));
+            assert_eq!(buffered.recv(&ctx), Err(ChannelError::Closed));
+            assert!(buffered.send(&ctx, 8).is_err());
+        });
+    }
+    sched.run();
+    assert_eq!(sched.channel_count(), 1);
+}
@@ -0,0 +1,191 @@
+use crossbeam::channel::{Sender, unbounded};
+use scheduler::{
+    Channel, Checkpoint, CheckpointError, HybridClock, PendingWait, Scheduler, TaskId, TimerSpec,
+    task::TaskContext,
+};
+use serial_test::file_serial;
+use std::thread;
+use std::time::Duration;
+
+/// Block the calling task for `dur` on the scheduler's clock.
+fn pause(ctx: &TaskContext, dur: Duration) {
+    let never = Channel::<()>::unbounded(ctx);
+    let _ = never.recv_timeout(ctx, dur);
+}
+
+/// Register the kinds the tests spawn: `nap` pauses for its argument in
+/// seconds, `parent` runs a `nap` child and joins it.
+fn register(sched: &mut Scheduler, done: Sender<TaskId>) {
+    let nap_done = done.clone();
+    sched.register_task("nap", move |ctx: TaskContext, secs: u64| {
+        pause(&ctx, Duration::from_secs(secs));
+        nap_done.send(ctx.tid).unwrap();
+    });
+    sched.register_task("parent", move |ctx: TaskContext, secs: u64| {
+        let child = unsafe { ctx.builder().spawn_registered("nap", &secs) }.unwrap();
+        child.join(&ctx).unwrap();
+        done.send(ctx.tid).unwrap();
+    });
+}
+
+/// Checkpoint a scheduler serving a named nap, a parent joining its nap
+/// child, an unregistered task and a one-shot timer.
+fn checkpoint_of_busy_scheduler() -> (Checkpoint, TaskId, TaskId) {
+    let mut sched = Scheduler::with_clock(Box::new(HybridClock::new()));
+    register(&mut sched, unbounded().0);
+    let napper = unsafe {
+        sched
+            .builder()
+            .name("napper")
+            .priority(3)
+            .tag("run", "1")
+            .spawn_registered("nap", &3600)
+    }
+    .unwrap();
+    let parent = unsafe { sched.builder().spawn_registered("parent", &7200) }.unwrap();
+    unsafe {
+        sched.spawn(|ctx: TaskContext| pause(&ctx, Duration::from_secs(3600)));
+        sched
+            .spawn_registered_timer(TimerSpec::at(Duration::from_secs(1800)), "nap", &0)
+            .unwrap();
+    }
+    let (handle, thread) = sched.serve_in_background();
+    thread::sleep(Duration::from_millis(50));
+    let checkpoint = handle.checkpoint().unwrap();
+    handle.shutdown();
+    thread.join().unwrap();
+    (checkpoint, napper, parent)
+}
+
+#[test]
+#[file_serial]
+fn checkpoint_records_registered_tasks_and_their_waits() {
+    let (checkpoint, napper, parent) = checkpoint_of_busy_scheduler();
+    let json = serde_json::to_string(&checkpoint).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Checkpoint>(&json).unwrap(),
+        checkpoint
+    );
+
+    assert_eq!(checkpoint.tasks.len(), 3);
+    let saved = &checkpoint.tasks[0];
+    assert_eq!(saved.id, napper);
+    assert_eq!(saved.priority, 3);
+    assert_eq!(saved.meta.name.as_deref(), Some("napper"));
+    assert_eq!(saved.meta.tags["run"], "1");
+    assert_eq!(saved.task.kind, "nap");
+    assert!(matches!(
+        saved.wait,
+        Some(PendingWait::Sleep { until }) if until >= Duration::from_secs(3600)
+    ));
+
+    let child = &checkpoint.tasks[2];
+    assert_eq!(child.parent, Some(parent));
+    assert_eq!(
+        checkpoint.tasks[1].wait,
+        Some(PendingWait::Join {
+            targets: vec![child.id],
+            until: None
+        })
+    );
+    assert_eq!(checkpoint.skipped.len(), 1);
+
+    let timer = &checkpoint.timers[0];
+    assert_eq!(timer.task.kind, "nap");
+    assert_eq!(timer.next_at, Some(Duration::from_secs(1800)));
+    assert_eq!(timer.fired, 0);
+}
+
+#[test]
+#[file_serial]
+fn restore_waits_out_old_sleeps_and_joins() {
+    let (checkpoint, napper, parent) = checkpoint_of_busy_scheduler();
+    let child = checkpoint.tasks[2].id;
+    let last = *checkpoint.skipped.iter().max().unwrap();
+
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    register(&mut sched, tx);
+    unsafe { sched.restore(&checkpoint) }.unwrap();
+    assert!(sched.snapshot().now >= checkpoint.now);
+    assert_eq!(sched.timers()[0].next_at, Some(Duration::from_secs(1800)));
+    sched.run();
+
+    let done: Vec<TaskId> = rx.try_iter().collect();
+    // The timer's run, then the napper restarting its hour after its old
+    // sleep, the child likewise after two hours and finally the parent,
+    // which joins a new child once the old one is gone.
+    assert_eq!(done.len(), 5);
+    assert!(done[0] > last);
+    assert_eq!(done[1], napper);
+    assert_eq!(done[2], child);
+    assert!(done[3] > last);
+    assert_eq!(done[4], parent);
+    assert!(sched.snapshot().now >= Duration::from_secs(3 * 7200));
+}
+
+#[test]
+#[file_serial]
+fn restore_rejects_unknown_kinds_and_busy_schedulers() {
+    let (checkpoint, ..) = checkpoint_of_busy_scheduler();
+
+    let mut sched = Scheduler::new();
+    sched.register_task("nap", |_ctx: TaskContext, _secs: u64| {});
+    assert_eq!(
+        unsafe { sched.restore(&checkpoint) },
+        Err(CheckpointError::UnknownKind("parent".into()))
+    );
+    assert!(sched.snapshot().tasks.is_empty());
+    assert!(matches!(
+        unsafe { sched.builder().spawn_registered("nap", &"soon") },
+        Err(CheckpointError::BadArgs { .. })
+    ));
+
+    unsafe { sched.spawn(|_ctx: TaskContext| {}) };
+    register(&mut sched, unbounded().0);
+    assert_eq!(
+        unsafe { sched.restore(&checkpoint) },
+        Err(CheckpointError::NotEmpty)
+    );
+}
+
+#[test]
+#[file_serial]
+fn handles_spawn_registered_tasks_and_orphans_restore_as_roots() {
+    let mut sched = Scheduler::with_clock(Box::new(HybridClock::new()));
+    register(&mut sched, unbounded().0);
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let orphan = ctx.builder().spawn_registered("nap", &3600).unwrap();
+            tx.send(orphan.id()).unwrap();
+            let _ = orphan.join(&ctx);
+        })
+    };
+    let (handle, thread) = sched.serve_in_background();
+    let submitted = handle.builder().spawn_registered("nap", &3600).unwrap();
+    assert!(matches!(
+        handle.builder().spawn_registered("nap", &"soon"),
+        Err(CheckpointError::BadArgs { .. })
+    ));
+    let orphan = rx.recv().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let checkpoint = handle.checkpoint().unwrap();
+    handle.shutdown();
+    thread.join().unwrap();
+    assert!(matches!(
+        handle.builder().spawn_registered("nap", &1),
+        Err(CheckpointError::Rejected(_))
+    ));
+
+    let saved = |tid| checkpoint.tasks.iter().find(|t| t.id == tid).unwrap();
+    assert_eq!(checkpoint.tasks.len(), 2);
+    assert_eq!(saved(submitted).parent, None);
+    assert_eq!(saved(orphan).parent, Some(checkpoint.skipped[0]));
+
+    let mut sched = Scheduler::new();
+    register(&mut sched, unbounded().0);
+    unsafe { sched.restore(&checkpoint) }.unwrap();
+    assert_eq!(sched.parent_of(orphan), None);
+    sched.run();
+}
@@ -0,0 +1,63 @@
+use crossbeam::channel::unbounded;
+use scheduler::{Channel, ClockKind, HybridClock, Scheduler, WallClock, task::TaskContext};
+use serial_test::file_serial;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Block the calling task for `dur` on the scheduler's clock.
+fn pause(ctx: &TaskContext, dur: Duration) {
+    let never = Channel::<()>::unbounded(ctx);
+    let _ = never.recv_timeout(ctx, dur);
+}
+
+#[test]
+#[file_serial]
+fn wall_clock_waits_out_sleeps() {
+    let mut sched = Scheduler::with_clock(Box::new(WallClock));
+    unsafe {
+        sched.spawn(|ctx: TaskContext| {
+            pause(&ctx, Duration::from_millis(100));
+        });
+    }
+    let start = Instant::now();
+    let order = sched.run();
+    let elapsed = start.elapsed();
+    assert_eq!(order.len(), 1);
+    assert!(elapsed >= Duration::from_millis(100), "took {elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5), "took {elapsed:?}");
+    assert!(sched.snapshot().now >= Duration::from_millis(100));
+}
+
+#[test]
+#[file_serial]
+fn hybrid_clock_can_be_fast_forwarded() {
+    let mut sched = Scheduler::with_clock(Box::new(HybridClock::new()));
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            pause(&ctx, Duration::from_secs(3600));
+            tx.send(()).unwrap();
+        });
+    }
+    let (handle, thread) = sched.serve_in_background();
+    thread::sleep(Duration::from_millis(50));
+    assert!(rx.try_recv().is_err());
+
+    let start = Instant::now();
+    handle.advance_clock(Duration::from_secs(3600)).unwrap();
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    handle.drain();
+    assert_eq!(thread.join().unwrap().len(), 1);
+}
+
+#[test]
+fn clock_kinds_use_lowercase_names() {
+    let kind: ClockKind = serde_json::from_str("\"hybrid\"").unwrap();
+    assert_eq!(kind, ClockKind::Hybrid);
+    assert_eq!(serde_json::to_string(&ClockKind::Wall).unwrap(), "\"wall\"");
+    assert_eq!(ClockKind::default(), ClockKind::Wall);
+    assert!(ClockKind::Virtual.build().is_virtual());
+    assert!(!ClockKind::Hybrid.build().is_virtual());
+}
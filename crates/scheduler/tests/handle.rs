@@ -0,0 +1,77 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    HandleError, Scheduler, TaskStatus, sync::Event, task::TaskContext, task::TaskState,
+};
+use serial_test::file_serial;
+use std::time::Duration;
+
+#[test]
+#[file_serial]
+fn handle_cancels_queries_and_subscribes_while_serving() {
+    let (handle, server) = Scheduler::new().serve_in_background();
+    let (tx, rx) = unbounded();
+    let waiter = handle
+        .builder()
+        .name("waiter")
+        .spawn(move |ctx: TaskContext| {
+            let never = Event::new(&ctx);
+            tx.send(()).unwrap();
+            never.wait(&ctx);
+        })
+        .unwrap();
+    let finished = handle.subscribe(waiter).unwrap();
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(handle.state(waiter), Ok(Some(TaskState::Running)));
+    let snap = handle.snapshot().unwrap();
+    assert_eq!(snap.tasks.len(), 1);
+    assert_eq!(snap.tasks[0].name.as_deref(), Some("waiter"));
+    assert!(matches!(
+        snap.tasks[0].status,
+        TaskStatus::Synchronizing { .. }
+    ));
+
+    handle.cancel(waiter).unwrap();
+    assert_eq!(
+        finished.recv_timeout(Duration::from_secs(5)),
+        Ok(TaskState::Cancelled)
+    );
+    assert_eq!(handle.state(waiter), Ok(Some(TaskState::Cancelled)));
+    // Late subscribers hear about tasks that already ended; unknown ids
+    // just disconnect.
+    assert_eq!(
+        handle.subscribe(waiter).unwrap().recv(),
+        Ok(TaskState::Cancelled)
+    );
+    assert!(handle.subscribe(9999).unwrap().recv().is_err());
+
+    handle.drain();
+    assert_eq!(server.join().unwrap(), vec![waiter]);
+    assert_eq!(handle.state(waiter), Err(HandleError::Stopped));
+    assert_eq!(handle.cancel(waiter), Err(HandleError::Stopped));
+}
+
+#[test]
+#[file_serial]
+fn handles_submit_from_many_threads() {
+    let (handle, server) = Scheduler::new().serve_in_background();
+    let submitters: Vec<_> = (0..4u32)
+        .map(|i| {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                let tid = handle
+                    .spawn(move |ctx: TaskContext| {
+                        ctx.yield_now();
+                        i
+                    })
+                    .unwrap();
+                handle.subscribe(tid).unwrap().recv().unwrap()
+            })
+        })
+        .collect();
+    for submitter in submitters {
+        assert_eq!(submitter.join().unwrap(), TaskState::Finished);
+    }
+    handle.shutdown();
+    assert_eq!(server.join().unwrap().len(), 4);
+}
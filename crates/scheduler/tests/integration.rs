@@ -45,9 +45,11 @@ fn integration_join_and_io_wait() {
         let io_tx = sched.io_handle();
 
         let child = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         unsafe {
@@ -0,0 +1,215 @@
+use crossbeam::channel::unbounded;
+#[cfg(feature = "async-io")]
+use nix::unistd::{close, pipe, write};
+use scheduler::{IO_PENDING_LIMIT, IoTrigger, IoWake, Scheduler, WaitMap, task::TaskContext};
+#[cfg(feature = "async-io")]
+use scheduler::{IoInterest, IoSource};
+use serial_test::file_serial;
+#[cfg(feature = "async-io")]
+use std::os::unix::io::RawFd;
+#[cfg(feature = "async-io")]
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(feature = "async-io")]
+struct Pipe {
+    fd: RawFd,
+    id: u64,
+    interest: IoInterest,
+    trigger: IoTrigger,
+}
+
+#[cfg(feature = "async-io")]
+impl IoSource for Pipe {
+    fn raw_fd(&self) -> RawFd {
+        self.fd
+    }
+    fn id(&self) -> u64 {
+        self.id
+    }
+    fn interest(&self) -> IoInterest {
+        self.interest
+    }
+    fn trigger(&self) -> IoTrigger {
+        self.trigger
+    }
+}
+
+#[cfg(not(feature = "async-io"))]
+#[test]
+#[file_serial]
+fn io_wait_timeout_tells_readiness_from_timeout() {
+    let mut sched = Scheduler::new();
+    let io_tx = sched.io_handle();
+    let (tx, rx) = unbounded();
+    for (source, dur) in [(1, Duration::from_millis(30)), (2, Duration::from_secs(5))] {
+        let tx = tx.clone();
+        unsafe {
+            sched.spawn(move |ctx: TaskContext| {
+                tx.send((source, ctx.io_wait_timeout(source, dur))).unwrap();
+            });
+        }
+    }
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        io_tx.send(2).unwrap();
+    });
+    sched.run();
+
+    let woken: Vec<_> = rx.try_iter().collect();
+    assert_eq!(woken, vec![(1, IoWake::TimedOut), (2, IoWake::Ready)]);
+}
+
+#[cfg(not(feature = "async-io"))]
+#[test]
+#[file_serial]
+fn readiness_without_waiters_is_kept_for_the_next_wait() {
+    let mut sched = Scheduler::new();
+    sched.io_handle().send(7).unwrap();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            ctx.yield_now();
+            tx.send(ctx.io_wait_timeout(7, Duration::from_millis(30)))
+                .unwrap();
+            tx.send(ctx.io_wait_timeout(7, Duration::from_millis(30)))
+                .unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![IoWake::Ready, IoWake::TimedOut]
+    );
+}
+
+#[cfg(not(feature = "async-io"))]
+#[test]
+#[file_serial]
+fn level_readiness_wakes_every_waiter() {
+    let mut sched = Scheduler::new();
+    let io_tx = sched.io_handle();
+    let (tx, rx) = unbounded();
+    for _ in 0..2 {
+        let tx = tx.clone();
+        unsafe {
+            sched.spawn(move |ctx: TaskContext| {
+                tx.send(ctx.io_wait_timeout(3, Duration::from_secs(5)))
+                    .unwrap();
+            });
+        }
+    }
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        io_tx.send(3).unwrap();
+    });
+    sched.run();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![IoWake::Ready, IoWake::Ready]
+    );
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+#[file_serial]
+fn wake_one_readiness_wakes_the_longest_waiter_only() {
+    let (rfd, wfd) = pipe().unwrap();
+    let mut sched = Scheduler::new();
+    sched.register_io(Arc::new(Pipe {
+        fd: rfd,
+        id: 1,
+        interest: IoInterest::Readable,
+        trigger: IoTrigger::WakeOne,
+    }));
+    let (tx, rx) = unbounded();
+    for name in ["first", "second"] {
+        let tx = tx.clone();
+        unsafe {
+            sched.spawn(move |ctx: TaskContext| {
+                tx.send((name, ctx.io_wait_timeout(1, Duration::from_millis(300))))
+                    .unwrap();
+            });
+        }
+    }
+    let writer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        write(wfd, &[1u8]).unwrap();
+        wfd
+    });
+    sched.run();
+    let _ = close(writer.join().unwrap());
+    let _ = close(rfd);
+
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![("first", IoWake::Ready), ("second", IoWake::TimedOut)]
+    );
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+#[file_serial]
+fn writable_interest_and_deregistration() {
+    let (rfd, wfd) = pipe().unwrap();
+    let mut sched = Scheduler::new();
+    sched.register_io(Arc::new(Pipe {
+        fd: wfd,
+        id: 1,
+        interest: IoInterest::Writable,
+        trigger: IoTrigger::WakeAll,
+    }));
+    sched.register_io(Arc::new(Pipe {
+        fd: rfd,
+        id: 2,
+        interest: IoInterest::Readable,
+        trigger: IoTrigger::WakeAll,
+    }));
+    assert!(sched.deregister_io(2));
+    assert!(!sched.deregister_io(2));
+    write(wfd, &[1u8]).unwrap();
+
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            // An empty pipe is writable at once; the unwatched reader never
+            // wakes although it holds data.
+            tx.send(ctx.io_wait_timeout(1, Duration::from_secs(5)))
+                .unwrap();
+            tx.send(ctx.io_wait_timeout(2, Duration::from_millis(50)))
+                .unwrap();
+        });
+    }
+    sched.run();
+    let _ = close(wfd);
+    let _ = close(rfd);
+
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![IoWake::Ready, IoWake::TimedOut]
+    );
+}
+
+#[test]
+#[file_serial]
+fn unconsumed_readiness_is_bounded_and_legacy_waits_still_work() {
+    let mut map = WaitMap::new();
+    for source in 0..=IO_PENDING_LIMIT as u64 {
+        assert!(map.complete_io_with(source, IoTrigger::WakeAll).is_empty());
+    }
+    assert!(!map.io_pending(0));
+    assert!(map.io_pending(IO_PENDING_LIMIT as u64));
+    assert!(!map.try_wait_io(1, 7));
+    assert!(map.try_wait_io(1, 7));
+    assert_eq!(map.forget_io(1), vec![7]);
+
+    #[allow(deprecated)]
+    {
+        map.wait_io(2, 8);
+        map.wait_io(2, 9);
+        assert_eq!(map.complete_io(2), vec![8, 9]);
+        assert!(map.complete_io(u64::MAX).is_empty());
+        assert!(!map.io_pending(u64::MAX));
+    }
+}
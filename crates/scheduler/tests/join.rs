@@ -9,16 +9,20 @@ fn test_join_wakes_waiter() {
     let order = std::thread::scope(|s| {
         let handle = unsafe { sched.start(s, barrier.clone()) };
         let child = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         let _parent = unsafe {
-            sched.spawn(move |ctx: TaskContext| {
-                ctx.syscall(SystemCall::Join(child));
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Join(child));
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         barrier.wait();
@@ -10,22 +10,28 @@ fn join_wake_before_next_ready() {
         let handle = unsafe { sched.start(s, barrier.clone()) };
 
         let child = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         let _parent = unsafe {
-            sched.spawn(move |ctx: TaskContext| {
-                ctx.syscall(SystemCall::Join(child));
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Join(child));
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         let _third = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         barrier.wait();
@@ -0,0 +1,106 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Channel, JoinHandle, Scheduler, SystemCall, TaskError,
+    task::{TaskContext, TaskState},
+};
+use serial_test::file_serial;
+use std::time::Duration;
+
+#[test]
+#[file_serial]
+fn join_returns_child_value() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let child = ctx.spawn(10, |_ctx: TaskContext| 6 * 7);
+            tx.send(child.join(&ctx)).unwrap();
+        });
+    }
+    let order = sched.run();
+    assert_eq!(order.len(), 2);
+    assert_eq!(rx.try_recv().unwrap(), Ok(42));
+}
+
+#[test]
+#[file_serial]
+fn join_reports_panic_payload() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let child: JoinHandle<u32> = ctx.spawn(10, |_ctx: TaskContext| panic!("boom"));
+            tx.send((child.id(), child.join(&ctx))).unwrap();
+        });
+    }
+    sched.run();
+    let (child, res) = rx.try_recv().unwrap();
+    assert_eq!(res, Err(TaskError::Panicked("boom".into())));
+    assert_eq!(sched.task_state(child), Some(TaskState::Failed));
+}
+
+#[test]
+#[file_serial]
+fn join_reports_cancel_and_timeout() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            // The child waits on its own parent, so it only ends by cancel.
+            let parent = JoinHandle::<()>::new(ctx.tid);
+            let mut slow = ctx.spawn(10, move |ctx: TaskContext| parent.join(&ctx));
+            tx.send(slow.join_timeout(&ctx, Duration::from_millis(10)))
+                .unwrap();
+            ctx.syscall(SystemCall::Cancel(slow.id()));
+            tx.send(slow.join(&ctx)).unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(rx.try_recv().unwrap(), Err(TaskError::TimedOut));
+    assert_eq!(rx.try_recv().unwrap(), Err(TaskError::Cancelled));
+}
+
+#[test]
+#[file_serial]
+fn dropped_handles_release_their_outcome() {
+    let mut sched = Scheduler::new();
+    let root = unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let joined = ctx.spawn(10, |_ctx: TaskContext| 1);
+            let ended = ctx.spawn(10, |_ctx: TaskContext| 2);
+            let running = ctx.spawn(10, |ctx: TaskContext| {
+                let never = Channel::<()>::unbounded(&ctx);
+                let _ = never.recv_timeout(&ctx, Duration::from_millis(100));
+            });
+            drop(running);
+            ctx.yield_now();
+            ctx.yield_now();
+            drop(ended);
+            assert_eq!(joined.join(&ctx), Ok(1));
+        })
+    };
+    sched.run();
+    // Only the root task, whose handle is still held, keeps its outcome.
+    assert_eq!(sched.unclaimed_results(), 1);
+    drop(root);
+}
+
+#[test]
+#[file_serial]
+fn root_spawns_return_typed_handles() {
+    let mut sched = Scheduler::new();
+    let answer = unsafe { sched.spawn(|_ctx: TaskContext| 6 * 7) };
+    let label = unsafe { sched.spawn(|_ctx: TaskContext| "forty-two") };
+    let mistyped = JoinHandle::<u64>::new(label.id());
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            tx.send(answer.join(&ctx)).unwrap();
+            tx.send(mistyped.join(&ctx).map(|n| n as i32)).unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(rx.try_recv().unwrap(), Ok(42));
+    assert_eq!(rx.try_recv().unwrap(), Err(TaskError::WrongType));
+    drop(label);
+}
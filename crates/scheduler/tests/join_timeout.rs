@@ -14,21 +14,25 @@ fn join_timeout_wakes() {
         let handle = unsafe { sched.start(s, barrier.clone()) };
 
         let child = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Sleep(Duration::from_millis(100)));
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Sleep(Duration::from_millis(100)));
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         let parent = unsafe {
-            sched.spawn(move |ctx: TaskContext| {
-                ctx.syscall(SystemCall::JoinTimeout {
-                    target: child,
-                    dur: Duration::from_millis(10),
-                });
-                ctx.syscall(SystemCall::Cancel(child));
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    ctx.syscall(SystemCall::JoinTimeout {
+                        target: child,
+                        dur: Duration::from_millis(10),
+                    });
+                    ctx.syscall(SystemCall::Cancel(child));
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         barrier.wait();
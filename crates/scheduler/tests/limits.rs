@@ -0,0 +1,109 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Channel, ChannelError, Scheduler, TaskError, TaskLimit, sync::Event, task::TaskContext,
+    task::TaskState,
+};
+use serial_test::file_serial;
+use std::time::{Duration, Instant};
+
+/// Poll a channel nobody sends on until the task is cancelled, counting the
+/// timeouts it saw.
+fn poll_forever(ctx: &TaskContext) -> u32 {
+    let idle = Channel::<u32>::unbounded(ctx);
+    let mut polls = 0;
+    loop {
+        match idle.recv_timeout(ctx, Duration::from_millis(10)) {
+            Err(ChannelError::TimedOut) => polls += 1,
+            Err(ChannelError::Cancelled) => return polls,
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
+
+#[test]
+#[file_serial]
+fn deadline_cancels_the_task_and_its_children() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    let (child_tx, child_rx) = unbounded();
+    let tid = unsafe {
+        sched
+            .builder()
+            .deadline(Duration::from_millis(55))
+            .spawn(move |ctx: TaskContext| {
+                let child = ctx.spawn(10, |ctx: TaskContext| poll_forever(&ctx));
+                ctx.on_cancel(move |_| child_tx.send(child.id()).unwrap());
+                tx.send(poll_forever(&ctx)).unwrap();
+            })
+            .id()
+    };
+    sched.run();
+
+    // The last poll ends at the deadline, before its own timeout.
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![5]);
+    assert_eq!(sched.task_state(tid), Some(TaskState::TimedOut));
+    let child = child_rx.try_recv().unwrap();
+    assert_eq!(sched.task_state(child), Some(TaskState::Cancelled));
+}
+
+#[test]
+#[file_serial]
+fn syscall_budget_is_reported_to_joiners() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let (out_tx, out_rx) = unbounded();
+            let chatty = ctx
+                .builder()
+                .syscall_budget(4)
+                .spawn(move |ctx: TaskContext| out_tx.send(poll_forever(&ctx)).unwrap());
+            let res = chatty.join(&ctx);
+            tx.send(format!("{:?} after {:?}", res, out_rx.try_recv()))
+                .unwrap();
+            let within = ctx
+                .builder()
+                .syscall_budget(4)
+                .spawn(|ctx: TaskContext| ctx.yield_now());
+            tx.send(format!("{:?}", within.join(&ctx))).unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![
+            // The channel open and three polls fit the budget; the fourth
+            // poll is cut short.
+            "Err(LimitExceeded(Syscalls)) after Ok(3)",
+            "Ok(())",
+        ]
+    );
+    assert_eq!(
+        TaskError::LimitExceeded(TaskLimit::Syscalls).to_string(),
+        "task exceeded its syscall budget"
+    );
+}
+
+#[test]
+#[file_serial]
+fn wall_time_budget_ends_a_task_blocked_forever() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    let tid = unsafe {
+        sched
+            .builder()
+            .wall_time_budget(Duration::from_millis(50))
+            .spawn(move |ctx: TaskContext| {
+                let never = Event::new(&ctx);
+                ctx.on_cancel(move |ctx| tx.send(ctx.is_cancelled()).unwrap());
+                never.wait(&ctx);
+            })
+            .id()
+    };
+    let started = Instant::now();
+    sched.run();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![true]);
+    assert_eq!(sched.task_state(tid), Some(TaskState::TimedOut));
+}
@@ -0,0 +1,160 @@
+use scheduler::{
+    Channel, JoinHandle, PalBus, PalRecvError, Scheduler, Stage, SystemCall, TaskEvent, TaskId,
+    task::TaskContext,
+};
+use serial_test::file_serial;
+use std::iter;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Block the calling task for `dur` of virtual time.
+fn pause(ctx: &TaskContext, dur: Duration) {
+    let never = Channel::<()>::unbounded(ctx);
+    let _ = never.recv_timeout(ctx, dur);
+}
+
+fn stages(events: &[TaskEvent], tid: TaskId) -> Vec<Stage> {
+    events
+        .iter()
+        .filter(|event| event.task_id == tid)
+        .map(|event| event.stage)
+        .collect()
+}
+
+#[test]
+#[file_serial]
+fn scheduler_publishes_task_lifecycle() {
+    let mut sched = Scheduler::new();
+    let sub = sched.pal().subscribe();
+    let root = unsafe {
+        sched
+            .builder()
+            .name("agent")
+            .spawn(|ctx: TaskContext| {
+                ctx.report(Stage::WaitingForLlm, "asking for a plan");
+                ctx.spawn(10, |ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Sleep(Duration::from_secs(1)));
+                });
+                let worker = ctx.spawn(10, |ctx: TaskContext| pause(&ctx, Duration::from_secs(1)));
+                worker.join(&ctx).unwrap();
+                let _: JoinHandle<()> = ctx.spawn(10, |_ctx: TaskContext| panic!("boom"));
+                let stuck = ctx.spawn(10, |ctx: TaskContext| {
+                    pause(&ctx, Duration::from_secs(3600))
+                });
+                stuck.cancel(&ctx);
+            })
+            .id()
+    };
+    sched.run();
+
+    let events: Vec<TaskEvent> = iter::from_fn(|| sub.try_recv().unwrap()).collect();
+    assert_eq!(events[0].task_id, root);
+    assert_eq!(events[0].message, "spawned \"agent\"");
+    assert_eq!(
+        stages(&events, root),
+        [
+            Stage::Spawned,
+            Stage::WaitingForLlm,
+            Stage::Joining,
+            Stage::Completed
+        ]
+    );
+    let children: Vec<TaskId> = events
+        .iter()
+        .filter(|event| event.message == format!("spawned by task {root}"))
+        .map(|event| event.task_id)
+        .collect();
+    assert_eq!(children.len(), 4);
+    assert_eq!(
+        stages(&events, children[0]),
+        [Stage::Spawned, Stage::Sleeping, Stage::Completed]
+    );
+    assert_eq!(
+        stages(&events, children[1]),
+        [Stage::Spawned, Stage::Completed]
+    );
+    assert_eq!(
+        stages(&events, children[2]),
+        [Stage::Spawned, Stage::Failed]
+    );
+    let failed = events.iter().find(|e| e.stage == Stage::Failed).unwrap();
+    assert_eq!(failed.message, "task panicked: boom");
+    assert_eq!(
+        stages(&events, children[3]),
+        [Stage::Spawned, Stage::Cancelled]
+    );
+    assert_eq!(sched.pal().recent(), events);
+}
+
+#[test]
+fn slow_subscribers_lag_and_late_ones_replay() {
+    let bus = PalBus::with_capacity(2, 3);
+    let slow = bus.subscribe();
+    for tid in 0..5 {
+        bus.publish(TaskEvent::new(tid, Stage::Idle, "waiting"));
+    }
+    assert_eq!(slow.try_recv(), Err(PalRecvError::Lagged(3)));
+    assert_eq!(slow.try_recv().unwrap().unwrap().task_id, 0);
+    assert_eq!(slow.try_recv().unwrap().unwrap().task_id, 1);
+    assert_eq!(slow.try_recv(), Ok(None));
+
+    let late = bus.subscribe();
+    let only = bus.subscribe_task(3);
+    bus.publish(TaskEvent::new(3, Stage::Completed, "finished"));
+    drop(bus);
+
+    let ids: Vec<TaskId> = iter::from_fn(|| late.recv().ok())
+        .map(|event| event.task_id)
+        .collect();
+    assert_eq!(ids, [2, 3, 4, 3]);
+    assert_eq!(only.recv().unwrap().stage, Stage::Idle);
+    assert_eq!(only.recv().unwrap().stage, Stage::Completed);
+    assert_eq!(only.recv(), Err(PalRecvError::Closed));
+}
+
+#[test]
+fn events_follow_the_adr_schema() {
+    let event = TaskEvent {
+        ts: UNIX_EPOCH + Duration::from_secs(1_751_040_720),
+        task_id: 7,
+        stage: Stage::WaitingForLlm,
+        message: "Running shell: cargo test".into(),
+    };
+    assert_eq!(
+        serde_json::to_string(&event).unwrap(),
+        r#"{"ts":"2025-06-27T16:12:00.000Z","task_id":7,"stage":"WaitingForLLM","message":"Running shell: cargo test"}"#
+    );
+}
+
+#[test]
+#[file_serial]
+fn events_carry_scheduler_time() {
+    let mut sched = Scheduler::simulated(7);
+    let sub = sched.pal().subscribe();
+    let tid = unsafe {
+        sched
+            .spawn(|ctx: TaskContext| {
+                ctx.syscall(SystemCall::Sleep(Duration::from_secs(5)));
+                ctx.report(Stage::ToolExecutionStart, "cargo test");
+            })
+            .id()
+    };
+    sched.run();
+
+    let events: Vec<TaskEvent> = iter::from_fn(|| sub.try_recv().unwrap()).collect();
+    let tool = events
+        .iter()
+        .find(|event| event.stage == Stage::ToolExecutionStart)
+        .unwrap();
+    assert_eq!(tool.task_id, tid);
+    assert_eq!(tool.ts, UNIX_EPOCH + Duration::from_secs(5));
+    assert_eq!(events[0].ts, UNIX_EPOCH);
+}
+
+#[test]
+fn events_nobody_receives_are_not_built() {
+    let bus = PalBus::with_capacity(4, 0);
+    bus.publish_with(|| unreachable!("no subscriber and no replay"));
+    let sub = bus.subscribe();
+    bus.publish_with(|| TaskEvent::new(1, Stage::Idle, "waiting"));
+    assert_eq!(sub.try_recv().unwrap().unwrap().task_id, 1);
+}
@@ -15,17 +15,21 @@ fn panic_isolation() {
         let handle = unsafe { sched.start(s, barrier.clone()) };
 
         let child = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Done);
-                panic!("boom");
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Done);
+                    panic!("boom");
+                })
+                .id()
         };
 
         let parent = unsafe {
-            sched.spawn(move |ctx: TaskContext| {
-                ctx.syscall(SystemCall::Join(child));
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Join(child));
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         barrier.wait();
@@ -1,3 +1,5 @@
+//! I/O sources need the `async-io` feature; without it this file builds no
+//! tests. Run with `cargo test -p scheduler --features async-io`.
 #[cfg(feature = "async-io")]
 use nix::unistd::{close, pipe, write};
 #[cfg(feature = "async-io")]
@@ -0,0 +1,88 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Aging, Channel, FairShare, ReadyEntry, ReadyQueue, Scheduler, SchedulingPolicy, Strict,
+    sync::Mutex, task::TaskContext,
+};
+use serial_test::file_serial;
+use std::time::Duration;
+
+fn entry(tid: u64, pri: u8, seq: u64, group: u64) -> ReadyEntry {
+    ReadyEntry::new(pri, seq, tid).in_group(group)
+}
+
+/// Queue one priority-10 task, then repeatedly push and pop priority-0 work.
+/// Returns the round in which the priority-10 task ran, if it did.
+fn rounds_until_low_runs(policy: Box<dyn SchedulingPolicy>) -> Option<u64> {
+    let mut q = ReadyQueue::with_policy(policy);
+    q.push(entry(1, 10, 0, 0));
+    for round in 1..=200 {
+        q.push(entry(100 + round, 0, round, 0));
+        if q.pop() == Some(1) {
+            return Some(round);
+        }
+    }
+    None
+}
+
+#[test]
+#[file_serial]
+fn strict_priority_starves_low_priority_work() {
+    assert_eq!(rounds_until_low_runs(Box::new(Strict)), None);
+}
+
+#[test]
+#[file_serial]
+fn aging_lets_low_priority_work_through() {
+    let round = rounds_until_low_runs(Box::new(Aging::new(4))).unwrap();
+    assert!(round <= 40, "ran after {round} rounds");
+}
+
+#[test]
+#[file_serial]
+fn fair_share_splits_dispatches_by_weight() {
+    let mut q = ReadyQueue::with_policy(Box::new(FairShare::new().weight(1, 3)));
+    for tid in 0..40 {
+        q.push(entry(tid, 10, tid, tid % 2));
+    }
+    let mut per_group = [0; 2];
+    for _ in 0..20 {
+        let tid = q.pop().unwrap();
+        per_group[(tid % 2) as usize] += 1;
+    }
+    assert_eq!(per_group, [5, 15]);
+}
+
+#[test]
+#[file_serial]
+fn set_priority_reorders_parked_child() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let lock = Mutex::new(&ctx);
+            let guard = lock.lock(&ctx);
+            let mut children = Vec::new();
+            for (name, pri) in [("low", 20), ("mid", 10)] {
+                let tx = tx.clone();
+                children.push(ctx.spawn(pri, move |ctx: TaskContext| {
+                    let _guard = lock.lock(&ctx);
+                    tx.send(name).unwrap();
+                }));
+            }
+            // A sibling may not re-prioritize outside its own subtree.
+            let mid = children[1].id();
+            let meddler = ctx.spawn(10, move |ctx: TaskContext| ctx.set_priority(mid, 0));
+            meddler.join(&ctx).unwrap();
+            // Time only advances once both children are parked on the lock.
+            let idle = Channel::<()>::unbounded(&ctx);
+            let _ = idle.recv_timeout(&ctx, Duration::from_millis(10));
+            ctx.set_priority(children[0].id(), 1);
+            drop(guard);
+            for child in children {
+                child.join(&ctx).unwrap();
+            }
+        });
+    }
+    sched.run();
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["low", "mid"]);
+}
@@ -8,15 +8,19 @@ fn priority_order() {
     let mut sched = Scheduler::new();
     // Spawn tasks then run scheduler on current thread
     let _high = unsafe {
-        sched.spawn_with_priority(5, |ctx: TaskContext| {
-            ctx.syscall(SystemCall::Done);
-        })
+        sched
+            .spawn_with_priority(5, |ctx: TaskContext| {
+                ctx.syscall(SystemCall::Done);
+            })
+            .id()
     };
     let _low = unsafe {
-        sched.spawn_with_priority(20, |ctx: TaskContext| {
-            ctx.syscall(SystemCall::Sleep(Duration::from_millis(1)));
-            ctx.syscall(SystemCall::Done);
-        })
+        sched
+            .spawn_with_priority(20, |ctx: TaskContext| {
+                ctx.syscall(SystemCall::Sleep(Duration::from_millis(1)));
+                ctx.syscall(SystemCall::Done);
+            })
+            .id()
     };
     let order = sched.run();
     println!("order: {order:?}");
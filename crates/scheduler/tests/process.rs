@@ -0,0 +1,80 @@
+//! Child processes need the `async-io` feature; without it this file builds
+//! no tests. Run with `cargo test -p scheduler --features async-io`.
+#![cfg(feature = "async-io")]
+
+use crossbeam::channel::unbounded;
+use scheduler::{Scheduler, task::TaskContext};
+use serial_test::file_serial;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+fn sh(script: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(script)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    cmd
+}
+
+#[test]
+#[file_serial]
+fn task_streams_output_and_awaits_exit_status() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    let (order_tx, order_rx) = unbounded();
+    let exit_tx = order_tx.clone();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let child = ctx
+                .spawn_process(sh("echo hello; sleep 0.2; echo oops >&2; exit 3"))
+                .unwrap();
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let stdout = child.stdout.as_ref().unwrap();
+            stdout.read_to_end(&ctx, &mut out).unwrap();
+            let stderr = child.stderr.as_ref().unwrap();
+            stderr.read_to_end(&ctx, &mut err).unwrap();
+            let status = child.wait(&ctx).unwrap();
+            exit_tx.send("exited").unwrap();
+            tx.send((out, err, status.code())).unwrap();
+        });
+    }
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            // Runs to completion while the other task waits on the child.
+            for _ in 0..3 {
+                ctx.yield_now();
+            }
+            order_tx.send("other").unwrap();
+        });
+    }
+    sched.run();
+
+    let (out, err, code) = rx.try_recv().unwrap();
+    assert_eq!(out, b"hello\n");
+    assert_eq!(err, b"oops\n");
+    assert_eq!(code, Some(3));
+    assert_eq!(
+        order_rx.try_iter().collect::<Vec<_>>(),
+        vec!["other", "exited"]
+    );
+}
+
+#[test]
+#[file_serial]
+fn children_of_ended_tasks_are_killed() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let missing = ctx.spawn_process(Command::new("/nonexistent/tool"));
+            tx.send(missing.map(|_| ()).map_err(|e| e.kind())).unwrap();
+            ctx.spawn_process(sh("sleep 30")).unwrap();
+        });
+    }
+    let start = Instant::now();
+    sched.run();
+
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(rx.try_recv(), Ok(Err(std::io::ErrorKind::NotFound)));
+}
@@ -5,16 +5,8 @@ use serial_test::file_serial;
 #[file_serial]
 fn test_ready_queue_fifo() {
     let mut q = ReadyQueue::new();
-    q.push(ReadyEntry {
-        pri: 10,
-        seq: 0,
-        tid: 1,
-    });
-    q.push(ReadyEntry {
-        pri: 10,
-        seq: 1,
-        tid: 2,
-    });
+    q.push(ReadyEntry::new(10, 0, 1));
+    q.push(ReadyEntry::new(10, 1, 2));
     assert_eq!(q.len(), 2);
     assert_eq!(q.pop(), Some(1));
     assert_eq!(q.pop(), Some(2));
@@ -25,17 +17,42 @@ fn test_ready_queue_fifo() {
 #[file_serial]
 fn test_ready_queue_no_duplicates() {
     let mut q = ReadyQueue::new();
-    q.push(ReadyEntry {
-        pri: 10,
-        seq: 0,
-        tid: 1,
-    });
-    q.push(ReadyEntry {
-        pri: 10,
-        seq: 1,
-        tid: 1,
-    });
+    q.push(ReadyEntry::new(10, 0, 1));
+    q.push(ReadyEntry::new(10, 1, 1));
     assert_eq!(q.len(), 1);
     assert_eq!(q.pop(), Some(1));
     assert!(q.is_empty());
 }
+
+#[test]
+#[file_serial]
+fn test_ready_queue_remove() {
+    let mut q = ReadyQueue::new();
+    for (seq, tid) in [1, 2, 3].into_iter().enumerate() {
+        q.push(ReadyEntry::new(10, seq as u64, tid));
+    }
+    assert!(q.remove(2));
+    assert!(!q.remove(2));
+    assert!(!q.contains(2));
+    assert_eq!(q.pop(), Some(1));
+    assert_eq!(q.pop(), Some(3));
+    assert!(q.is_empty());
+}
+
+#[test]
+#[file_serial]
+fn test_ready_queue_remove_keeps_priority_order() {
+    let mut q = ReadyQueue::new();
+    for (seq, (pri, tid)) in [(5, 1), (0, 2), (9, 3), (0, 4)].into_iter().enumerate() {
+        q.push(ReadyEntry::new(pri, seq as u64, tid));
+    }
+    assert!(q.remove(2));
+    assert_eq!(q.peek().map(|e| e.tid), Some(4));
+    q.push(ReadyEntry::new(0, 4, 2));
+    assert_eq!(q.len(), 4);
+    assert_eq!(q.pop(), Some(4));
+    assert_eq!(q.pop(), Some(2));
+    assert_eq!(q.pop(), Some(1));
+    assert_eq!(q.pop(), Some(3));
+    assert!(q.is_empty());
+}
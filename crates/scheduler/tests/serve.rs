@@ -0,0 +1,97 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    HandleError, Scheduler,
+    sync::Event,
+    task::{TaskContext, TaskState},
+};
+use serial_test::file_serial;
+use std::thread;
+use std::time::Duration;
+
+/// Run a serving scheduler on its own thread and return its handle.
+fn serve() -> (scheduler::SchedulerHandle, thread::JoinHandle<Vec<u64>>) {
+    let (tx, rx) = unbounded();
+    let server = thread::spawn(move || {
+        let mut sched = Scheduler::new();
+        tx.send(sched.handle()).unwrap();
+        sched.serve()
+    });
+    (rx.recv().unwrap(), server)
+}
+
+#[test]
+#[file_serial]
+fn serve_runs_submissions_until_drained() {
+    let (handle, server) = serve();
+    let (tx, rx) = unbounded();
+
+    // The loop keeps waiting although it has nothing to do.
+    thread::sleep(Duration::from_millis(50));
+    let first_tx = tx.clone();
+    let first = handle
+        .spawn(move |_ctx: TaskContext| first_tx.send("first").unwrap())
+        .unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("first"));
+
+    let second = handle
+        .builder()
+        .name("in-flight")
+        .spawn(move |ctx: TaskContext| {
+            for _ in 0..3 {
+                ctx.yield_now();
+            }
+            tx.send("second").unwrap();
+        })
+        .unwrap();
+    handle.drain();
+    assert!(!handle.is_accepting());
+    assert_eq!(
+        handle.spawn(|_ctx: TaskContext| ()),
+        Err(HandleError::ShuttingDown)
+    );
+
+    let done = server.join().unwrap();
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["second"]);
+    assert_eq!(done, vec![first, second]);
+}
+
+#[test]
+#[file_serial]
+fn shutdown_cancels_tasks_in_flight() {
+    let (handle, server) = serve();
+    let (tx, rx) = unbounded();
+    handle
+        .spawn(move |ctx: TaskContext| {
+            let never = Event::new(&ctx);
+            let hook_tx = tx.clone();
+            ctx.on_cancel(move |_| hook_tx.send("cleaned up").unwrap());
+            tx.send("started").unwrap();
+            never.wait(&ctx);
+        })
+        .unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("started"));
+    handle.shutdown();
+
+    assert_eq!(server.join().unwrap().len(), 1);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["cleaned up"]);
+}
+
+#[test]
+#[file_serial]
+fn serve_forgets_the_oldest_ended_tasks() {
+    let mut sched = Scheduler::new();
+    sched.set_retention(2);
+    let (handle, server) = sched.serve_in_background();
+    let tids: Vec<u64> = (0..5)
+        .map(|i| {
+            let tid = handle.spawn(move |_ctx: TaskContext| i).unwrap();
+            handle.subscribe(tid).unwrap().recv().unwrap();
+            tid
+        })
+        .collect();
+
+    assert_eq!(handle.state(tids[0]), Ok(None));
+    assert_eq!(handle.state(tids[4]), Ok(Some(TaskState::Finished)));
+    handle.drain();
+    assert_eq!(server.join().unwrap(), tids[3..]);
+}
@@ -0,0 +1,91 @@
+use scheduler::{
+    Scheduler, SimOutcome,
+    sync::Event,
+    task::{TaskContext, TaskId},
+};
+use serial_test::file_serial;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Two tasks increment a shared counter with a yield between read and write,
+/// so some interleavings lose an update. Returns the final counter value.
+fn lost_update(mut sched: Scheduler) -> (u64, Vec<TaskId>, Option<SimOutcome>) {
+    let counter = Arc::new(AtomicU64::new(0));
+    for _ in 0..2 {
+        let counter = counter.clone();
+        unsafe {
+            sched.spawn(move |ctx: TaskContext| {
+                let v = counter.load(Ordering::SeqCst);
+                ctx.yield_now();
+                counter.store(v + 1, Ordering::SeqCst);
+            });
+        }
+    }
+    sched.run();
+    (
+        counter.load(Ordering::SeqCst),
+        sched.schedule().to_vec(),
+        sched.sim_outcome().cloned(),
+    )
+}
+
+#[test]
+#[file_serial]
+fn same_seed_gives_same_schedule() {
+    let (value, schedule, outcome) = lost_update(Scheduler::simulated(7));
+    assert_eq!(outcome, Some(SimOutcome::Completed));
+    for _ in 0..3 {
+        assert_eq!(
+            lost_update(Scheduler::simulated(7)),
+            (value, schedule.clone(), Some(SimOutcome::Completed))
+        );
+    }
+}
+
+#[test]
+#[file_serial]
+fn fuzzing_seeds_finds_and_replays_a_race() {
+    let results: Vec<_> = (0..32)
+        .map(|seed| (seed, lost_update(Scheduler::simulated(seed))))
+        .collect();
+    let (seed, (_, schedule, _)) = results
+        .iter()
+        .find(|(_, (value, _, _))| *value == 1)
+        .expect("some seed interleaves the read and write");
+    assert!(results.iter().any(|(_, (value, _, _))| *value == 2));
+
+    let (value, replayed, _) = lost_update(Scheduler::simulated(*seed));
+    assert_eq!((value, &replayed), (1, schedule));
+    let (value, replayed, outcome) = lost_update(Scheduler::replaying(schedule.clone()));
+    assert_eq!((value, &replayed), (1, schedule));
+    assert_eq!(outcome, Some(SimOutcome::Completed));
+}
+
+#[test]
+#[file_serial]
+fn simulation_reports_deadlock_and_divergence() {
+    let mut sched = Scheduler::simulated(1);
+    let stuck = unsafe {
+        sched
+            .spawn(|ctx: TaskContext| {
+                Event::new(&ctx).wait(&ctx);
+            })
+            .id()
+    };
+    sched.run();
+    assert_eq!(
+        sched.sim_outcome(),
+        Some(&SimOutcome::Deadlock(vec![stuck]))
+    );
+
+    let mut sched = Scheduler::replaying(vec![42]);
+    unsafe { sched.spawn(|_ctx: TaskContext| {}) };
+    sched.run();
+    assert_eq!(
+        sched.sim_outcome(),
+        Some(&SimOutcome::Diverged {
+            step: 0,
+            expected: 42
+        })
+    );
+}
@@ -0,0 +1,95 @@
+use scheduler::{
+    Channel, Scheduler, SchedulerSnapshot, SystemCall, TaskStatus, WaitTarget, task::TaskContext,
+};
+use serial_test::file_serial;
+use std::time::Duration;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[test]
+#[file_serial]
+fn snapshot_describes_each_blocked_task() {
+    let mut sched = Scheduler::simulated(11);
+    // Stop while everything but the spinner is parked; the spinner keeps the
+    // virtual clock from advancing.
+    sched.set_step_limit(200);
+    let root = unsafe {
+        sched
+            .spawn(|ctx: TaskContext| {
+                let sleeper =
+                    ctx.spawn(10, |ctx: TaskContext| ctx.syscall(SystemCall::Sleep(HOUR)));
+                ctx.spawn(10, |ctx: TaskContext| {
+                    let chan = Channel::<()>::unbounded(&ctx);
+                    let _ = chan.recv_timeout(&ctx, HOUR / 2);
+                });
+                ctx.spawn(10, |ctx: TaskContext| ctx.syscall(SystemCall::IoWait(7)));
+                ctx.spawn(20, |ctx: TaskContext| {
+                    loop {
+                        ctx.yield_now();
+                    }
+                });
+                sleeper.join(&ctx).unwrap();
+            })
+            .id()
+    };
+    sched.run();
+
+    let snap = sched.snapshot();
+    assert_eq!(snap.now, Duration::ZERO);
+    let (sleeper, receiver, io, spinner) = (root + 1, root + 2, root + 3, root + 4);
+    let status: Vec<_> = snap
+        .tasks
+        .iter()
+        .map(|t| (t.tid, t.status.clone()))
+        .collect();
+    assert_eq!(
+        status,
+        vec![
+            (
+                root,
+                TaskStatus::Joining {
+                    targets: vec![sleeper]
+                }
+            ),
+            (sleeper, TaskStatus::Sleeping { until: HOUR }),
+            (
+                receiver,
+                TaskStatus::TimedWait {
+                    target: WaitTarget::Recv(1),
+                    until: HOUR / 2
+                }
+            ),
+            (io, TaskStatus::IoWait { source: 7 }),
+            (spinner, TaskStatus::Ready),
+        ]
+    );
+    let spin = &snap.tasks[4];
+    assert_eq!(
+        (spin.parent, spin.pri, spin.name.as_deref()),
+        (Some(root), 20, None)
+    );
+    assert!(snap.tasks.iter().all(|t| t.syscalls > 0));
+    assert_eq!(snap.sleepers.len(), 1);
+    assert_eq!(snap.sleepers[0].tid, sleeper);
+    assert_eq!(snap.timeouts.len(), 1);
+    assert_eq!(snap.timeouts[0].tid, receiver);
+
+    let json = serde_json::to_string(&snap).unwrap();
+    assert_eq!(
+        serde_json::from_str::<SchedulerSnapshot>(&json).unwrap(),
+        snap
+    );
+}
+
+#[test]
+#[file_serial]
+fn snapshot_is_empty_after_run() {
+    let mut sched = Scheduler::new();
+    unsafe {
+        sched.spawn(|ctx: TaskContext| ctx.syscall(SystemCall::Sleep(Duration::from_millis(5))));
+    }
+    sched.run();
+    let snap = sched.snapshot();
+    assert!(snap.tasks.is_empty() && snap.sleepers.is_empty() && snap.timeouts.is_empty());
+    assert!(snap.now >= Duration::from_millis(5));
+}
@@ -0,0 +1,37 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Scheduler, SystemCall,
+    task::{TaskContext, TaskState},
+};
+use serial_test::file_serial;
+
+#[test]
+#[file_serial]
+fn task_spawns_child() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    let parent = unsafe {
+        sched
+            .spawn(move |ctx: TaskContext| {
+                let child = ctx
+                    .spawn(5, |ctx: TaskContext| {
+                        ctx.syscall(SystemCall::Done);
+                    })
+                    .id();
+                tx.send(child).unwrap();
+                ctx.syscall(SystemCall::Join(child));
+                ctx.syscall(SystemCall::Done);
+            })
+            .id()
+    };
+    let order = sched.run();
+    let child = rx.try_recv().expect("child id returned to parent");
+
+    assert_ne!(child, parent);
+    assert_eq!(order.len(), 2);
+    assert!(order.contains(&child));
+    assert_eq!(sched.parent_of(child), Some(parent));
+    assert_eq!(sched.children_of(parent), &[child]);
+    assert_eq!(sched.task_state(child), Some(TaskState::Finished));
+    assert_eq!(sched.parent_of(parent), None);
+}
@@ -11,9 +11,11 @@ fn stale_ready_id_is_ignored() {
     thread::scope(|s| {
         let handle = unsafe { sched.start(s, barrier.clone()) };
         let child = unsafe {
-            sched.spawn(|ctx: TaskContext| {
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(|ctx: TaskContext| {
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
         sched.ready_push_duplicate_for_test(child);
         barrier.wait();
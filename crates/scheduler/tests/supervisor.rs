@@ -0,0 +1,128 @@
+use scheduler::{
+    RestartStrategy, Scheduler, SupervisorSpec, SupervisorStatus,
+    sync::Event,
+    task::{TaskContext, TaskState},
+};
+use serial_test::file_serial;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Count incarnations of a child and return the number of the current one.
+fn incarnation(counter: &AtomicUsize) -> usize {
+    counter.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// Park the calling task until it is cancelled.
+fn park_forever(ctx: &TaskContext) {
+    Event::new(ctx).wait(ctx);
+}
+
+#[test]
+#[file_serial]
+fn one_for_one_restarts_until_child_succeeds() {
+    let mut sched = Scheduler::new();
+    let runs = Arc::new(AtomicUsize::new(0));
+    let r = runs.clone();
+    let spec = SupervisorSpec::new(RestartStrategy::OneForOne).child(10, move |_ctx| {
+        if incarnation(&r) < 3 {
+            panic!("transient failure");
+        }
+    });
+    let sup = unsafe { sched.supervise(spec) };
+    sched.run();
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+    assert_eq!(sched.supervisor_restarts(sup), 2);
+    assert_eq!(
+        sched.supervisor_status(sup),
+        Some(SupervisorStatus::Running)
+    );
+    assert_eq!(sched.supervised_children(sup), &[None]);
+}
+
+#[test]
+#[file_serial]
+fn one_for_all_restarts_live_siblings() {
+    let mut sched = Scheduler::new();
+    let (a, b) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let (ra, rb) = (a.clone(), b.clone());
+    let spec = SupervisorSpec::new(RestartStrategy::OneForAll)
+        .child(10, move |ctx| {
+            if incarnation(&ra) == 1 {
+                ctx.yield_now();
+                panic!("first run fails");
+            }
+        })
+        .child(10, move |ctx| {
+            if incarnation(&rb) == 1 {
+                park_forever(&ctx);
+            }
+        });
+    let sup = unsafe { sched.supervise(spec) };
+    sched.run();
+    assert_eq!(a.load(Ordering::SeqCst), 2);
+    assert_eq!(b.load(Ordering::SeqCst), 2);
+    assert_eq!(sched.supervisor_restarts(sup), 1);
+}
+
+#[test]
+#[file_serial]
+fn rest_for_one_leaves_earlier_children_alone() {
+    let mut sched = Scheduler::new();
+    let counters: Vec<_> = (0..3).map(|_| Arc::new(AtomicUsize::new(0))).collect();
+    // The first child stays parked on this event until the last child's
+    // restarted incarnation sets it.
+    let release = Arc::new(AtomicU64::new(0));
+    let (rx, ry, rz) = (
+        counters[0].clone(),
+        counters[1].clone(),
+        counters[2].clone(),
+    );
+    let (ex, ez) = (release.clone(), release.clone());
+    let spec = SupervisorSpec::new(RestartStrategy::RestForOne)
+        .child(10, move |ctx| {
+            incarnation(&rx);
+            let ev = Event::new(&ctx);
+            ex.store(ev.id(), Ordering::SeqCst);
+            ev.wait(&ctx);
+        })
+        .child(10, move |ctx| {
+            if incarnation(&ry) == 1 {
+                ctx.yield_now();
+                panic!("middle child fails");
+            }
+        })
+        .child(10, move |ctx| {
+            if incarnation(&rz) == 1 {
+                park_forever(&ctx);
+            }
+            Event::from_id(ez.load(Ordering::SeqCst)).set(&ctx);
+        });
+    unsafe { sched.supervise(spec) };
+    sched.run();
+    let runs: Vec<_> = counters.iter().map(|c| c.load(Ordering::SeqCst)).collect();
+    assert_eq!(runs, vec![1, 2, 2]);
+}
+
+#[test]
+#[file_serial]
+fn exceeding_intensity_gives_up_and_cancels_children() {
+    let mut sched = Scheduler::new();
+    let runs = Arc::new(AtomicUsize::new(0));
+    let r = runs.clone();
+    let spec = SupervisorSpec::new(RestartStrategy::OneForOne)
+        .intensity(2, Duration::from_secs(1))
+        .backoff(Duration::from_millis(100), Duration::from_millis(150))
+        .child(10, move |_ctx| {
+            incarnation(&r);
+            panic!("always fails");
+        })
+        .child(10, |ctx| park_forever(&ctx));
+    let sup = unsafe { sched.supervise(spec) };
+    let parked = sched.supervised_children(sup)[1].unwrap();
+    sched.run();
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+    assert_eq!(sched.supervisor_restarts(sup), 2);
+    assert_eq!(sched.supervisor_status(sup), Some(SupervisorStatus::GaveUp));
+    assert_eq!(sched.task_state(parked), Some(TaskState::Cancelled));
+}
@@ -0,0 +1,135 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Scheduler, SystemCall,
+    sync::{Barrier, Event, Mutex, Semaphore},
+    task::{TaskContext, TaskError},
+};
+use serial_test::file_serial;
+
+#[test]
+#[file_serial]
+fn mutex_wakes_waiters_by_priority() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let lock = Mutex::new(&ctx);
+            let guard = lock.lock(&ctx);
+            let mut children = Vec::new();
+            for pri in [20u8, 5, 20, 1] {
+                let tx = tx.clone();
+                children.push(ctx.spawn(pri, move |ctx: TaskContext| {
+                    let _guard = lock.lock(&ctx);
+                    tx.send(pri).unwrap();
+                }));
+            }
+            // Let every child park on the lock before releasing it.
+            for _ in 0..8 {
+                ctx.yield_now();
+            }
+            drop(guard);
+            for child in children {
+                child.join(&ctx).unwrap();
+            }
+        });
+    }
+    let order = sched.run();
+    assert_eq!(order.len(), 5);
+    let acquired: Vec<u8> = rx.try_iter().collect();
+    assert_eq!(acquired, vec![1, 5, 20, 20]);
+}
+
+#[test]
+#[file_serial]
+fn semaphore_limits_concurrent_holders() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let permits = Semaphore::new(&ctx, 2);
+            let inside = Event::new(&ctx);
+            let mut children = Vec::new();
+            for i in 0..3 {
+                let tx = tx.clone();
+                children.push(ctx.spawn(10, move |ctx: TaskContext| {
+                    let _permit = permits.acquire(&ctx);
+                    tx.send(format!("enter {i}")).unwrap();
+                    inside.wait(&ctx);
+                    tx.send(format!("leave {i}")).unwrap();
+                }));
+            }
+            for _ in 0..8 {
+                ctx.yield_now();
+            }
+            inside.set(&ctx);
+            for child in children {
+                child.join(&ctx).unwrap();
+            }
+        });
+    }
+    sched.run();
+    let events: Vec<String> = rx.try_iter().collect();
+    assert_eq!(&events[..2], ["enter 0", "enter 1"]);
+    let third = events.iter().position(|e| e == "enter 2").unwrap();
+    assert!(third > 2, "third holder entered early: {events:?}");
+}
+
+#[test]
+#[file_serial]
+fn barrier_releases_all_parties_with_one_leader() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let barrier = Barrier::new(&ctx, 3);
+            let children: Vec<_> = (0..2)
+                .map(|_| {
+                    let tx = tx.clone();
+                    ctx.spawn(10, move |ctx: TaskContext| {
+                        tx.send(barrier.wait(&ctx)).unwrap()
+                    })
+                })
+                .collect();
+            tx.send(barrier.wait(&ctx)).unwrap();
+            for child in children {
+                child.join(&ctx).unwrap();
+            }
+            ctx.syscall(SystemCall::Done);
+        });
+    }
+    sched.run();
+    let leaders: Vec<bool> = rx.try_iter().collect();
+    assert_eq!(leaders.len(), 3);
+    assert_eq!(leaders.iter().filter(|&&l| l).count(), 1);
+}
+
+#[test]
+#[file_serial]
+fn destroyed_objects_are_freed_and_wake_their_waiters() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let lock = Mutex::new(&ctx);
+            let permits = Semaphore::new(&ctx, 1);
+            let barrier = Barrier::new(&ctx, 2);
+            let ready = Event::new(&ctx);
+            let waiter = ctx.spawn(10, move |ctx: TaskContext| ready.wait(&ctx));
+            for _ in 0..4 {
+                ctx.yield_now();
+            }
+            drop(lock.lock(&ctx));
+            lock.destroy(&ctx);
+            permits.destroy(&ctx);
+            barrier.destroy(&ctx);
+            ready.destroy(&ctx);
+            tx.send(waiter.join(&ctx)).unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(sched.sync_count(), 0);
+    match rx.try_recv().unwrap() {
+        Err(TaskError::Panicked(msg)) => assert!(msg.contains("no such event"), "{msg}"),
+        other => panic!("waiter on a destroyed event: {other:?}"),
+    }
+}
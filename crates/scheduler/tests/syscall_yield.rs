@@ -13,10 +13,12 @@ fn tasks_yield_after_syscall() {
 
         let child = unsafe {
             let tx = tx.clone();
-            sched.spawn(move |ctx: TaskContext| {
-                tx.send("child done").unwrap();
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    tx.send("child done").unwrap();
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
 
         unsafe {
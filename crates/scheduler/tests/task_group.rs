@@ -0,0 +1,122 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Channel, Scheduler, TaskError, TaskGroup, TaskId,
+    sync::Event,
+    task::{TaskContext, TaskState},
+};
+use serial_test::file_serial;
+
+#[test]
+#[file_serial]
+fn join_all_returns_values_in_spawn_order() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let mut group = TaskGroup::new(&ctx);
+            for (pri, val) in [(30, 'a'), (10, 'b'), (20, 'c')] {
+                group.spawn(pri, move |_ctx: TaskContext| val);
+            }
+            tx.send(group.join_all()).unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(rx.try_recv().unwrap(), Ok(vec!['a', 'b', 'c']));
+}
+
+#[test]
+#[file_serial]
+fn join_any_claims_members_one_by_one() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let gate = Event::new(&ctx);
+            let mut group = TaskGroup::new(&ctx);
+            let slow = group.spawn(10, move |ctx: TaskContext| {
+                gate.wait(&ctx);
+                1
+            });
+            let fast = group.spawn(10, |_ctx: TaskContext| 2);
+            let first = group.join_any().unwrap();
+            gate.set(&ctx);
+            let second = group.join_any().unwrap();
+            tx.send((first, fast.id(), second, slow.id(), group.join_any()))
+                .unwrap();
+        });
+    }
+    sched.run();
+    let (first, fast, second, slow, rest) = rx.try_recv().unwrap();
+    assert_eq!(first, (fast, Ok(2)));
+    assert_eq!(second, (slow, Ok(1)));
+    assert!(rest.is_none());
+}
+
+#[test]
+#[file_serial]
+fn failing_member_cancels_siblings() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let gate = Event::new(&ctx);
+            let mut group = TaskGroup::new(&ctx);
+            let stuck = group.spawn(10, move |ctx: TaskContext| gate.wait(&ctx));
+            group.spawn(20, |_ctx: TaskContext| panic!("member failed"));
+            tx.send((stuck.id(), group.join_all())).unwrap();
+        });
+    }
+    sched.run();
+    let (stuck, res) = rx.try_recv().unwrap();
+    assert_eq!(res, Err(TaskError::Panicked("member failed".into())));
+    assert_eq!(sched.task_state(stuck), Some(TaskState::Cancelled));
+}
+
+#[test]
+#[file_serial]
+fn cancel_and_failure_cascade_to_descendants() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let gate = Event::new(&ctx);
+            let go = Event::new(&ctx);
+            let ids = Channel::<TaskId>::unbounded(&ctx);
+            // Each subtree is a parent with a child and grandchild parked on
+            // an event nobody sets.
+            let subtree = move |fail: bool| {
+                move |ctx: TaskContext| {
+                    ctx.spawn(10, move |ctx: TaskContext| {
+                        let grandchild = ctx.spawn(10, move |ctx: TaskContext| gate.wait(&ctx));
+                        ids.send(&ctx, ctx.tid).unwrap();
+                        ids.send(&ctx, grandchild.id()).unwrap();
+                        gate.wait(&ctx);
+                    });
+                    if fail {
+                        go.wait(&ctx);
+                        panic!("parent failed");
+                    }
+                    gate.wait(&ctx);
+                }
+            };
+            let cancelled = ctx.spawn(10, subtree(false));
+            let failed = ctx.spawn(10, subtree(true));
+            let descendants: Vec<TaskId> = (0..4).map(|_| ids.recv(&ctx).unwrap()).collect();
+            cancelled.cancel(&ctx);
+            go.set(&ctx);
+            tx.send((
+                descendants,
+                cancelled.join(&ctx),
+                failed.join(&ctx).map_err(|e| e.to_string()),
+            ))
+            .unwrap();
+        });
+    }
+    sched.run();
+    let (descendants, cancelled, failed) = rx.try_recv().unwrap();
+    assert_eq!(cancelled, Err(TaskError::Cancelled));
+    assert!(failed.unwrap_err().contains("parent failed"));
+    for tid in descendants {
+        assert_eq!(sched.task_state(tid), Some(TaskState::Cancelled));
+    }
+}
@@ -0,0 +1,139 @@
+use crossbeam::channel::unbounded;
+use scheduler::{Scheduler, TaskMeta, sync::Event, task::TaskContext};
+use serial_test::file_serial;
+use std::collections::BTreeMap;
+
+#[test]
+#[file_serial]
+fn builder_names_tasks_and_children_inherit_tags() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    let root = unsafe {
+        sched
+            .builder()
+            .name("ingest")
+            .priority(3)
+            .tags([("request_id", "req-7"), ("tenant", "acme")])
+            .spawn(move |ctx: TaskContext| {
+                tx.send(ctx.meta().clone()).unwrap();
+                let child = ctx
+                    .builder()
+                    .name("parse")
+                    .tag("tenant", "other")
+                    .spawn(|ctx: TaskContext| ctx.meta().clone());
+                tx.send(child.join(&ctx).unwrap()).unwrap();
+                let anonymous = ctx.spawn(10, |ctx: TaskContext| ctx.meta().clone());
+                tx.send(anonymous.join(&ctx).unwrap()).unwrap();
+            })
+            .id()
+    };
+    assert_eq!(sched.priority_of(root), Some(3));
+    sched.run();
+
+    let meta = |name: Option<&str>, tenant: &str| TaskMeta {
+        name: name.map(String::from),
+        tags: BTreeMap::from([
+            ("request_id".to_string(), "req-7".to_string()),
+            ("tenant".to_string(), tenant.to_string()),
+        ]),
+    };
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![
+            meta(Some("ingest"), "acme"),
+            meta(Some("parse"), "other"),
+            meta(None, "acme"),
+        ]
+    );
+}
+
+#[test]
+#[file_serial]
+fn task_locals_are_private_to_each_task() {
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let first = ctx.set_local(1u32);
+            let second = ctx.set_local(2u32);
+            tx.send(format!("{first:?} {second:?}")).unwrap();
+            // Clones of the context share the task's slots.
+            ctx.clone()
+                .with_local(|n: Option<&mut u32>| *n.unwrap() += 40);
+            ctx.set_local(String::from("corr-1"));
+            let child = ctx.spawn(10, |ctx: TaskContext| ctx.take_local::<u32>());
+            tx.send(format!("{:?}", child.join(&ctx).unwrap())).unwrap();
+            let taken = (ctx.take_local::<u32>(), ctx.take_local::<u32>());
+            tx.send(format!("{taken:?}")).unwrap();
+            tx.send(ctx.with_local(|s: Option<&mut String>| s.cloned().unwrap()))
+                .unwrap();
+        });
+    }
+    sched.run();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec!["None Some(1)", "None", "(Some(42), None)", "corr-1"]
+    );
+}
+
+#[test]
+#[file_serial]
+fn snapshot_reports_names_and_tags() {
+    let mut sched = Scheduler::simulated(0);
+    unsafe {
+        sched
+            .builder()
+            .name("server")
+            .tag("request_id", "req-9")
+            .spawn(|ctx: TaskContext| {
+                let never = Event::new(&ctx);
+                let child = ctx
+                    .builder()
+                    .name("handler")
+                    .spawn(move |ctx: TaskContext| never.wait(&ctx));
+                child.join(&ctx).unwrap();
+            });
+    }
+    sched.run();
+    let tags = BTreeMap::from([("request_id".to_string(), "req-9".to_string())]);
+    let names: Vec<_> = sched
+        .snapshot()
+        .tasks
+        .into_iter()
+        .map(|t| (t.name, t.tags))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            (Some("server".into()), tags.clone()),
+            (Some("handler".into()), tags),
+        ]
+    );
+}
+
+#[test]
+#[file_serial]
+fn task_bodies_run_inside_their_span() {
+    // Coroutines run on `may` worker threads, so the subscriber must be global.
+    tracing::subscriber::set_global_default(tracing_subscriber::registry()).unwrap();
+    let mut sched = Scheduler::new();
+    let (tx, rx) = unbounded();
+    for name in ["first", "second"] {
+        let tx = tx.clone();
+        unsafe {
+            sched.builder().name(name).spawn(move |ctx: TaskContext| {
+                tx.send(tracing::Span::current() == *ctx.span()).unwrap();
+                ctx.yield_now();
+                tx.send(tracing::Span::current() == *ctx.span()).unwrap();
+                let child = ctx.spawn(10, |_ctx: TaskContext| ());
+                child.join(&ctx).unwrap();
+                tx.send(tracing::Span::current() == *ctx.span()).unwrap();
+            });
+        }
+    }
+    sched.run();
+    drop(tx);
+    let seen: Vec<bool> = rx.iter().collect();
+    assert_eq!(seen, [true; 6]);
+    assert!(tracing::Span::current().is_none());
+}
@@ -0,0 +1,185 @@
+use scheduler::{
+    Channel, CronSchedule, HybridClock, MissedTicks, Schedule, Scheduler, TimerSpec,
+    task::TaskContext,
+};
+use serial_test::file_serial;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Block the calling task for `dur` of virtual time.
+fn pause(ctx: &TaskContext, dur: Duration) {
+    let never = Channel::<()>::unbounded(ctx);
+    let _ = never.recv_timeout(ctx, dur);
+}
+
+#[test]
+#[file_serial]
+fn periodic_timer_runs_until_cancelled() {
+    let mut sched = Scheduler::new();
+    let runs = Arc::new(AtomicU64::new(0));
+    let id = Arc::new(AtomicU64::new(0));
+    let (counter, own_id) = (runs.clone(), id.clone());
+    let timer = unsafe {
+        sched.spawn_every(Duration::from_secs(10), move |ctx: TaskContext| {
+            if counter.fetch_add(1, Ordering::Relaxed) + 1 == 3 {
+                ctx.cancel_timer(own_id.load(Ordering::Relaxed));
+            }
+        })
+    };
+    id.store(timer, Ordering::Relaxed);
+    let before = sched.timers();
+    assert_eq!(before.len(), 1);
+    assert_eq!(before[0].next_at, Some(Duration::from_secs(10)));
+
+    sched.run();
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+    assert_eq!(sched.snapshot().now, Duration::from_secs(30));
+    assert!(sched.timers().is_empty());
+}
+
+#[test]
+#[file_serial]
+fn missed_ticks_follow_the_policy() {
+    // Ticks every 10s; the first run takes 25s and misses the ticks at 20s
+    // and 30s. A one-shot timer cancels the periodic one at 45s.
+    let expected = [
+        (MissedTicks::Skip, 2),
+        (MissedTicks::CatchUp, 4),
+        (MissedTicks::Coalesce, 3),
+    ];
+    for (policy, runs) in expected {
+        let mut sched = Scheduler::new();
+        let count = Arc::new(AtomicU64::new(0));
+        let counter = count.clone();
+        let spec = TimerSpec::every(Duration::from_secs(10))
+            .missed(policy)
+            .name("compaction");
+        let timer = unsafe {
+            sched.spawn_timer(spec, move |ctx: TaskContext| {
+                if counter.fetch_add(1, Ordering::Relaxed) == 0 {
+                    pause(&ctx, Duration::from_secs(25));
+                }
+            })
+        };
+        unsafe {
+            sched.spawn_at(Duration::from_secs(45), move |ctx: TaskContext| {
+                ctx.cancel_timer(timer)
+            });
+        }
+        sched.run();
+        assert_eq!(count.load(Ordering::Relaxed), runs, "{policy:?}");
+    }
+}
+
+#[test]
+#[file_serial]
+fn clock_jumps_skip_ticks_without_counting_each_one() {
+    let mut sched = Scheduler::with_clock(Box::new(HybridClock::new()));
+    let timer = unsafe { sched.spawn_every(Duration::from_millis(1), |_ctx: TaskContext| {}) };
+    let (handle, thread) = sched.serve_in_background();
+    let decade = Duration::from_secs(10 * 365 * 86_400);
+    handle.advance_clock(decade).unwrap();
+
+    let start = Instant::now();
+    let ticks = loop {
+        let timers = handle.snapshot().unwrap().timers;
+        let ticks = timers[0].fired + timers[0].skipped;
+        if ticks >= decade.as_millis() as u64 || start.elapsed() > Duration::from_secs(5) {
+            break ticks;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert!(ticks >= decade.as_millis() as u64, "only {ticks} ticks");
+    handle.cancel_timer(timer).unwrap();
+    handle.shutdown();
+    thread.join().unwrap();
+}
+
+#[test]
+#[file_serial]
+fn timers_show_up_in_snapshots() {
+    let mut sched = Scheduler::new();
+    let spec = TimerSpec::every(Duration::from_secs(5))
+        .missed(MissedTicks::Coalesce)
+        .name("probe");
+    let probe = unsafe { sched.spawn_timer(spec, |_ctx: TaskContext| {}) };
+    let once = unsafe {
+        sched.spawn_at(Duration::from_secs(12), move |ctx: TaskContext| {
+            ctx.cancel_timer(probe)
+        })
+    };
+
+    let timers = sched.snapshot().timers;
+    assert_eq!(timers, sched.timers());
+    assert_eq!(timers.len(), 2);
+    assert_eq!(
+        (timers[0].id, timers[0].name.as_deref()),
+        (probe, Some("probe"))
+    );
+    assert_eq!(timers[0].schedule, Schedule::Every(Duration::from_secs(5)));
+    assert_eq!(timers[0].missed, MissedTicks::Coalesce);
+    assert_eq!(timers[0].next_at, Some(Duration::from_secs(5)));
+    assert_eq!((timers[0].fired, timers[0].running), (0, None));
+    assert_eq!(timers[1].id, once);
+    assert_eq!(timers[1].next_at, Some(Duration::from_secs(12)));
+
+    sched.run();
+    assert!(sched.snapshot().timers.is_empty());
+    assert_eq!(sched.snapshot().now, Duration::from_secs(12));
+}
+
+#[test]
+#[file_serial]
+fn cron_timer_fires_on_matching_minutes() {
+    let mut sched = Scheduler::new();
+    let runs = Arc::new(AtomicU64::new(0));
+    let counter = runs.clone();
+    let timer = unsafe {
+        sched
+            .spawn_cron("*/15 * * * *", move |_ctx: TaskContext| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap()
+    };
+    unsafe {
+        sched.spawn_at(Duration::from_secs(3600), move |ctx: TaskContext| {
+            ctx.cancel_timer(timer)
+        });
+    }
+    sched.run();
+    assert_eq!(runs.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn cron_expressions_match_calendar_minutes() {
+    let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+    // Friday 2024-01-05 10:00 UTC to Monday 2024-01-08 09:30 UTC.
+    let weekdays = CronSchedule::parse("30 9 * * 1-5").unwrap();
+    assert_eq!(
+        weekdays.next_after(at(1_704_448_800)),
+        Some(at(1_704_706_200))
+    );
+    // The next February 29 after March 2024 is in 2028.
+    let leap = CronSchedule::parse("0 0 29 2 *").unwrap();
+    assert_eq!(leap.next_after(at(1_709_251_200)), Some(at(1_835_395_200)));
+    let hourly = CronSchedule::parse("@hourly").unwrap();
+    assert_eq!(hourly.next_after(at(3_600)), Some(at(7_200)));
+    assert_eq!(
+        CronSchedule::parse("0 0 30 2 *")
+            .unwrap()
+            .next_after(SystemTime::now()),
+        None
+    );
+
+    for bad in [
+        "* * * *",
+        "60 * * * *",
+        "*/0 * * * *",
+        "5-1 * * * *",
+        "a * * * *",
+    ] {
+        assert!(CronSchedule::parse(bad).is_err(), "{bad}");
+    }
+}
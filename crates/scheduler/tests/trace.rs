@@ -0,0 +1,102 @@
+use scheduler::{
+    Channel, JsonLinesSink, MemorySink, Scheduler, TraceCall, TraceEvent, TraceOutcome, TraceReply,
+    replay_trace,
+    task::{TaskContext, TaskState},
+    trace::read_json_lines,
+};
+use serial_test::file_serial;
+use std::fs::File;
+use std::io::BufReader;
+
+/// A parent spawns a producer and a consumer sharing a channel, then joins
+/// both. `extra_yield` perturbs the consumer to provoke a divergence.
+fn pipeline(sched: &mut Scheduler, extra_yield: bool) {
+    unsafe {
+        sched.spawn(move |ctx: TaskContext| {
+            let chan = Channel::<u32>::bounded(&ctx, 1);
+            let producer = ctx.spawn(10, move |ctx: TaskContext| {
+                for i in 0..3 {
+                    chan.send(&ctx, i).unwrap();
+                }
+                chan.close(&ctx);
+            });
+            let consumer = ctx.spawn(10, move |ctx: TaskContext| {
+                if extra_yield {
+                    ctx.yield_now();
+                }
+                while chan.recv(&ctx).is_ok() {}
+            });
+            producer.join(&ctx).unwrap();
+            consumer.join(&ctx).unwrap();
+        });
+    }
+}
+
+fn record(seed: u64) -> (Vec<TraceEvent>, Vec<u64>) {
+    let sink = MemorySink::new();
+    let mut sched = Scheduler::simulated(seed);
+    sched.set_trace_sink(Box::new(sink.clone()));
+    pipeline(&mut sched, false);
+    let done = sched.run();
+    (sink.events(), done)
+}
+
+#[test]
+#[file_serial]
+fn recorded_trace_replays_to_same_outcome() {
+    for seed in 0..4 {
+        let (trace, done) = record(seed);
+        let exited: Vec<_> = trace
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Exited { tid, state, .. } => Some((*tid, *state)),
+                TraceEvent::Syscall { .. } => None,
+            })
+            .collect();
+        let finished: Vec<_> = done.iter().map(|&tid| (tid, TaskState::Finished)).collect();
+        assert_eq!(exited, finished);
+        assert!(trace.iter().any(|event| matches!(
+            event,
+            TraceEvent::Syscall {
+                call: TraceCall::ChannelOpen { cap: Some(1) },
+                outcome: TraceOutcome::Replied(TraceReply::ChannelOpened(_)),
+                ..
+            }
+        )));
+        assert_eq!(replay_trace(&trace, |sched| pipeline(sched, false)), Ok(()));
+    }
+}
+
+#[test]
+#[file_serial]
+fn json_lines_trace_round_trips() {
+    let path = std::env::temp_dir().join(format!("scheduler-trace-{}.jsonl", std::process::id()));
+    let mut sched = Scheduler::simulated(3);
+    sched.set_trace_sink(Box::new(JsonLinesSink::new(File::create(&path).unwrap())));
+    pipeline(&mut sched, false);
+    sched.run();
+    drop(sched);
+
+    let trace = read_json_lines(BufReader::new(File::open(&path).unwrap())).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(trace, record(3).0);
+    assert_eq!(replay_trace(&trace, |sched| pipeline(sched, false)), Ok(()));
+}
+
+#[test]
+#[file_serial]
+fn replay_reports_first_divergence() {
+    let (trace, _) = record(5);
+    let err = replay_trace(&trace, |sched| pipeline(sched, true)).unwrap_err();
+    assert!(err.index < trace.len());
+    assert_eq!(err.expected.as_ref(), trace.get(err.index));
+    assert_ne!(err.expected, err.actual);
+
+    // A truncated trace diverges where the recording stops, as the replay
+    // keeps running past it.
+    let cut = trace.len() / 2;
+    let err = replay_trace(&trace[..cut], |sched| pipeline(sched, false)).unwrap_err();
+    assert_eq!(err.index, cut);
+    assert_eq!(err.expected, None);
+    assert!(err.actual.is_some());
+}
@@ -0,0 +1,121 @@
+use scheduler::{Scheduler, SystemCall, task::TaskContext};
+use serial_test::file_serial;
+use std::time::{Duration, Instant};
+
+/// Burn CPU without yielding to the scheduler.
+fn spin(dur: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < dur {
+        std::hint::spin_loop();
+    }
+}
+
+#[test]
+#[file_serial]
+fn stalled_task_is_reported_and_others_keep_running() {
+    let mut sched = Scheduler::new();
+    sched.set_stall_timeout(Duration::from_millis(50));
+    let hog = unsafe {
+        sched
+            .builder()
+            .name("hog")
+            .spawn(|ctx: TaskContext| {
+                ctx.call("crunch", |ctx| {
+                    spin(Duration::from_millis(300));
+                    ctx.syscall(SystemCall::Log("crunched".into()));
+                });
+            })
+            .id()
+    };
+    let polite = unsafe {
+        sched
+            .spawn(|ctx: TaskContext| {
+                for _ in 0..3 {
+                    ctx.yield_now();
+                }
+            })
+            .id()
+    };
+    let done = sched.run();
+
+    assert!(done.contains(&hog) && done.contains(&polite));
+    let stalls = sched.stalls();
+    assert_eq!(stalls.len(), 1);
+    assert_eq!(stalls[0].tid, hog);
+    assert_eq!(stalls[0].name.as_deref(), Some("hog"));
+    assert_eq!(stalls[0].call_stack, vec!["crunch"]);
+    assert!(stalls[0].stalled_for.unwrap() >= Duration::from_millis(50));
+    assert_eq!(sched.snapshot().stalls, stalls);
+}
+
+#[test]
+#[file_serial]
+fn tasks_that_yield_in_time_are_not_reported() {
+    let mut sched = Scheduler::new();
+    sched.set_stall_timeout(Duration::from_millis(200));
+    unsafe {
+        sched.spawn(|ctx: TaskContext| {
+            for _ in 0..5 {
+                spin(Duration::from_millis(5));
+                ctx.yield_now();
+            }
+        });
+    }
+    sched.run();
+    assert!(sched.stalls().is_empty());
+}
+
+#[test]
+#[file_serial]
+fn only_the_latest_stalls_are_kept() {
+    let mut sched = Scheduler::new();
+    sched.set_stall_timeout(Duration::from_millis(50));
+    sched.set_stall_history(1);
+    unsafe {
+        sched.spawn(|ctx: TaskContext| {
+            for frame in ["first", "second"] {
+                ctx.call(frame, |ctx| {
+                    spin(Duration::from_millis(200));
+                    ctx.yield_now();
+                });
+            }
+        })
+    };
+    sched.run();
+
+    let stalls = sched.stalls();
+    assert_eq!(stalls.len(), 1);
+    assert_eq!(stalls[0].call_stack, vec!["second"]);
+    assert!(stalls[0].stalled_for.is_some());
+}
+
+/// CPU time used by the calling thread.
+fn thread_cpu() -> Duration {
+    use nix::sys::resource::{UsageWho, getrusage};
+    let usage = getrusage(UsageWho::RUSAGE_THREAD).unwrap();
+    let (user, sys) = (usage.user_time(), usage.system_time());
+    Duration::new(user.tv_sec() as u64, user.tv_usec() as u32 * 1000)
+        + Duration::new(sys.tv_sec() as u64, sys.tv_usec() as u32 * 1000)
+}
+
+#[test]
+#[file_serial]
+fn loop_sleeps_while_more_tasks_stall_than_are_reported() {
+    let mut sched = Scheduler::new();
+    sched.set_stall_timeout(Duration::from_millis(20));
+    sched.set_max_stalled(1);
+    for _ in 0..3 {
+        unsafe {
+            sched.spawn(|ctx: TaskContext| {
+                std::thread::sleep(Duration::from_millis(300));
+                ctx.yield_now();
+            });
+        }
+    }
+    let (start, cpu) = (Instant::now(), thread_cpu());
+    sched.run();
+    let (wall, cpu) = (start.elapsed(), thread_cpu() - cpu);
+
+    assert!(cpu < wall / 4, "scheduler used {cpu:?} of CPU in {wall:?}");
+    assert!(!sched.stalls().is_empty());
+}
@@ -0,0 +1,113 @@
+use crossbeam::channel::unbounded;
+use scheduler::{
+    Channel, ChannelError, Scheduler,
+    task::{TaskContext, TaskState},
+};
+use serial_test::file_serial;
+use std::time::{Duration, Instant};
+
+#[test]
+#[file_serial]
+fn workers_run_joins_and_channels_to_completion() {
+    let mut sched = Scheduler::with_workers(4);
+    assert_eq!(sched.workers(), 4);
+    let (tx, rx) = unbounded();
+    for i in 0..32u64 {
+        let tx = tx.clone();
+        unsafe {
+            sched.spawn_with_priority((i % 4) as u8, move |ctx: TaskContext| {
+                let chan = Channel::<u64>::bounded(&ctx, 1);
+                let child = ctx.spawn(10, move |ctx: TaskContext| {
+                    for n in 0..4 {
+                        chan.send(&ctx, i * 10 + n).unwrap();
+                    }
+                    chan.close(&ctx);
+                });
+                let mut sum = 0;
+                while let Ok(n) = chan.recv(&ctx) {
+                    sum += n;
+                }
+                child.join(&ctx).unwrap();
+                tx.send(sum).unwrap();
+            });
+        }
+    }
+    let order = sched.run();
+    assert_eq!(order.len(), 64);
+    let total: u64 = rx.try_iter().sum();
+    assert_eq!(total, (0..32u64).map(|i| 40 * i + 6).sum::<u64>());
+    for tid in order {
+        assert_eq!(sched.task_state(tid), Some(TaskState::Finished));
+    }
+}
+
+/// CPU time used by the whole process, worker threads included.
+fn process_cpu() -> Duration {
+    use nix::sys::resource::{UsageWho, getrusage};
+    let usage = getrusage(UsageWho::RUSAGE_SELF).unwrap();
+    let (user, sys) = (usage.user_time(), usage.system_time());
+    Duration::new(user.tv_sec() as u64, user.tv_usec() as u32 * 1000)
+        + Duration::new(sys.tv_sec() as u64, sys.tv_usec() as u32 * 1000)
+}
+
+#[test]
+#[file_serial]
+fn idle_workers_park_instead_of_polling() {
+    let mut sched = Scheduler::with_workers(4);
+    unsafe {
+        sched.spawn(|ctx: TaskContext| {
+            // Hold a `may` worker without yielding; the dispatch loops have
+            // nothing to do meanwhile.
+            std::thread::sleep(Duration::from_millis(500));
+            ctx.yield_now();
+        });
+    }
+    let (start, cpu) = (Instant::now(), process_cpu());
+    sched.run();
+    let (wall, cpu) = (start.elapsed(), process_cpu() - cpu);
+    assert!(cpu < wall / 20, "workers used {cpu:?} of CPU in {wall:?}");
+}
+
+#[test]
+#[file_serial]
+fn workers_advance_virtual_time_when_idle() {
+    let mut sched = Scheduler::with_workers(4);
+    let (tx, rx) = unbounded();
+    for _ in 0..8 {
+        let tx = tx.clone();
+        unsafe {
+            sched.spawn(move |ctx: TaskContext| {
+                let chan = Channel::<()>::unbounded(&ctx);
+                tx.send(chan.recv_timeout(&ctx, Duration::from_secs(60)))
+                    .unwrap();
+            });
+        }
+    }
+    let start = Instant::now();
+    sched.run();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let results: Vec<_> = rx.try_iter().collect();
+    assert_eq!(results, vec![Err(ChannelError::TimedOut); 8]);
+}
+
+#[cfg(not(feature = "async-io"))]
+#[test]
+#[file_serial]
+fn workers_wake_io_waiters() {
+    let mut sched = Scheduler::with_workers(2);
+    let io_tx = sched.io_handle();
+    let waiter = unsafe {
+        sched
+            .spawn(|ctx: TaskContext| {
+                ctx.syscall(scheduler::SystemCall::IoWait(7));
+                ctx.syscall(scheduler::SystemCall::Done);
+            })
+            .id()
+    };
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        io_tx.send(7).unwrap();
+    });
+    let order = sched.run();
+    assert_eq!(order, vec![waiter]);
+}
@@ -11,18 +11,22 @@ fn yield_order() {
         let handle = unsafe { sched.start(s, barrier.clone()) };
         let a = unsafe {
             let tb = task_barrier.clone();
-            sched.spawn(move |ctx: TaskContext| {
-                tb.wait();
-                ctx.yield_now();
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    tb.wait();
+                    ctx.yield_now();
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
         let b = unsafe {
             let tb = task_barrier.clone();
-            sched.spawn(move |ctx: TaskContext| {
-                tb.wait();
-                ctx.syscall(SystemCall::Done);
-            })
+            sched
+                .spawn(move |ctx: TaskContext| {
+                    tb.wait();
+                    ctx.syscall(SystemCall::Done);
+                })
+                .id()
         };
         barrier.wait();
         task_barrier.wait();